@@ -15,9 +15,8 @@
 //!   wstest -m fuzzingclient -s /config/fuzzingclient.json
 //! ```
 
-use rsws::{Config, Connection, HandshakeRequest, HandshakeResponse, Message, Role};
+use rsws::{Config, Message};
 use std::error::Error;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{TcpListener, TcpStream};
 
 const ADDR: &str = "127.0.0.1:9001";
@@ -48,35 +47,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
     }
 }
 
-async fn handle_connection(mut stream: TcpStream) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let mut reader = BufReader::new(&mut stream);
-    let mut request_bytes = Vec::new();
-
-    loop {
-        let mut line = String::new();
-        let bytes_read = reader.read_line(&mut line).await?;
-        if bytes_read == 0 {
-            return Ok(());
-        }
-        request_bytes.extend_from_slice(line.as_bytes());
-        if line == "\r\n" {
-            break;
-        }
-    }
-
-    let request = HandshakeRequest::parse(&request_bytes)?;
+async fn handle_connection(stream: TcpStream) -> Result<(), Box<dyn Error + Send + Sync>> {
     let mut config = Config::server();
-    request.validate_with_config(&config)?;
-
-    let response = HandshakeResponse::from_request(&request);
-    let mut response_bytes = Vec::new();
-    let _ = response.write(&mut response_bytes);
-    stream.write_all(&response_bytes).await?;
-
     config.limits.max_message_size = 64 * 1024 * 1024;
     config.limits.max_frame_size = 64 * 1024 * 1024;
 
-    let mut conn = Connection::new(stream, Role::Server, config);
+    let mut conn = match rsws::server::accept(stream, config).await {
+        Ok((conn, _request)) => conn,
+        // The fuzzing client probes with connections it closes immediately.
+        Err(rsws::Error::ConnectionClosed(_)) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
 
     while conn.is_open() {
         match conn.recv().await? {
@@ -1,10 +1,11 @@
 //! Example: Integrating rsws with an Axum HTTP server.
 //!
 //! This demonstrates how to use rsws as the WebSocket protocol handler
-//! while letting Axum handle HTTP routing and the upgrade handshake.
+//! while letting Axum handle HTTP routing. The `WebSocketUpgrade` extractor
+//! validates the upgrade request and hands the connection to a handler.
 //!
 //! Run with:
-//!   cargo run --example axum_server
+//!   cargo run --example axum_server --features axum
 //!
 //! Test with the built-in client example:
 //!   cargo run --example client
@@ -13,11 +14,11 @@
 
 use axum::Router;
 use axum::extract::Request;
-use axum::http::{StatusCode, header};
 use axum::response::{Html, IntoResponse, Response};
 use axum::routing::get;
-use hyper_util::rt::TokioIo;
-use rsws::{CloseCode, Config, Connection, Message, Role, compute_accept_key};
+use rsws::integrations::axum::{UpgradedConnection, WebSocketUpgrade};
+use rsws::integrations::hyper::{is_upgrade_request, upgrade};
+use rsws::{CloseCode, Config, Message};
 use std::error::Error;
 
 const ADDR: &str = "127.0.0.1:9001";
@@ -40,15 +41,9 @@ async fn main() -> Result<(), Box<dyn Error>> {
 /// Routes "/" — serves the HTML test page for normal browser requests,
 /// or upgrades to WebSocket when the client sends upgrade headers
 /// (e.g. `cargo run --example client` connects to "/").
-async fn root_handler(req: Request) -> Response {
-    let is_upgrade = req
-        .headers()
-        .get(header::UPGRADE)
-        .and_then(|v| v.to_str().ok())
-        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
-
-    if is_upgrade {
-        return ws_handler(req).await;
+async fn root_handler(mut req: Request) -> Response {
+    if is_upgrade_request(&req) {
+        return upgrade(&mut req, Config::server(), run_session);
     }
 
     Html(
@@ -84,74 +79,17 @@ async fn root_handler(req: Request) -> Response {
     .into_response()
 }
 
-/// Handles the WebSocket upgrade using rsws.
-///
-/// The flow:
-///   1. Validate WebSocket upgrade headers from the HTTP request.
-///   2. Compute `Sec-WebSocket-Accept` using rsws's `compute_accept_key`.
-///   3. Return a `101 Switching Protocols` response to complete the handshake.
-///   4. Spawn a task that awaits the upgraded raw I/O stream and wraps it in
-///      an rsws `Connection` for full RFC 6455 message handling.
-async fn ws_handler(mut req: Request) -> Response {
-    // --- Step 1: Validate upgrade headers ---
-    let headers = req.headers();
-
-    let is_upgrade = headers
-        .get(header::UPGRADE)
-        .and_then(|v| v.to_str().ok())
-        .is_some_and(|v| v.eq_ignore_ascii_case("websocket"));
-
-    if !is_upgrade {
-        return (StatusCode::BAD_REQUEST, "Not a WebSocket upgrade request").into_response();
-    }
-
-    let has_connection_upgrade = headers
-        .get(header::CONNECTION)
-        .and_then(|v| v.to_str().ok())
-        .is_some_and(|v| v.to_lowercase().contains("upgrade"));
+/// Handles "/ws": the extractor rejects requests that are not valid
+/// WebSocket upgrades, and `on_upgrade` returns the 101 response and runs the
+/// session once the connection is switched over.
+async fn ws_handler(ws: WebSocketUpgrade) -> Response {
+    ws.with_config(Config::server()).on_upgrade(run_session)
+}
 
-    if !has_connection_upgrade {
-        return (StatusCode::BAD_REQUEST, "Missing Connection: Upgrade").into_response();
+async fn run_session(conn: UpgradedConnection) {
+    if let Err(e) = handle_websocket(conn).await {
+        eprintln!("WebSocket session error: {}", e);
     }
-
-    let sec_key = match headers
-        .get("sec-websocket-key")
-        .and_then(|v| v.to_str().ok())
-    {
-        Some(key) => key.to_owned(),
-        None => {
-            return (StatusCode::BAD_REQUEST, "Missing Sec-WebSocket-Key").into_response();
-        }
-    };
-
-    // --- Step 2: Compute the accept key with rsws ---
-    let accept_key = compute_accept_key(&sec_key);
-
-    // --- Step 3: Spawn the upgrade task ---
-    //
-    // `hyper::upgrade::on` consumes the request body and resolves once the
-    // HTTP layer hands over the raw bidirectional byte stream (`Upgraded`).
-    // We wrap it in `TokioIo` so it implements `tokio::io::AsyncRead + AsyncWrite`.
-    tokio::spawn(async move {
-        match hyper::upgrade::on(&mut req).await {
-            Ok(upgraded) => {
-                let io = TokioIo::new(upgraded);
-                if let Err(e) = handle_websocket(io).await {
-                    eprintln!("WebSocket session error: {}", e);
-                }
-            }
-            Err(e) => eprintln!("HTTP upgrade failed: {}", e),
-        }
-    });
-
-    // --- Step 4: Return 101 to complete the handshake ---
-    Response::builder()
-        .status(StatusCode::SWITCHING_PROTOCOLS)
-        .header(header::UPGRADE, "websocket")
-        .header(header::CONNECTION, "Upgrade")
-        .header("Sec-WebSocket-Accept", accept_key)
-        .body(axum::body::Body::empty())
-        .unwrap()
 }
 
 /// Echo handler powered by rsws.
@@ -160,11 +98,8 @@ async fn ws_handler(mut req: Request) -> Response {
 /// its type, and send a response. Ping/Pong is handled automatically by
 /// `Connection::recv`.
 async fn handle_websocket(
-    io: TokioIo<hyper::upgrade::Upgraded>,
+    mut conn: UpgradedConnection,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let config = Config::server();
-    let mut conn = Connection::new(io, Role::Server, config);
-
     println!("  WebSocket connection established");
 
     while conn.is_open() {
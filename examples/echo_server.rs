@@ -3,10 +3,11 @@
 //! Run with: cargo run --example echo_server
 //! Then connect with: cargo run --example client
 
-use rsws::{Config, Connection, HandshakeRequest, HandshakeResponse, Message, Role};
+use rsws::server::ServerStream;
+use rsws::{Config, Connection, Message, Server};
 use std::error::Error;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{TcpListener, TcpStream};
+use std::time::Duration;
+use tokio::net::TcpListener;
 
 const ADDR: &str = "127.0.0.1:9001";
 
@@ -16,49 +17,33 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let listener = TcpListener::bind(ADDR).await?;
 
-    loop {
-        let (stream, addr) = listener.accept().await?;
-        println!("New connection from: {}", addr);
+    // Ctrl+C closes every live connection with 1001 Going Away.
+    Server::new(Config::server())
+        .route("/", |conn, _| run_session(conn))
+        .with_max_connections(1024)
+        .with_shutdown_timeout(Duration::from_secs(5))
+        .serve_with_shutdown(listener, async {
+            let _ = tokio::signal::ctrl_c().await;
+            println!("Shutting down");
+        })
+        .await;
+    Ok(())
+}
 
-        tokio::spawn(async move {
-            if let Err(e) = handle_connection(stream).await {
-                eprintln!("Connection error from {}: {}", addr, e);
-            }
-        });
+async fn run_session(conn: Connection<ServerStream>) {
+    if let Err(e) = handle_connection(conn).await {
+        eprintln!("Connection error: {}", e);
     }
 }
 
-async fn handle_connection(mut stream: TcpStream) -> Result<(), Box<dyn Error + Send + Sync>> {
-    // Step 1: Read HTTP upgrade request
-    let mut reader = BufReader::new(&mut stream);
-    let mut request_bytes = Vec::new();
-
-    loop {
-        let mut line = String::new();
-        reader.read_line(&mut line).await?;
-        request_bytes.extend_from_slice(line.as_bytes());
-        if line == "\r\n" {
-            break;
-        }
+async fn handle_connection(
+    mut conn: Connection<ServerStream>,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    if let Some(request) = conn.request() {
+        println!("  Handshake complete for path: {}", request.path);
     }
 
-    // Step 2: Parse and validate handshake request
-    let request = HandshakeRequest::parse(&request_bytes)?;
-    let config = Config::server();
-    request.validate_with_config(&config)?;
-    println!("  Handshake request for path: {}", request.path);
-
-    // Step 3: Generate and send handshake response
-    let response = HandshakeResponse::from_request(&request);
-    let mut response_bytes = Vec::new();
-    let _ = response.write(&mut response_bytes);
-    stream.write_all(&response_bytes).await?;
-    println!("  Handshake complete");
-
-    // Step 4: Create WebSocket connection
-    let mut conn = Connection::new(stream, Role::Server, config);
-
-    // Step 5: Echo loop - handle messages
+    // Echo loop - handle messages
     while conn.is_open() {
         match conn.recv().await? {
             Some(Message::Text(text)) => {
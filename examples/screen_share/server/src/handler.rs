@@ -1,6 +1,5 @@
-use rsws::{Config, Connection, HandshakeRequest, HandshakeResponse, Message, Role};
+use rsws::{Config, Message};
 use std::error::Error;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 use tokio::sync::broadcast;
 
@@ -8,35 +7,14 @@ use crate::room::{RoomManager, RoomMessage};
 use crate::types::{ClientMessage, ServerMessage};
 
 pub async fn handle_connection(
-    mut stream: TcpStream,
+    stream: TcpStream,
     room_manager: RoomManager,
     addr: std::net::SocketAddr,
 ) -> Result<(), Box<dyn Error + Send + Sync>> {
-    let mut reader = BufReader::new(&mut stream);
-    let mut request_bytes = Vec::new();
-
-    loop {
-        let mut line = String::new();
-        reader.read_line(&mut line).await?;
-        request_bytes.extend_from_slice(line.as_bytes());
-        if line == "\r\n" {
-            break;
-        }
-    }
-
-    let request = HandshakeRequest::parse(&request_bytes)?;
-    let config = Config::server();
-    request.validate_with_config(&config)?;
-
-    let response = HandshakeResponse::from_request(&request);
-    let mut response_bytes = Vec::new();
-    let _ = response.write(&mut response_bytes);
-    stream.write_all(&response_bytes).await?;
-
+    // Read the upgrade request, validate it and send the 101 response
+    let (mut conn, _request) = rsws::server::accept(stream, Config::server()).await?;
     println!("  [{}] Handshake complete", addr);
 
-    let mut conn = Connection::new(stream, Role::Server, config);
-
     let mut username: Option<String> = None;
     let mut room_id: Option<String> = None;
     let mut rx: Option<broadcast::Receiver<RoomMessage>> = None;
@@ -1,16 +1,15 @@
-use rsws::{CloseCode, Config, Connection, HandshakeResponse, Message, Role, compute_accept_key};
+use rsws::client::ClientBuilder;
+use rsws::{CloseCode, Message};
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 use tokio::sync::Semaphore;
 use tokio::task::JoinSet;
 use tokio::time::timeout;
 
 const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
-const MAX_HEADER_SIZE: usize = 8192;
 const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 30;
 
 struct ClientMetrics {
@@ -276,54 +275,37 @@ fn parse_args() -> TestConfig {
     let mut i = 1;
     while i < args.len() {
         match args[i].as_str() {
-            "-h" | "--host" => {
-                if i + 1 < args.len() {
-                    host = args[i + 1].clone();
-                    i += 1;
-                }
+            "-h" | "--host" if i + 1 < args.len() => {
+                host = args[i + 1].clone();
+                i += 1;
             }
-            "-p" | "--port" => {
-                if i + 1 < args.len() {
-                    port = args[i + 1].parse().unwrap_or(9001);
-                    i += 1;
-                }
+            "-p" | "--port" if i + 1 < args.len() => {
+                port = args[i + 1].parse().unwrap_or(9001);
+                i += 1;
             }
-            "-c" | "--clients" => {
-                if i + 1 < args.len() {
-                    num_clients = args[i + 1].parse().unwrap_or(1000);
-                    i += 1;
-                }
+            "-c" | "--clients" if i + 1 < args.len() => {
+                num_clients = args[i + 1].parse().unwrap_or(1000);
+                i += 1;
             }
-            "-m" | "--messages" => {
-                if i + 1 < args.len() {
-                    messages_per_client = args[i + 1].parse().unwrap_or(100);
-                    i += 1;
-                }
+            "-m" | "--messages" if i + 1 < args.len() => {
+                messages_per_client = args[i + 1].parse().unwrap_or(100);
+                i += 1;
             }
-            "-s" | "--size" => {
-                if i + 1 < args.len() {
-                    message_size = args[i + 1].parse().unwrap_or(128);
-                    i += 1;
-                }
+            "-s" | "--size" if i + 1 < args.len() => {
+                message_size = args[i + 1].parse().unwrap_or(128);
+                i += 1;
             }
-            "--max-concurrent" => {
-                if i + 1 < args.len() {
-                    max_concurrent = args[i + 1].parse().unwrap_or(200);
-                    i += 1;
-                }
+            "--max-concurrent" if i + 1 < args.len() => {
+                max_concurrent = args[i + 1].parse().unwrap_or(200);
+                i += 1;
             }
-            "--connect-timeout" => {
-                if i + 1 < args.len() {
-                    connect_timeout_secs =
-                        args[i + 1].parse().unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS);
-                    i += 1;
-                }
+            "--connect-timeout" if i + 1 < args.len() => {
+                connect_timeout_secs = args[i + 1].parse().unwrap_or(DEFAULT_CONNECT_TIMEOUT_SECS);
+                i += 1;
             }
-            "--warmup" => {
-                if i + 1 < args.len() {
-                    warmup_ms = args[i + 1].parse().unwrap_or(100);
-                    i += 1;
-                }
+            "--warmup" if i + 1 < args.len() => {
+                warmup_ms = args[i + 1].parse().unwrap_or(100);
+                i += 1;
             }
             "-j" | "--json" => {
                 json = true;
@@ -401,57 +383,14 @@ async fn run_client(
     .map_err(|_| "Connection timeout")?
     .map_err(|e| format!("Connection failed: {}", e))?;
 
-    let mut stream = stream;
-
-    let key = generate_websocket_key(client_id);
-    let request = format!(
-        "GET / HTTP/1.1\r\n\
-         Host: {}\r\n\
-         Upgrade: websocket\r\n\
-         Connection: Upgrade\r\n\
-         Sec-WebSocket-Key: {}\r\n\
-         Sec-WebSocket-Version: 13\r\n\
-         \r\n",
-        config.server_addr, key
-    );
-    stream.write_all(request.as_bytes()).await?;
-
-    let mut reader = BufReader::new(&mut stream);
-    let mut response_bytes = Vec::new();
-
-    let handshake_result = timeout(Duration::from_secs(HANDSHAKE_TIMEOUT_SECS), async {
-        loop {
-            let mut line = String::new();
-            reader.read_line(&mut line).await?;
-            response_bytes.extend_from_slice(line.as_bytes());
-            if response_bytes.len() > MAX_HEADER_SIZE {
-                return Err("Header too large".into());
-            }
-            if line == "\r\n" {
-                break;
-            }
-        }
-        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
-    })
-    .await;
-
-    match handshake_result {
-        Ok(Ok(())) => {}
-        Ok(Err(e)) => return Err(e),
-        Err(_) => return Err("Handshake timeout".into()),
-    }
-
-    let response = HandshakeResponse::parse(&response_bytes)?;
-    let expected_accept = compute_accept_key(&key);
-    if response.accept != expected_accept {
-        return Err("Invalid Sec-WebSocket-Accept".into());
-    }
+    let url = format!("ws://{}/", config.server_addr);
+    let handshake = ClientBuilder::new(&url)?.handshake(stream);
+    let (mut conn, _response) = timeout(Duration::from_secs(HANDSHAKE_TIMEOUT_SECS), handshake)
+        .await
+        .map_err(|_| "Handshake timeout")??;
 
     metrics.connection_success();
 
-    let ws_config = Config::client();
-    let mut conn = Connection::new(stream, Role::Client, ws_config);
-
     let payload: String = (0..config.message_size)
         .map(|i| (b'a' + (i % 26) as u8) as char)
         .collect();
@@ -491,20 +430,6 @@ async fn run_client(
     Ok(())
 }
 
-fn generate_websocket_key(seed: usize) -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-    let time_seed = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_nanos();
-    let combined = time_seed.wrapping_add(seed as u128);
-    let mut bytes = [0u8; 16];
-    for (i, b) in bytes.iter_mut().enumerate() {
-        *b = ((combined >> (i * 4)) & 0xFF) as u8;
-    }
-    base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes)
-}
-
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let config = parse_args();
@@ -1,14 +1,12 @@
-use rsws::{Config, Connection, HandshakeRequest, HandshakeResponse, Message, Role};
+use rsws::{Config, Message};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::time::timeout;
 
 const HANDSHAKE_TIMEOUT_SECS: u64 = 10;
-const MAX_HEADER_SIZE: usize = 8192;
 
 struct ServerMetrics {
     connections_total: AtomicU64,
@@ -153,26 +151,20 @@ fn parse_args() -> (String, u16, bool, u64) {
     let mut i = 1;
     while i < args.len() {
         match args[i].as_str() {
-            "-h" | "--host" => {
-                if i + 1 < args.len() {
-                    host = args[i + 1].clone();
-                    i += 1;
-                }
+            "-h" | "--host" if i + 1 < args.len() => {
+                host = args[i + 1].clone();
+                i += 1;
             }
-            "-p" | "--port" => {
-                if i + 1 < args.len() {
-                    port = args[i + 1].parse().unwrap_or(9001);
-                    i += 1;
-                }
+            "-p" | "--port" if i + 1 < args.len() => {
+                port = args[i + 1].parse().unwrap_or(9001);
+                i += 1;
             }
             "-j" | "--json" => {
                 json = true;
             }
-            "-i" | "--interval" => {
-                if i + 1 < args.len() {
-                    report_interval = args[i + 1].parse().unwrap_or(5);
-                    i += 1;
-                }
+            "-i" | "--interval" if i + 1 < args.len() => {
+                report_interval = args[i + 1].parse().unwrap_or(5);
+                i += 1;
             }
             "--help" => {
                 println!("WebSocket Stress Test Server");
@@ -199,7 +191,7 @@ fn parse_args() -> (String, u16, bool, u64) {
 async fn handle_connection(stream: TcpStream, metrics: Arc<ServerMetrics>) {
     metrics.connection_opened();
 
-    if let Err(_) = handle_connection_inner(stream, &metrics).await {
+    if handle_connection_inner(stream, &metrics).await.is_err() {
         metrics.error();
     }
 
@@ -207,44 +199,18 @@ async fn handle_connection(stream: TcpStream, metrics: Arc<ServerMetrics>) {
 }
 
 async fn handle_connection_inner(
-    mut stream: TcpStream,
+    stream: TcpStream,
     metrics: &ServerMetrics,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let mut reader = BufReader::new(&mut stream);
-    let mut request_bytes = Vec::new();
-
-    let handshake_result = timeout(Duration::from_secs(HANDSHAKE_TIMEOUT_SECS), async {
-        loop {
-            let mut line = String::new();
-            reader.read_line(&mut line).await?;
-            request_bytes.extend_from_slice(line.as_bytes());
-            if request_bytes.len() > MAX_HEADER_SIZE {
-                return Err("Header too large".into());
-            }
-            if line == "\r\n" {
-                break;
-            }
-        }
-        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(())
-    })
-    .await;
-
-    match handshake_result {
-        Ok(Ok(())) => {}
-        Ok(Err(e)) => return Err(e),
+    let mut conn = match timeout(
+        Duration::from_secs(HANDSHAKE_TIMEOUT_SECS),
+        rsws::server::accept(stream, Config::server()),
+    )
+    .await
+    {
+        Ok(result) => result?.0,
         Err(_) => return Err("Handshake timeout".into()),
-    }
-
-    let request = HandshakeRequest::parse(&request_bytes)?;
-    let config = Config::server();
-    request.validate_with_config(&config)?;
-
-    let response = HandshakeResponse::from_request(&request);
-    let mut response_bytes = Vec::new();
-    let _ = response.write(&mut response_bytes);
-    stream.write_all(&response_bytes).await?;
-
-    let mut conn = Connection::new(stream, Role::Server, config);
+    };
 
     while conn.is_open() {
         match conn.recv().await? {
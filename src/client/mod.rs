@@ -0,0 +1,865 @@
+//! High-level WebSocket client.
+//!
+//! This module performs the client side of the opening handshake (RFC 6455
+//! Section 4.1): it parses the `ws://` URL, opens the TCP connection, sends
+//! the HTTP Upgrade request with a random `Sec-WebSocket-Key`, verifies the
+//! server's `Sec-WebSocket-Accept`, and returns a ready [`Connection`].
+//! With the `tls-rustls` or `tls-native` feature, `connect_tls` does the
+//! same for `wss://` URLs, running the TLS handshake first. Either can be tunnelled through
+//! an HTTP CONNECT or SOCKS5 [`Proxy`].
+//!
+//! Extensions registered with [`ClientBuilder::with_extensions`] are offered
+//! in `Sec-WebSocket-Extensions`; the ones the server accepts are configured
+//! and active on the returned connection.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use rsws::Message;
+//!
+//! let (mut conn, _response) = rsws::client::connect("ws://127.0.0.1:9001/chat").await?;
+//! conn.send(Message::text("Hello")).await?;
+//! ```
+
+mod proxy;
+mod url;
+
+pub use proxy::{Proxy, ProxyKind};
+pub use url::WsUrl;
+
+use std::fmt;
+use std::sync::Arc;
+
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+use crate::codec::handshake::read_http_head;
+use crate::config::Config;
+use crate::connection::{Connection, Role};
+use crate::error::{Error, Result};
+use crate::extensions::{ExtensionOffer, ExtensionRegistry};
+use crate::protocol::handshake::{is_token, validate_header_name, validate_header_value};
+use crate::protocol::{HandshakeResponse, generate_key};
+#[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+use crate::tls::{Connector, MaybeTlsStream};
+#[cfg(feature = "tls-rustls")]
+use crate::tls::{TlsClientConfigBuilder, TlsConnector};
+
+/// Connect to a `ws://` URL with the default client configuration.
+///
+/// Equivalent to `ClientBuilder::new(url)?.connect().await`.
+///
+/// # Errors
+///
+/// See [`ClientBuilder::connect`].
+pub async fn connect(url: &str) -> Result<(Connection<TcpStream>, HandshakeResponse)> {
+    ClientBuilder::new(url)?.connect().await
+}
+
+/// Connect to a `wss://` URL with the default client and TLS configuration.
+///
+/// Equivalent to `ClientBuilder::new(url)?.connect_tls().await`.
+///
+/// # Errors
+///
+/// See [`ClientBuilder::connect_tls`].
+#[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+pub async fn connect_tls(
+    url: &str,
+) -> Result<(Connection<MaybeTlsStream<TcpStream>>, HandshakeResponse)> {
+    ClientBuilder::new(url)?.connect_tls().await
+}
+
+/// The connector `connect_tls` uses when none was set: rustls with the
+/// bundled Mozilla roots and `http/1.1` via ALPN, else native-tls.
+#[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+fn default_tls_connector() -> Result<Connector> {
+    #[cfg(feature = "tls-rustls")]
+    {
+        let config = TlsClientConfigBuilder::new()
+            .with_webpki_roots()
+            .with_alpn_protocols(["http/1.1"])
+            .build()?;
+        Ok(TlsConnector::new(config).into())
+    }
+    #[cfg(not(feature = "tls-rustls"))]
+    {
+        Connector::new()
+    }
+}
+
+/// Creates the extension registry offered by each handshake.
+#[derive(Clone)]
+struct ExtensionFactory(Arc<dyn Fn() -> ExtensionRegistry + Send + Sync>);
+
+impl fmt::Debug for ExtensionFactory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ExtensionFactory")
+            .field(&(self.0)())
+            .finish()
+    }
+}
+
+/// Builder for client-side WebSocket connections.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// use rsws::client::ClientBuilder;
+/// use rsws::Config;
+///
+/// let (conn, response) = ClientBuilder::new("ws://localhost:9001/ws?token=abc")?
+///     .with_config(Config::client().with_fragment_size(4096))
+///     .with_origin("https://app.example.com")
+///     .with_header("Authorization", "Bearer abc")
+///     .connect()
+///     .await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    url: WsUrl,
+    config: Config,
+    origin: Option<String>,
+    headers: Vec<(String, String)>,
+    protocols: Vec<String>,
+    extensions: Option<ExtensionFactory>,
+    proxy: Option<Proxy>,
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+    tls_connector: Option<Connector>,
+}
+
+impl ClientBuilder {
+    /// Create a builder for the given `ws://` or `wss://` URL.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidUrl`] if the URL cannot be parsed.
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(Self::from_url(WsUrl::parse(url)?))
+    }
+
+    /// Create a builder from an already parsed URL.
+    #[must_use]
+    pub fn from_url(url: WsUrl) -> Self {
+        Self {
+            url,
+            config: Config::client(),
+            origin: None,
+            headers: Vec::new(),
+            protocols: Vec::new(),
+            extensions: None,
+            proxy: None,
+            #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+            tls_connector: None,
+        }
+    }
+
+    /// Set the connection configuration (default: [`Config::client()`]).
+    #[must_use]
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Send an `Origin` header with the request.
+    #[must_use]
+    pub fn with_origin(mut self, origin: impl Into<String>) -> Self {
+        self.origin = Some(origin.into());
+        self
+    }
+
+    /// Add an extra header to the upgrade request (e.g. `Authorization`).
+    #[must_use]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Offer subprotocols in `Sec-WebSocket-Protocol`, most preferred first.
+    ///
+    /// The server's choice is checked against this list and available from
+    /// [`Connection::subprotocol`].
+    #[must_use]
+    pub fn with_subprotocols<I, S>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Offer extensions in the handshake.
+    ///
+    /// `factory` builds a fresh [`ExtensionRegistry`] for every handshake, so
+    /// per-connection extension state (such as compression contexts) is
+    /// never shared. All registered extensions are offered; those the server
+    /// accepts are configured and active on the returned connection.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,ignore
+    /// use rsws::extensions::ExtensionRegistry;
+    /// use rsws::extensions::deflate::{DeflateConfig, DeflateExtension};
+    ///
+    /// let builder = ClientBuilder::new("ws://localhost:9001/")?.with_extensions(|| {
+    ///     let mut registry = ExtensionRegistry::new();
+    ///     registry
+    ///         .add(Box::new(DeflateExtension::client(DeflateConfig::default())))
+    ///         .expect("no RSV conflicts");
+    ///     registry
+    /// });
+    /// ```
+    #[must_use]
+    pub fn with_extensions<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> ExtensionRegistry + Send + Sync + 'static,
+    {
+        self.extensions = Some(ExtensionFactory(Arc::new(factory)));
+        self
+    }
+
+    /// Tunnel the connection through `proxy`.
+    ///
+    /// The URL host is passed to the proxy unresolved; TLS and the
+    /// WebSocket handshake then run over the tunnel.
+    #[must_use]
+    pub fn with_proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Tunnel the connection through the proxy configured in the
+    /// environment for this URL, if any. See [`Proxy::from_env`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidUrl` if the proxy variable is malformed.
+    pub fn with_proxy_from_env(mut self) -> Result<Self> {
+        self.proxy = Proxy::from_env(&self.url)?;
+        Ok(self)
+    }
+
+    /// Use `connector` for the TLS handshake in [`Self::connect_tls`]: a
+    /// rustls `TlsConnector`, a `NativeTlsConnector` or a [`Connector`].
+    ///
+    /// By default, with `tls-rustls` the bundled Mozilla roots are trusted
+    /// and `http/1.1` is offered via ALPN; with only `tls-native` the
+    /// platform's trust store is used.
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+    #[must_use]
+    pub fn with_tls_connector(mut self, connector: impl Into<Connector>) -> Self {
+        self.tls_connector = Some(connector.into());
+        self
+    }
+
+    /// Use a rustls `config` for the TLS handshake, e.g. one from
+    /// [`TlsClientConfigBuilder`] with custom roots or pins.
+    ///
+    /// Shorthand for `with_tls_connector(TlsConnector::new(config))`.
+    #[cfg(feature = "tls-rustls")]
+    #[must_use]
+    pub fn with_tls_config(self, config: Arc<rustls::ClientConfig>) -> Self {
+        self.with_tls_connector(TlsConnector::new(config))
+    }
+
+    /// The URL this builder connects to.
+    #[must_use]
+    pub fn url(&self) -> &WsUrl {
+        &self.url
+    }
+
+    /// Open a TCP connection to the URL's host, or a tunnel through the
+    /// proxy, and perform the handshake.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::InvalidUrl` for `wss://` URLs; use `connect_tls` or
+    ///   [`Self::handshake`] over your own TLS stream
+    /// - `Error::Io` if the TCP connection fails
+    /// - `Error::Proxy` if the proxy refuses the tunnel
+    /// - `Error::HandshakeTimeout` if opening the proxy tunnel exceeds
+    ///   `timeouts.handshake`
+    /// - Any error from [`Self::handshake`]
+    pub async fn connect(self) -> Result<(Connection<TcpStream>, HandshakeResponse)> {
+        if self.url.is_secure() {
+            return Err(Error::InvalidUrl(
+                "wss:// requires TLS; use connect_tls or ClientBuilder::handshake".into(),
+            ));
+        }
+
+        let stream = self.dial().await?;
+        self.handshake(stream).await
+    }
+
+    /// Open a TCP connection to a `wss://` URL's host, run the TLS handshake,
+    /// then perform the WebSocket handshake over the TLS stream.
+    ///
+    /// The URL host is sent as SNI and the server certificate is verified
+    /// against it; for IP address hosts no SNI is sent and the certificate
+    /// must carry a matching IP address SAN. The port defaults to 443. With
+    /// a proxy, TLS runs end to end through the tunnel. Opening the tunnel,
+    /// the TLS handshake and the WebSocket handshake are each bounded by
+    /// `timeouts.handshake`.
+    ///
+    /// With rustls, the negotiated protocol version and cipher suite are
+    /// available from `conn.get_ref()`:
+    ///
+    /// ```rust,ignore
+    /// let (conn, _) = ClientBuilder::new("wss://example.com/chat")?.connect_tls().await?;
+    /// let tls = conn.get_ref();
+    /// log::info!("{:?} {:?}", tls.protocol_version(), tls.cipher_suite());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - `Error::InvalidUrl` for `ws://` URLs
+    /// - `Error::Io` if the TCP connection fails
+    /// - `Error::Proxy` if the proxy refuses the tunnel
+    /// - `Error::Tls` if the TLS handshake fails, e.g. the certificate is not
+    ///   trusted or not valid for the host
+    /// - `Error::HandshakeTimeout` if opening the proxy tunnel or the TLS
+    ///   handshake exceeds `timeouts.handshake`
+    /// - Any error from [`Self::handshake`]
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+    pub async fn connect_tls(
+        mut self,
+    ) -> Result<(Connection<MaybeTlsStream<TcpStream>>, HandshakeResponse)> {
+        if !self.url.is_secure() {
+            return Err(Error::InvalidUrl(
+                "connect_tls requires a wss:// URL; use connect for ws://".into(),
+            ));
+        }
+
+        let connector = match self.tls_connector.take() {
+            Some(connector) => connector,
+            None => default_tls_connector()?,
+        };
+
+        let stream = self.dial().await?;
+        let tls = connector.connect(self.url.host(), stream);
+        let tls = match self.config.timeouts.as_ref().map(|t| t.handshake) {
+            Some(limit) => tokio::time::timeout(limit, tls)
+                .await
+                .map_err(|_| Error::HandshakeTimeout)??,
+            None => tls.await?,
+        };
+        self.handshake(tls).await
+    }
+
+    /// Connect to the URL's host, directly or through the proxy.
+    async fn dial(&self) -> Result<TcpStream> {
+        let (host, port) = (self.url.host(), self.url.port());
+        let Some(proxy) = &self.proxy else {
+            return Ok(TcpStream::connect((host, port)).await?);
+        };
+        match self.config.timeouts.as_ref().map(|t| t.handshake) {
+            Some(limit) => tokio::time::timeout(limit, proxy.connect(host, port))
+                .await
+                .map_err(|_| Error::HandshakeTimeout)?,
+            None => proxy.connect(host, port).await,
+        }
+    }
+
+    /// Perform the opening handshake over an already connected stream.
+    ///
+    /// Use this for streams that need extra setup before the upgrade, such
+    /// as TLS or proxy tunnels.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::InvalidHeaderValue` if an origin or extra header contains CR/LF
+    /// - `Error::HandshakeTooLarge` if the response exceeds `limits.max_handshake_size`
+    /// - `Error::HandshakeTimeout` if the exchange exceeds `timeouts.handshake`
+    /// - `Error::InvalidHeaderValue` if a subprotocol is not a valid token
+    /// - `Error::InvalidHandshake` if the response is not a valid 101, the
+    ///   `Sec-WebSocket-Accept` value does not match the key, or the server
+    ///   selected a subprotocol that was not offered
+    /// - `Error::InvalidExtension` if the server accepted an extension that
+    ///   was not offered, accepted one more than once, or sent parameters the
+    ///   extension rejects
+    /// - `Error::Io` on stream failure
+    pub async fn handshake<S>(self, stream: S) -> Result<(Connection<S>, HandshakeResponse)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        match self.config.timeouts.as_ref().map(|t| t.handshake) {
+            Some(limit) => tokio::time::timeout(limit, self.exchange(stream))
+                .await
+                .map_err(|_| Error::HandshakeTimeout)?,
+            None => self.exchange(stream).await,
+        }
+    }
+
+    async fn exchange<S>(self, mut stream: S) -> Result<(Connection<S>, HandshakeResponse)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut extensions = self
+            .extensions
+            .as_ref()
+            .map_or_else(ExtensionRegistry::new, |factory| (factory.0)());
+
+        let key = generate_key()?;
+        let request = self.build_request(&key, &extensions)?;
+        stream.write_all(&request).await?;
+        stream.flush().await?;
+
+        let (head, leftover) =
+            read_http_head(&mut stream, self.config.limits.max_handshake_size).await?;
+        let response = HandshakeResponse::parse(&head)?;
+
+        response.verify(&key, &self.protocols)?;
+
+        // RFC 6455 Section 9.1: fail on extensions that were not offered.
+        let accepted = response
+            .extensions
+            .iter()
+            .map(|ext| ExtensionOffer::parse(ext))
+            .collect::<Result<Vec<_>>>()?;
+        extensions.configure(&accepted)?;
+
+        let conn = Connection::from_parts(stream, leftover, Role::Client, self.config, extensions)
+            .with_subprotocol(response.protocol.clone());
+        Ok((conn, response))
+    }
+
+    fn build_request(&self, key: &str, extensions: &ExtensionRegistry) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(256);
+        buf.extend_from_slice(format!("GET {} HTTP/1.1\r\n", self.url.resource()).as_bytes());
+        buf.extend_from_slice(format!("Host: {}\r\n", self.url.host_header()).as_bytes());
+        buf.extend_from_slice(b"Upgrade: websocket\r\n");
+        buf.extend_from_slice(b"Connection: Upgrade\r\n");
+        buf.extend_from_slice(format!("Sec-WebSocket-Key: {}\r\n", key).as_bytes());
+        buf.extend_from_slice(b"Sec-WebSocket-Version: 13\r\n");
+
+        if let Some(ref origin) = self.origin {
+            validate_header_value("Origin", origin)?;
+            buf.extend_from_slice(format!("Origin: {}\r\n", origin).as_bytes());
+        }
+
+        if !self.protocols.is_empty() {
+            for protocol in &self.protocols {
+                if !is_token(protocol) {
+                    return Err(Error::InvalidHeaderValue {
+                        header: "Sec-WebSocket-Protocol".to_string(),
+                        reason: format!("invalid subprotocol name: {:?}", protocol),
+                    });
+                }
+            }
+            buf.extend_from_slice(
+                format!("Sec-WebSocket-Protocol: {}\r\n", self.protocols.join(", ")).as_bytes(),
+            );
+        }
+
+        if !extensions.is_empty() {
+            let offer = extensions.offer_header();
+            validate_header_value("Sec-WebSocket-Extensions", &offer)?;
+            buf.extend_from_slice(format!("Sec-WebSocket-Extensions: {}\r\n", offer).as_bytes());
+        }
+
+        for (name, value) in &self.headers {
+            validate_header_name(name)?;
+            validate_header_value(name, value)?;
+            buf.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+
+        buf.extend_from_slice(b"\r\n");
+        Ok(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extensions::{Extension, ExtensionParam};
+    use crate::message::Message;
+    use crate::protocol::compute_accept_key;
+    use crate::protocol::{Frame, HandshakeRequest};
+    use tokio::io::{AsyncReadExt, duplex};
+
+    #[test]
+    fn test_build_request() {
+        let builder = ClientBuilder::new("ws://[::1]:9001/chat?room=1")
+            .unwrap()
+            .with_origin("http://example.com")
+            .with_header("Authorization", "Bearer t");
+        let bytes = builder
+            .build_request("dGhlIHNhbXBsZSBub25jZQ==", &ExtensionRegistry::new())
+            .unwrap();
+
+        let req = HandshakeRequest::parse(&bytes).unwrap();
+        req.validate().unwrap();
+        assert_eq!(req.path, "/chat?room=1");
+        assert_eq!(req.host, "[::1]:9001");
+        assert_eq!(req.origin.as_deref(), Some("http://example.com"));
+        assert!(
+            String::from_utf8(bytes)
+                .unwrap()
+                .contains("Authorization: Bearer t\r\n")
+        );
+    }
+
+    #[test]
+    fn test_build_request_rejects_header_injection() {
+        let builder = ClientBuilder::new("ws://example.com/")
+            .unwrap()
+            .with_header("X-Test", "a\r\nX-Injected: b");
+        assert!(matches!(
+            builder.build_request("key", &ExtensionRegistry::new()),
+            Err(Error::InvalidHeaderValue { .. })
+        ));
+
+        let builder = ClientBuilder::new("ws://example.com/")
+            .unwrap()
+            .with_header("Bad Name", "v");
+        assert!(matches!(
+            builder.build_request("key", &ExtensionRegistry::new()),
+            Err(Error::InvalidHeaderValue { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejects_wss_without_tls() {
+        let result = connect("wss://example.com/").await;
+        assert!(matches!(result, Err(Error::InvalidUrl(_))));
+    }
+
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+    #[tokio::test]
+    async fn test_connect_tls_rejects_ws() {
+        let result = connect_tls("ws://example.com/").await;
+        assert!(matches!(result, Err(Error::InvalidUrl(_))));
+    }
+
+    async fn read_request(server: &mut tokio::io::DuplexStream) -> HandshakeRequest {
+        let (head, _) = read_http_head(server, 8192).await.unwrap();
+        HandshakeRequest::parse(&head).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_handshake_over_stream() {
+        let (client_io, mut server_io) = duplex(4096);
+
+        let server = tokio::spawn(async move {
+            let req = read_request(&mut server_io).await;
+            let response = HandshakeResponse::from_request(&req);
+            let mut buf = Vec::new();
+            response.write(&mut buf).unwrap();
+            // Unmasked text frame "hi" right behind the response.
+            buf.extend_from_slice(&[0x81, 0x02, b'h', b'i']);
+            server_io.write_all(&buf).await.unwrap();
+            server_io
+        });
+
+        let (mut conn, response) = ClientBuilder::new("ws://localhost/")
+            .unwrap()
+            .handshake(client_io)
+            .await
+            .unwrap();
+        assert!(response.protocol.is_none());
+        assert_eq!(conn.recv().await.unwrap(), Some(Message::text("hi")));
+
+        drop(server.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_wrong_accept() {
+        let (client_io, mut server_io) = duplex(4096);
+
+        tokio::spawn(async move {
+            let _ = read_request(&mut server_io).await;
+            server_io
+                .write_all(
+                    b"HTTP/1.1 101 Switching Protocols\r\n\
+                      Upgrade: websocket\r\n\
+                      Connection: Upgrade\r\n\
+                      Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n",
+                )
+                .await
+                .unwrap();
+            let mut rest = Vec::new();
+            let _ = server_io.read_to_end(&mut rest).await;
+        });
+
+        let result = ClientBuilder::new("ws://localhost/")
+            .unwrap()
+            .handshake(client_io)
+            .await;
+        assert!(matches!(result, Err(Error::InvalidHandshake(msg)) if msg.contains("Accept")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_handshake_timeout() {
+        let (client_io, _server_io) = duplex(4096);
+        let config = Config::client().with_timeouts(crate::config::Timeouts::default());
+
+        let result = ClientBuilder::new("ws://localhost/")
+            .unwrap()
+            .with_config(config)
+            .handshake(client_io)
+            .await;
+        assert!(matches!(result, Err(Error::HandshakeTimeout)));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_non_101() {
+        let (client_io, mut server_io) = duplex(4096);
+
+        tokio::spawn(async move {
+            let _ = read_request(&mut server_io).await;
+            server_io
+                .write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let result = ClientBuilder::new("ws://localhost/")
+            .unwrap()
+            .handshake(client_io)
+            .await;
+        assert!(matches!(result, Err(Error::InvalidHandshake(_))));
+    }
+
+    struct TestExtension;
+
+    impl Extension for TestExtension {
+        fn name(&self) -> &str {
+            "x-test"
+        }
+
+        fn negotiate(&mut self, _params: &[ExtensionParam]) -> Result<Vec<ExtensionParam>> {
+            Ok(vec![])
+        }
+
+        fn encode(&mut self, _frame: &mut Frame) -> Result<()> {
+            Ok(())
+        }
+
+        fn decode(&mut self, _frame: &mut Frame) -> Result<()> {
+            Ok(())
+        }
+
+        fn offer_params(&self) -> Vec<ExtensionParam> {
+            vec![ExtensionParam::flag("level")]
+        }
+    }
+
+    fn test_registry() -> ExtensionRegistry {
+        let mut registry = ExtensionRegistry::new();
+        registry.add(Box::new(TestExtension)).unwrap();
+        registry
+    }
+
+    async fn respond_with_extensions(server_io: &mut tokio::io::DuplexStream, extensions: &str) {
+        let req = read_request(server_io).await;
+        let mut response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\n",
+            compute_accept_key(&req.key)
+        );
+        if !extensions.is_empty() {
+            response.push_str(&format!("Sec-WebSocket-Extensions: {}\r\n", extensions));
+        }
+        response.push_str("\r\n");
+        server_io.write_all(response.as_bytes()).await.unwrap();
+    }
+
+    #[test]
+    fn test_build_request_offers_extensions() {
+        let builder = ClientBuilder::new("ws://example.com/").unwrap();
+
+        let bytes = builder.build_request("key", &test_registry()).unwrap();
+        let req = HandshakeRequest::parse(&bytes).unwrap();
+        assert_eq!(req.extensions, vec!["x-test; level".to_string()]);
+
+        let bytes = builder
+            .build_request("key", &ExtensionRegistry::new())
+            .unwrap();
+        let req = HandshakeRequest::parse(&bytes).unwrap();
+        assert!(req.extensions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_configures_accepted_extensions() {
+        let (client_io, mut server_io) = duplex(4096);
+
+        let server = tokio::spawn(async move {
+            respond_with_extensions(&mut server_io, "x-test").await;
+            server_io
+        });
+
+        let (mut conn, response) = ClientBuilder::new("ws://localhost/")
+            .unwrap()
+            .with_extensions(test_registry)
+            .handshake(client_io)
+            .await
+            .unwrap();
+        assert_eq!(response.extensions, vec!["x-test".to_string()]);
+        assert_eq!(conn.extensions_mut().negotiated_count(), 1);
+
+        drop(server.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_without_accepted_extensions() {
+        let (client_io, mut server_io) = duplex(4096);
+
+        let server = tokio::spawn(async move {
+            respond_with_extensions(&mut server_io, "").await;
+            server_io
+        });
+
+        let (mut conn, _) = ClientBuilder::new("ws://localhost/")
+            .unwrap()
+            .with_extensions(test_registry)
+            .handshake(client_io)
+            .await
+            .unwrap();
+        assert_eq!(conn.extensions_mut().negotiated_count(), 0);
+
+        drop(server.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_unoffered_extension() {
+        let (client_io, mut server_io) = duplex(4096);
+
+        tokio::spawn(async move {
+            respond_with_extensions(&mut server_io, "permessage-deflate").await;
+            server_io
+        });
+
+        let result = ClientBuilder::new("ws://localhost/")
+            .unwrap()
+            .handshake(client_io)
+            .await;
+        assert!(matches!(result, Err(Error::InvalidExtension(msg)) if msg.contains("not offered")));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_duplicate_extension() {
+        let (client_io, mut server_io) = duplex(4096);
+
+        tokio::spawn(async move {
+            respond_with_extensions(&mut server_io, "x-test, x-test").await;
+            server_io
+        });
+
+        let result = ClientBuilder::new("ws://localhost/")
+            .unwrap()
+            .with_extensions(test_registry)
+            .handshake(client_io)
+            .await;
+        assert!(
+            matches!(result, Err(Error::InvalidExtension(msg)) if msg.contains("more than once"))
+        );
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_handshake_negotiates_deflate() {
+        use crate::codec::WebSocketCodec;
+        use crate::extensions::deflate::{DeflateConfig, DeflateExtension};
+
+        let (client_io, mut server_io) = duplex(4096);
+
+        let server = tokio::spawn(async move {
+            respond_with_extensions(&mut server_io, "permessage-deflate").await;
+            let mut codec = WebSocketCodec::new(server_io, Role::Server, Config::server());
+            codec.set_allowed_rsv_bits(0x40);
+            codec.read_frame().await.unwrap()
+        });
+
+        let (mut conn, _) = ClientBuilder::new("ws://localhost/")
+            .unwrap()
+            .with_extensions(|| {
+                let mut registry = ExtensionRegistry::new();
+                registry
+                    .add(Box::new(DeflateExtension::client(DeflateConfig::default())))
+                    .unwrap();
+                registry
+            })
+            .handshake(client_io)
+            .await
+            .unwrap();
+        conn.send(Message::text("compressed compressed compressed"))
+            .await
+            .unwrap();
+
+        let frame = server.await.unwrap();
+        assert!(frame.rsv1);
+    }
+
+    async fn respond_with_protocol(server_io: &mut tokio::io::DuplexStream, protocol: &str) {
+        let req = read_request(server_io).await;
+        let mut response = HandshakeResponse::from_request(&req);
+        response.protocol = Some(protocol.to_string());
+        let mut buf = Vec::new();
+        response.write(&mut buf).unwrap();
+        server_io.write_all(&buf).await.unwrap();
+    }
+
+    #[test]
+    fn test_build_request_offers_subprotocols() {
+        let builder = ClientBuilder::new("ws://example.com/")
+            .unwrap()
+            .with_subprotocols(["chat.v2", "chat.v1"]);
+        let bytes = builder
+            .build_request("key", &ExtensionRegistry::new())
+            .unwrap();
+        let req = HandshakeRequest::parse(&bytes).unwrap();
+        assert_eq!(req.protocols, vec!["chat.v2", "chat.v1"]);
+
+        let builder = ClientBuilder::new("ws://example.com/")
+            .unwrap()
+            .with_subprotocols(["a, b"]);
+        assert!(matches!(
+            builder.build_request("key", &ExtensionRegistry::new()),
+            Err(Error::InvalidHeaderValue { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_exposes_subprotocol() {
+        let (client_io, mut server_io) = duplex(4096);
+
+        let server = tokio::spawn(async move {
+            respond_with_protocol(&mut server_io, "chat.v1").await;
+            server_io
+        });
+
+        let (conn, response) = ClientBuilder::new("ws://localhost/")
+            .unwrap()
+            .with_subprotocols(["chat.v2", "chat.v1"])
+            .handshake(client_io)
+            .await
+            .unwrap();
+        assert_eq!(response.protocol.as_deref(), Some("chat.v1"));
+        assert_eq!(conn.subprotocol(), Some("chat.v1"));
+
+        let (reader, _writer) = conn.split();
+        assert_eq!(reader.subprotocol(), Some("chat.v1"));
+
+        drop(server.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_unoffered_subprotocol() {
+        let (client_io, mut server_io) = duplex(4096);
+
+        tokio::spawn(async move {
+            respond_with_protocol(&mut server_io, "mqtt").await;
+            server_io
+        });
+
+        let result = ClientBuilder::new("ws://localhost/")
+            .unwrap()
+            .with_subprotocols(["chat.v1"])
+            .handshake(client_io)
+            .await;
+        assert!(matches!(result, Err(Error::InvalidHandshake(msg)) if msg.contains("mqtt")));
+    }
+}
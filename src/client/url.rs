@@ -0,0 +1,310 @@
+//! WebSocket URL parsing (RFC 6455 Section 3).
+
+use std::fmt;
+use std::net::Ipv6Addr;
+use std::str::FromStr;
+
+use crate::error::{Error, Result};
+
+const WS_DEFAULT_PORT: u16 = 80;
+const WSS_DEFAULT_PORT: u16 = 443;
+
+/// A parsed `ws://` or `wss://` URL.
+///
+/// Only the parts that matter for the opening handshake are kept: the scheme,
+/// host, port and resource name (path plus query string). Fragments are
+/// rejected as required by RFC 6455 Section 3.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WsUrl {
+    secure: bool,
+    host: String,
+    port: u16,
+    explicit_port: bool,
+    resource: String,
+}
+
+impl WsUrl {
+    /// Parse a WebSocket URL.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidUrl`] if:
+    /// - The scheme is not `ws` or `wss`.
+    /// - The host is missing, or an IPv6 literal is malformed.
+    /// - The port is not a valid non-zero `u16`.
+    /// - The URL contains user info or a fragment.
+    pub fn parse(url: &str) -> Result<Self> {
+        let (scheme, rest) = url
+            .split_once("://")
+            .ok_or_else(|| Error::InvalidUrl(format!("missing scheme: {}", url)))?;
+
+        let secure = if scheme.eq_ignore_ascii_case("ws") {
+            false
+        } else if scheme.eq_ignore_ascii_case("wss") {
+            true
+        } else {
+            return Err(Error::InvalidUrl(format!("unsupported scheme: {}", scheme)));
+        };
+
+        if rest.contains('#') {
+            return Err(Error::InvalidUrl(
+                "fragment identifiers are not allowed in WebSocket URLs".into(),
+            ));
+        }
+
+        let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, resource) = rest.split_at(authority_end);
+
+        if authority.contains('@') {
+            return Err(Error::InvalidUrl(
+                "user info is not supported in WebSocket URLs".into(),
+            ));
+        }
+
+        let (host, port) = Self::split_host_port(authority)?;
+        if host.is_empty() {
+            return Err(Error::InvalidUrl(format!("missing host: {}", url)));
+        }
+
+        let resource = if resource.is_empty() {
+            "/".to_string()
+        } else if resource.starts_with('?') {
+            format!("/{}", resource)
+        } else {
+            resource.to_string()
+        };
+
+        let default_port = if secure {
+            WSS_DEFAULT_PORT
+        } else {
+            WS_DEFAULT_PORT
+        };
+
+        Ok(Self {
+            secure,
+            host,
+            port: port.unwrap_or(default_port),
+            explicit_port: port.is_some(),
+            resource,
+        })
+    }
+
+    pub(super) fn split_host_port(authority: &str) -> Result<(String, Option<u16>)> {
+        let (host, port_str) = if let Some(after_bracket) = authority.strip_prefix('[') {
+            let (literal, after) = after_bracket.split_once(']').ok_or_else(|| {
+                Error::InvalidUrl(format!("unterminated IPv6 literal: {}", authority))
+            })?;
+            literal
+                .parse::<Ipv6Addr>()
+                .map_err(|_| Error::InvalidUrl(format!("invalid IPv6 address: {}", literal)))?;
+            let port_str = match after {
+                "" => None,
+                _ => Some(after.strip_prefix(':').ok_or_else(|| {
+                    Error::InvalidUrl(format!("unexpected data after IPv6 literal: {}", after))
+                })?),
+            };
+            (literal.to_string(), port_str)
+        } else {
+            let (host, port_str) = match authority.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            };
+            if host.contains(':') {
+                return Err(Error::InvalidUrl(format!(
+                    "IPv6 address must be enclosed in brackets: {}",
+                    authority
+                )));
+            }
+            (host.to_ascii_lowercase(), port_str)
+        };
+
+        let port = match port_str {
+            Some("") => {
+                return Err(Error::InvalidUrl(format!(
+                    "missing port after ':': {}",
+                    authority
+                )));
+            }
+            Some(p) => {
+                let port: u16 = p
+                    .parse()
+                    .map_err(|_| Error::InvalidUrl(format!("invalid port: {}", p)))?;
+                if port == 0 {
+                    return Err(Error::InvalidUrl("port must not be 0".into()));
+                }
+                Some(port)
+            }
+            None => None,
+        };
+
+        Ok((host, port))
+    }
+
+    /// Returns `true` for `wss://` URLs.
+    #[must_use]
+    pub fn is_secure(&self) -> bool {
+        self.secure
+    }
+
+    /// The host name or IP address, without IPv6 brackets.
+    #[must_use]
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The port, defaulting to 80 for `ws://` and 443 for `wss://`.
+    #[must_use]
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// The resource name sent in the request line (path and query string).
+    #[must_use]
+    pub fn resource(&self) -> &str {
+        &self.resource
+    }
+
+    /// The path component, without the query string.
+    #[must_use]
+    pub fn path(&self) -> &str {
+        self.resource
+            .split_once('?')
+            .map_or(self.resource.as_str(), |(path, _)| path)
+    }
+
+    /// The raw query string, if present.
+    #[must_use]
+    pub fn query(&self) -> Option<&str> {
+        self.resource.split_once('?').map(|(_, query)| query)
+    }
+
+    /// The value for the `Host` header.
+    ///
+    /// IPv6 literals are bracketed and the port is omitted when it is the
+    /// scheme's default, as browsers do.
+    #[must_use]
+    pub fn host_header(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+
+        if self.explicit_port && !self.is_default_port() {
+            format!("{}:{}", host, self.port)
+        } else {
+            host
+        }
+    }
+
+    fn is_default_port(&self) -> bool {
+        if self.secure {
+            self.port == WSS_DEFAULT_PORT
+        } else {
+            self.port == WS_DEFAULT_PORT
+        }
+    }
+}
+
+impl FromStr for WsUrl {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for WsUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.secure { "wss" } else { "ws" };
+        write!(f, "{}://{}{}", scheme, self.host_header(), self.resource)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_default_port() {
+        let url = WsUrl::parse("ws://example.com/chat").unwrap();
+        assert!(!url.is_secure());
+        assert_eq!(url.host(), "example.com");
+        assert_eq!(url.port(), 80);
+        assert_eq!(url.resource(), "/chat");
+        assert_eq!(url.host_header(), "example.com");
+
+        let url = WsUrl::parse("wss://example.com").unwrap();
+        assert!(url.is_secure());
+        assert_eq!(url.port(), 443);
+        assert_eq!(url.resource(), "/");
+    }
+
+    #[test]
+    fn test_parse_explicit_port_and_query() {
+        let url = WsUrl::parse("ws://localhost:9001/ws?token=abc&room=1").unwrap();
+        assert_eq!(url.port(), 9001);
+        assert_eq!(url.path(), "/ws");
+        assert_eq!(url.query(), Some("token=abc&room=1"));
+        assert_eq!(url.resource(), "/ws?token=abc&room=1");
+        assert_eq!(url.host_header(), "localhost:9001");
+    }
+
+    #[test]
+    fn test_parse_query_without_path() {
+        let url = WsUrl::parse("ws://example.com?x=1").unwrap();
+        assert_eq!(url.resource(), "/?x=1");
+        assert_eq!(url.path(), "/");
+    }
+
+    #[test]
+    fn test_parse_ipv6_literal() {
+        let url = WsUrl::parse("ws://[::1]:8080/").unwrap();
+        assert_eq!(url.host(), "::1");
+        assert_eq!(url.port(), 8080);
+        assert_eq!(url.host_header(), "[::1]:8080");
+
+        let url = WsUrl::parse("wss://[2001:db8::1]").unwrap();
+        assert_eq!(url.port(), 443);
+        assert_eq!(url.host_header(), "[2001:db8::1]");
+    }
+
+    #[test]
+    fn test_explicit_default_port_omitted_from_host_header() {
+        let url = WsUrl::parse("ws://example.com:80/").unwrap();
+        assert_eq!(url.host_header(), "example.com");
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_urls() {
+        for bad in [
+            "http://example.com",
+            "example.com",
+            "ws://",
+            "ws://:80/",
+            "ws://example.com:0/",
+            "ws://example.com:99999/",
+            "ws://example.com:/",
+            "ws://[::1]:/",
+            "ws://::1/",
+            "ws://::1:9001/",
+            "ws://2001:db8::1/",
+            "ws://[::1/",
+            "ws://[not-an-ip]/",
+            "ws://user:pass@example.com/",
+            "ws://example.com/#frag",
+        ] {
+            assert!(
+                matches!(WsUrl::parse(bad), Err(Error::InvalidUrl(_))),
+                "{} should be rejected",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_roundtrip() {
+        let url: WsUrl = "WS://Example.COM:9001/a?b=c".parse().unwrap();
+        assert_eq!(url.to_string(), "ws://example.com:9001/a?b=c");
+    }
+}
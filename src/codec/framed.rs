@@ -1,5 +1,10 @@
-use bytes::{BufMut, BytesMut};
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use std::future::{Future, poll_fn};
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf, ReadHalf, WriteHalf};
+use tokio::time::{Instant, Sleep};
 
 use crate::config::Config;
 use crate::connection::Role;
@@ -13,6 +18,15 @@ use crate::protocol::validation::FrameValidator;
 ///
 /// Handles low-level frame reading/writing with automatic masking (for clients)
 /// and validation according to RFC 6455.
+///
+/// When `config.timeouts` is set, reads and writes are bounded:
+///
+/// - Waiting for the first byte of a frame is bounded by `idle`, measured
+///   from the last complete frame received (`Error::IdleTimeout`).
+/// - Receiving the rest of a frame is bounded by `read`, measured from the
+///   frame's first byte (`Error::ReadTimeout`), so a peer cannot keep a
+///   frame open by trickling bytes.
+/// - Each write and flush is bounded by `write` (`Error::WriteTimeout`).
 pub struct WebSocketCodec<T> {
     io: T,
     read_buf: BytesMut,
@@ -20,21 +34,43 @@ pub struct WebSocketCodec<T> {
     role: Role,
     config: Config,
     validator: FrameValidator,
+    last_frame_at: Instant,
+    frame_started_at: Option<Instant>,
+    read_timer: Option<Pin<Box<Sleep>>>,
+    write_timer: Option<Pin<Box<Sleep>>>,
+    needs_flush: bool,
 }
 
 impl<T> WebSocketCodec<T> {
     /// Create a new codec wrapping the given I/O stream.
     #[must_use]
     pub fn new(io: T, role: Role, config: Config) -> Self {
+        Self::from_parts(io, Bytes::new(), role, config)
+    }
+
+    /// Create a codec whose read buffer is seeded with `leftover` bytes.
+    ///
+    /// Use this when the handshake was read with a buffered reader that may
+    /// have consumed the start of the WebSocket stream. Frames contained in
+    /// `leftover` are decoded before anything is read from `io`.
+    #[must_use]
+    pub fn from_parts(io: T, leftover: Bytes, role: Role, config: Config) -> Self {
         let validator = FrameValidator::new(role, config.limits.clone())
             .with_accept_unmasked(config.accept_unmasked_frames);
+        let mut read_buf = BytesMut::with_capacity(config.read_buffer_size.max(leftover.len()));
+        read_buf.extend_from_slice(&leftover);
         Self {
             io,
-            read_buf: BytesMut::with_capacity(config.read_buffer_size),
+            read_buf,
             write_buf: BytesMut::with_capacity(config.write_buffer_size),
             role,
             config,
             validator,
+            last_frame_at: Instant::now(),
+            frame_started_at: None,
+            read_timer: None,
+            write_timer: None,
+            needs_flush: false,
         }
     }
 
@@ -57,116 +93,147 @@ impl<T> WebSocketCodec<T> {
         Ok(mask)
     }
 
+    /// Get a reference to the underlying I/O stream.
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    /// Consume the codec and return the underlying I/O stream.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+
     pub fn set_allowed_rsv_bits(&mut self, bits: u8) {
         self.validator.set_allowed_rsv_bits(bits);
     }
+
+    /// Encode a frame into the write buffer without touching the stream.
+    ///
+    /// Clients automatically mask the frame; servers send unmasked. Buffered
+    /// frames go out with the next [`Self::write_frame`], `poll_write_buffered`
+    /// or flush.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::FrameTooLarge` if payload exceeds configured limits
+    pub fn buffer_frame(&mut self, frame: &Frame) -> Result<()> {
+        // Validate frame size before allocation
+        let payload_size = frame.payload().len();
+        self.config.limits.check_frame_size(payload_size)?;
+
+        let mask = if self.role.must_mask() {
+            Some(self.generate_mask()?)
+        } else {
+            None
+        };
+
+        let start = self.write_buf.len();
+        let wire_size = frame.wire_size(mask.is_some());
+        self.write_buf.resize(start + wire_size, 0);
+
+        let written = frame.write(&mut self.write_buf[start..], mask)?;
+        self.write_buf.truncate(start + written);
+        self.needs_flush = true;
+        Ok(())
+    }
+
+    /// Number of encoded bytes waiting to be written to the stream.
+    #[must_use]
+    pub fn buffered_len(&self) -> usize {
+        self.write_buf.len()
+    }
+
+    /// Whether frames have been buffered or written since the last flush.
+    #[must_use]
+    pub fn needs_flush(&self) -> bool {
+        self.needs_flush
+    }
+
+    /// Deadline for the next read and the error to report when it passes.
+    fn read_deadline(&mut self) -> Option<(Instant, Error)> {
+        let timeouts = self.config.timeouts.as_ref()?;
+        if self.read_buf.is_empty() {
+            self.frame_started_at = None;
+            Some((self.last_frame_at + timeouts.idle, Error::IdleTimeout))
+        } else {
+            let started = *self.frame_started_at.get_or_insert_with(Instant::now);
+            Some((started + timeouts.read, Error::ReadTimeout))
+        }
+    }
+}
+
+/// Poll `timer` until `deadline`, arming or re-arming it as needed.
+pub(crate) fn poll_deadline(
+    timer: &mut Option<Pin<Box<Sleep>>>,
+    deadline: Instant,
+    cx: &mut Context<'_>,
+) -> Poll<()> {
+    let sleep = timer.get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+    if sleep.deadline() != deadline {
+        sleep.as_mut().reset(deadline);
+    }
+    sleep.as_mut().poll(cx)
 }
 
-impl<T: AsyncRead + AsyncWrite + Unpin> WebSocketCodec<T> {
+impl<T: AsyncRead + Unpin> WebSocketCodec<T> {
+    /// Read the next frame from the stream.
+    ///
+    /// This is cancel safe: bytes of a partially received frame stay buffered
+    /// and are picked up by the next call.
+    ///
+    /// # Errors
+    ///
+    /// - Frame validation errors (masking, RSV bits, size limits)
+    /// - `Error::IdleTimeout` if no frame starts within `timeouts.idle`
+    /// - `Error::ReadTimeout` if a started frame is not complete within `timeouts.read`
+    /// - `Error::ConnectionClosed` if the stream reaches EOF
+    /// - `Error::Io` if the read fails
     pub async fn read_frame(&mut self) -> Result<Frame> {
+        poll_fn(|cx| self.poll_read_frame(cx)).await
+    }
+
+    /// Poll for the next frame.
+    ///
+    /// Poll-based form of [`Self::read_frame`], with the same errors.
+    pub fn poll_read_frame(&mut self, cx: &mut Context<'_>) -> Poll<Result<Frame>> {
         loop {
-            if self.read_buf.len() >= 2 {
-                // Validate frame before parsing (extract metadata from raw buffer)
-                let byte0 = self.read_buf[0];
-                let byte1 = self.read_buf[1];
-                let fin = (byte0 & 0x80) != 0;
-                let opcode = OpCode::from_u8(byte0 & 0x0F)?;
-                let rsv1 = (byte0 & 0x40) != 0;
-                let rsv2 = (byte0 & 0x20) != 0;
-                let rsv3 = (byte0 & 0x10) != 0;
-                let masked = (byte1 & 0x80) != 0;
-                let payload_len_initial = byte1 & 0x7F;
-
-                // Calculate payload length and base header length from the wire prefix.
-                let payload_and_header = match payload_len_initial {
-                    0..=125 => Some((payload_len_initial as usize, 2usize)),
-                    126 if self.read_buf.len() >= 4 => Some((
-                        u16::from_be_bytes([self.read_buf[2], self.read_buf[3]]) as usize,
-                        4usize,
-                    )),
-                    127 if self.read_buf.len() >= 10 => {
-                        let len_u64 = u64::from_be_bytes([
-                            self.read_buf[2],
-                            self.read_buf[3],
-                            self.read_buf[4],
-                            self.read_buf[5],
-                            self.read_buf[6],
-                            self.read_buf[7],
-                            self.read_buf[8],
-                            self.read_buf[9],
-                        ]);
-                        let len = usize::try_from(len_u64).map_err(|_| {
-                            Error::PayloadTooLargeForPlatform {
-                                size: len_u64,
-                                max: usize::MAX as u64,
-                            }
-                        })?;
-                        Some((len, 10usize))
-                    }
-                    _ => None,
-                };
-
-                if let Some((payload_len, base_header_len)) = payload_and_header {
-                    self.validator
-                        .validate_incoming(masked, rsv1, rsv2, rsv3, payload_len)?;
-
-                    let header_len = base_header_len + if masked { 4 } else { 0 };
-                    if self.read_buf.len() >= header_len {
-                        let total_size = header_len.checked_add(payload_len).ok_or(
-                            Error::PayloadTooLargeForPlatform {
-                                size: payload_len as u64,
-                                max: usize::MAX as u64,
-                            },
-                        )?;
-
-                        if self.read_buf.len() >= total_size {
-                            let frame_bytes = self.read_buf.split_to(total_size).freeze();
-                            let payload_start = header_len;
-                            let payload_end = payload_start + payload_len;
-
-                            let mut frame = if masked {
-                                let mask_offset = base_header_len;
-                                let mask = [
-                                    frame_bytes[mask_offset],
-                                    frame_bytes[mask_offset + 1],
-                                    frame_bytes[mask_offset + 2],
-                                    frame_bytes[mask_offset + 3],
-                                ];
-                                let mut payload = frame_bytes[payload_start..payload_end].to_vec();
-                                apply_mask_simd(&mut payload, mask);
-                                Frame::new(fin, opcode, payload)
-                            } else {
-                                Frame::new_from_bytes(
-                                    fin,
-                                    opcode,
-                                    frame_bytes.slice(payload_start..payload_end),
-                                )
-                            };
-                            frame.rsv1 = rsv1;
-                            frame.rsv2 = rsv2;
-                            frame.rsv3 = rsv3;
-                            return Ok(frame);
-                        }
-                    }
-                }
+            if let Some(frame) = self.decode_frame()? {
+                self.read_timer = None;
+                self.last_frame_at = Instant::now();
+                self.frame_started_at = None;
+                return Poll::Ready(Ok(frame));
             }
 
+            let deadline = self.read_deadline();
             self.read_buf.reserve(4096);
 
-            // SAFETY: `chunk_mut()` returns uninitialized memory as `UninitSlice`.
-            // We create a raw slice to pass to `read()`, which only writes to it.
-            // We only advance by the exact number of bytes `read()` reports writing.
-            let buf = self.read_buf.chunk_mut();
-            let buf_slice =
-                unsafe { std::slice::from_raw_parts_mut(buf.as_mut_ptr(), buf.len().min(4096)) };
+            let dst = self.read_buf.chunk_mut();
+            let len = dst.len().min(4096);
+            // SAFETY: the slice is only handed to `ReadBuf::uninit`, which never
+            // de-initializes memory, and we only advance by the bytes it reports filled.
+            let mut buf = ReadBuf::uninit(unsafe { &mut dst.as_uninit_slice_mut()[..len] });
+
+            match Pin::new(&mut self.io).poll_read(cx, &mut buf) {
+                Poll::Ready(Ok(())) => {}
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e.into())),
+                Poll::Pending => {
+                    if let Some((deadline, err)) = deadline
+                        && poll_deadline(&mut self.read_timer, deadline, cx).is_ready()
+                    {
+                        self.read_timer = None;
+                        return Poll::Ready(Err(err));
+                    }
+                    return Poll::Pending;
+                }
+            }
 
-            let n = self.io.read(buf_slice).await?;
+            let n = buf.filled().len();
             if n == 0 {
-                return Err(Error::ConnectionClosed(None));
+                return Poll::Ready(Err(Error::ConnectionClosed(None)));
             }
 
-            // SAFETY: `read()` guarantees it initialized exactly `n` bytes.
-            // We advance by `n` to mark those bytes as part of the buffer.
+            // SAFETY: `poll_read()` initialized exactly `n` bytes of the spare capacity.
             unsafe { self.read_buf.advance_mut(n) };
 
             // Shrink buffer if it's significantly oversized to prevent memory bloat
@@ -180,51 +247,275 @@ impl<T: AsyncRead + AsyncWrite + Unpin> WebSocketCodec<T> {
         }
     }
 
+    /// Decode one complete frame from the read buffer, if there is one.
+    fn decode_frame(&mut self) -> Result<Option<Frame>> {
+        if self.read_buf.len() >= 2 {
+            // Validate frame before parsing (extract metadata from raw buffer)
+            let byte0 = self.read_buf[0];
+            let byte1 = self.read_buf[1];
+            let fin = (byte0 & 0x80) != 0;
+            let opcode = OpCode::from_u8(byte0 & 0x0F)?;
+            let rsv1 = (byte0 & 0x40) != 0;
+            let rsv2 = (byte0 & 0x20) != 0;
+            let rsv3 = (byte0 & 0x10) != 0;
+            let masked = (byte1 & 0x80) != 0;
+            let payload_len_initial = byte1 & 0x7F;
+
+            // Calculate payload length and base header length from the wire prefix.
+            let payload_and_header = match payload_len_initial {
+                0..=125 => Some((payload_len_initial as usize, 2usize)),
+                126 if self.read_buf.len() >= 4 => Some((
+                    u16::from_be_bytes([self.read_buf[2], self.read_buf[3]]) as usize,
+                    4usize,
+                )),
+                127 if self.read_buf.len() >= 10 => {
+                    let len_u64 = u64::from_be_bytes([
+                        self.read_buf[2],
+                        self.read_buf[3],
+                        self.read_buf[4],
+                        self.read_buf[5],
+                        self.read_buf[6],
+                        self.read_buf[7],
+                        self.read_buf[8],
+                        self.read_buf[9],
+                    ]);
+                    let len = usize::try_from(len_u64).map_err(|_| {
+                        Error::PayloadTooLargeForPlatform {
+                            size: len_u64,
+                            max: usize::MAX as u64,
+                        }
+                    })?;
+                    Some((len, 10usize))
+                }
+                _ => None,
+            };
+
+            if let Some((payload_len, base_header_len)) = payload_and_header {
+                self.validator
+                    .validate_incoming(masked, rsv1, rsv2, rsv3, payload_len)?;
+
+                let header_len = base_header_len + if masked { 4 } else { 0 };
+                if self.read_buf.len() >= header_len {
+                    let total_size = header_len.checked_add(payload_len).ok_or(
+                        Error::PayloadTooLargeForPlatform {
+                            size: payload_len as u64,
+                            max: usize::MAX as u64,
+                        },
+                    )?;
+
+                    if self.read_buf.len() >= total_size {
+                        let frame_bytes = self.read_buf.split_to(total_size).freeze();
+                        let payload_start = header_len;
+                        let payload_end = payload_start + payload_len;
+
+                        let mut frame = if masked {
+                            let mask_offset = base_header_len;
+                            let mask = [
+                                frame_bytes[mask_offset],
+                                frame_bytes[mask_offset + 1],
+                                frame_bytes[mask_offset + 2],
+                                frame_bytes[mask_offset + 3],
+                            ];
+                            let mut payload = frame_bytes[payload_start..payload_end].to_vec();
+                            apply_mask_simd(&mut payload, mask);
+                            Frame::new(fin, opcode, payload)
+                        } else {
+                            Frame::new_from_bytes(
+                                fin,
+                                opcode,
+                                frame_bytes.slice(payload_start..payload_end),
+                            )
+                        };
+                        frame.rsv1 = rsv1;
+                        frame.rsv2 = rsv2;
+                        frame.rsv3 = rsv3;
+                        return Ok(Some(frame));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> WebSocketCodec<T> {
     /// Write a frame to the underlying stream (does not flush).
     ///
-    /// Clients automatically mask the frame; servers send unmasked.
+    /// Clients automatically mask the frame; servers send unmasked. Frames
+    /// queued with [`Self::buffer_frame`] are written out first.
     ///
     /// # Errors
     ///
     /// - `Error::FrameTooLarge` if payload exceeds configured limits
+    /// - `Error::WriteTimeout` if the write does not complete within `timeouts.write`
     /// - `Error::Io` if the write fails
     pub async fn write_frame(&mut self, frame: &Frame) -> Result<()> {
-        // Validate frame size before allocation
-        let payload_size = frame.payload().len();
-        self.config.limits.check_frame_size(payload_size)?;
+        self.buffer_frame(frame)?;
+        poll_fn(|cx| self.poll_write_buffered(cx)).await
+    }
 
-        let mask = if self.role.must_mask() {
-            Some(self.generate_mask()?)
-        } else {
-            None
-        };
+    /// Flush any buffered data to the underlying stream.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::WriteTimeout` if the flush does not complete within `timeouts.write`
+    /// - `Error::Io` if the flush fails
+    pub async fn flush(&mut self) -> Result<()> {
+        poll_fn(|cx| self.poll_flush(cx)).await
+    }
 
-        let wire_size = frame.wire_size(mask.is_some());
-        self.write_buf.clear();
-        self.write_buf.reserve(wire_size);
-        self.write_buf.resize(wire_size, 0);
+    /// Flush queued frames and shut down the write side of the stream.
+    ///
+    /// For TCP this sends a FIN; TLS streams send `close_notify` first.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::WriteTimeout` if the shutdown does not complete within `timeouts.write`
+    /// - `Error::Io` if the shutdown fails
+    pub async fn shutdown(&mut self) -> Result<()> {
+        poll_fn(|cx| self.poll_shutdown(cx)).await
+    }
 
-        let written = frame.write(&mut self.write_buf, mask)?;
-        self.io.write_all(&self.write_buf[..written]).await?;
+    /// Write out frames queued with [`Self::buffer_frame`] without flushing
+    /// the stream.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::WriteTimeout` if the stream stays blocked for `timeouts.write`
+    /// - `Error::Io` if the write fails
+    pub fn poll_write_buffered(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while !self.write_buf.is_empty() {
+            match Pin::new(&mut self.io).poll_write(cx, &self.write_buf) {
+                Poll::Ready(Ok(0)) => {
+                    self.write_timer = None;
+                    let err = std::io::Error::from(std::io::ErrorKind::WriteZero);
+                    return Poll::Ready(Err(err.into()));
+                }
+                Poll::Ready(Ok(n)) => self.write_buf.advance(n),
+                Poll::Ready(Err(e)) => {
+                    self.write_timer = None;
+                    return Poll::Ready(Err(e.into()));
+                }
+                Poll::Pending => return self.poll_write_timeout(cx),
+            }
+        }
+        self.write_timer = None;
 
-        // Shrink write buffer if significantly oversized
-        if self.write_buf.capacity() > 64 * 1024 && self.write_buf.capacity() > wire_size * 4 {
-            self.write_buf = BytesMut::with_capacity(8192);
+        // Shrink write buffer if a large message left it oversized
+        if self.write_buf.capacity() > 64 * 1024 {
+            self.write_buf = BytesMut::with_capacity(self.config.write_buffer_size);
         }
 
-        Ok(())
+        Poll::Ready(Ok(()))
     }
 
-    /// Flush any buffered data to the underlying stream.
-    pub async fn flush(&mut self) -> Result<()> {
-        self.io.flush().await?;
-        Ok(())
+    /// Write out queued frames and flush the stream.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::poll_write_buffered`].
+    pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.poll_write_buffered(cx))?;
+        match Pin::new(&mut self.io).poll_flush(cx) {
+            Poll::Ready(result) => {
+                self.write_timer = None;
+                result?;
+                self.needs_flush = false;
+                Poll::Ready(Ok(()))
+            }
+            Poll::Pending => self.poll_write_timeout(cx),
+        }
     }
 
-    /// Consume the codec and return the underlying I/O stream.
-    #[must_use]
-    pub fn into_inner(self) -> T {
-        self.io
+    /// Flush queued frames, then shut down the write side of the stream.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::poll_write_buffered`].
+    pub fn poll_shutdown(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.poll_flush(cx))?;
+        match Pin::new(&mut self.io).poll_shutdown(cx) {
+            Poll::Ready(result) => {
+                self.write_timer = None;
+                Poll::Ready(result.map_err(Error::from))
+            }
+            Poll::Pending => self.poll_write_timeout(cx),
+        }
+    }
+
+    /// Bound a blocked write or flush by `timeouts.write`, measured from
+    /// when the stream first stopped accepting data.
+    fn poll_write_timeout(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let Some(limit) = self.config.timeouts.as_ref().map(|t| t.write) else {
+            return Poll::Pending;
+        };
+        let timer = self
+            .write_timer
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(limit)));
+        ready!(timer.as_mut().poll(cx));
+        self.write_timer = None;
+        Poll::Ready(Err(Error::WriteTimeout))
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite> WebSocketCodec<T> {
+    /// Split into a read codec and a write codec over the two halves of the stream.
+    ///
+    /// Buffered incoming bytes and timing state stay with the read codec.
+    pub(crate) fn split(self) -> (WebSocketCodec<ReadHalf<T>>, WebSocketCodec<WriteHalf<T>>) {
+        let (read_io, write_io) = tokio::io::split(self.io);
+        let reader = WebSocketCodec {
+            io: read_io,
+            read_buf: self.read_buf,
+            write_buf: BytesMut::new(),
+            role: self.role,
+            config: self.config.clone(),
+            validator: self.validator.clone(),
+            last_frame_at: self.last_frame_at,
+            frame_started_at: self.frame_started_at,
+            read_timer: None,
+            write_timer: None,
+            needs_flush: false,
+        };
+        let writer = WebSocketCodec {
+            io: write_io,
+            read_buf: BytesMut::new(),
+            write_buf: self.write_buf,
+            role: self.role,
+            config: self.config,
+            validator: self.validator,
+            last_frame_at: self.last_frame_at,
+            frame_started_at: None,
+            read_timer: None,
+            write_timer: None,
+            needs_flush: self.needs_flush,
+        };
+        (reader, writer)
+    }
+
+    /// Rejoin codecs produced by [`Self::split`].
+    ///
+    /// The halves must come from the same stream.
+    pub(crate) fn unsplit(
+        reader: WebSocketCodec<ReadHalf<T>>,
+        writer: WebSocketCodec<WriteHalf<T>>,
+    ) -> Self
+    where
+        T: Unpin,
+    {
+        WebSocketCodec {
+            io: reader.io.unsplit(writer.io),
+            read_buf: reader.read_buf,
+            write_buf: writer.write_buf,
+            role: reader.role,
+            config: reader.config,
+            validator: reader.validator,
+            last_frame_at: reader.last_frame_at,
+            frame_started_at: reader.frame_started_at,
+            read_timer: None,
+            write_timer: None,
+            needs_flush: writer.needs_flush,
+        }
     }
 }
 
@@ -232,9 +523,8 @@ impl<T: AsyncRead + AsyncWrite + Unpin> WebSocketCodec<T> {
 mod tests {
     use super::*;
     use std::io::Cursor;
-    use std::pin::Pin;
-    use std::task::{Context, Poll};
-    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use std::time::Duration;
+    use tokio::io::AsyncWriteExt;
 
     struct MockStream {
         read_data: Cursor<Vec<u8>>,
@@ -572,4 +862,84 @@ mod tests {
             "Mask keys should be independently random, not derivable from previous key"
         );
     }
+
+    fn timeouts(read: u64, write: u64, idle: u64) -> crate::config::Timeouts {
+        crate::config::Timeouts::new(
+            Duration::from_secs(30),
+            Duration::from_secs(read),
+            Duration::from_secs(write),
+            Duration::from_secs(idle),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_read_frame_idle_timeout() {
+        let (_peer, io) = tokio::io::duplex(64);
+        let config = Config::server().with_timeouts(timeouts(5, 5, 10));
+        let mut codec = WebSocketCodec::new(io, Role::Server, config);
+
+        let start = Instant::now();
+        assert_eq!(codec.read_frame().await, Err(Error::IdleTimeout));
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_read_frame_timeout_on_partial_frame() {
+        let (mut peer, io) = tokio::io::duplex(64);
+        let config = Config::server().with_timeouts(timeouts(5, 5, 300));
+        let mut codec = WebSocketCodec::new(io, Role::Server, config);
+
+        // Header of a masked 5-byte text frame; the payload never arrives.
+        peer.write_all(&[0x81, 0x85, 0, 0, 0, 0, b'H'])
+            .await
+            .unwrap();
+        let start = Instant::now();
+        assert_eq!(codec.read_frame().await, Err(Error::ReadTimeout));
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_read_timeout_not_reset_by_trickled_bytes() {
+        let (mut peer, io) = tokio::io::duplex(64);
+        let config = Config::server().with_timeouts(timeouts(5, 5, 300));
+        let mut codec = WebSocketCodec::new(io, Role::Server, config);
+
+        let trickle = tokio::spawn(async move {
+            for byte in [0x81, 0x85, 0, 0, 0, 0, b'H', b'e', b'l', b'l'] {
+                peer.write_all(&[byte]).await.unwrap();
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            peer
+        });
+
+        assert_eq!(codec.read_frame().await, Err(Error::ReadTimeout));
+        drop(trickle.await.unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_write_frame_timeout() {
+        // A peer that never reads: the duplex buffer fills up and writes block.
+        let (_peer, io) = tokio::io::duplex(16);
+        let config = Config::server().with_timeouts(timeouts(5, 5, 300));
+        let mut codec = WebSocketCodec::new(io, Role::Server, config);
+
+        let frame = Frame::binary(vec![0u8; 1024]);
+        assert_eq!(codec.write_frame(&frame).await, Err(Error::WriteTimeout));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_no_timeouts_by_default() {
+        let (mut peer, io) = tokio::io::duplex(64);
+        let mut codec = WebSocketCodec::new(io, Role::Server, Config::server());
+
+        let writer = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(3600)).await;
+            peer.write_all(&[0x81, 0x80, 0, 0, 0, 0]).await.unwrap();
+            peer
+        });
+
+        let frame = codec.read_frame().await.unwrap();
+        assert_eq!(frame.opcode, OpCode::Text);
+        drop(writer.await.unwrap());
+    }
 }
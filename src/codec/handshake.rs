@@ -0,0 +1,119 @@
+//! Reading the HTTP head of the opening handshake from an async stream.
+
+use bytes::{Bytes, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::error::{Error, Result};
+use crate::protocol::find_head_end;
+
+const READ_CHUNK: usize = 1024;
+
+/// Read an HTTP request or response head, up to and including the blank line.
+///
+/// The stream is read in chunks, so a peer that pipelines frames behind the
+/// head may have some of them read along with it. Those bytes are returned as
+/// the second element and must be passed to
+/// [`Connection::from_parts`](crate::Connection::from_parts) so they are not
+/// lost.
+///
+/// # Errors
+///
+/// - `Error::HandshakeTooLarge` if no terminator is found within `max_size` bytes
+/// - `Error::ConnectionClosed` if the stream ends before the head is complete
+/// - `Error::Io` on read failure
+pub(crate) async fn read_http_head<S>(io: &mut S, max_size: usize) -> Result<(Vec<u8>, Bytes)>
+where
+    S: AsyncRead + Unpin,
+{
+    let mut buf = BytesMut::with_capacity(READ_CHUNK);
+    let mut scanned: usize = 0;
+
+    loop {
+        // Resume the search a few bytes back so a terminator split across
+        // reads is still found.
+        let start = scanned.saturating_sub(3);
+        if let Some(end) = find_head_end(&buf[start..]).map(|n| start + n) {
+            if end > max_size {
+                return Err(Error::HandshakeTooLarge {
+                    size: end,
+                    max: max_size,
+                });
+            }
+            let head = buf.split_to(end);
+            return Ok((head.to_vec(), buf.freeze()));
+        }
+        if buf.len() >= max_size {
+            return Err(Error::HandshakeTooLarge {
+                size: buf.len(),
+                max: max_size,
+            });
+        }
+        scanned = buf.len();
+
+        buf.reserve(READ_CHUNK);
+        if io.read_buf(&mut buf).await? == 0 {
+            return Err(Error::ConnectionClosed(None));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_read_head_returns_trailing_bytes() {
+        let data = b"GET / HTTP/1.1\r\nHost: x\r\n\r\n\x81\x00".to_vec();
+        let mut io = std::io::Cursor::new(data);
+
+        let (head, leftover) = read_http_head(&mut io, 8192).await.unwrap();
+        assert_eq!(head, b"GET / HTTP/1.1\r\nHost: x\r\n\r\n");
+        assert_eq!(&leftover[..], b"\x81\x00");
+    }
+
+    #[tokio::test]
+    async fn test_read_head_split_terminator() {
+        let (mut tx, mut rx) = tokio::io::duplex(64);
+        let reader = tokio::spawn(async move { read_http_head(&mut rx, 8192).await });
+
+        tx.write_all(b"GET / HTTP/1.1\r\nHost: x\r\n\r")
+            .await
+            .unwrap();
+        tokio::task::yield_now().await;
+        tx.write_all(b"\n").await.unwrap();
+
+        let (head, leftover) = reader.await.unwrap().unwrap();
+        assert!(head.ends_with(b"\r\n\r\n"));
+        assert!(leftover.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_read_head_too_large() {
+        let mut io = std::io::Cursor::new(vec![b'A'; 100]);
+        let result = read_http_head(&mut io, 64).await;
+        assert!(matches!(
+            result,
+            Err(Error::HandshakeTooLarge { max: 64, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_head_too_large_with_terminator() {
+        let mut data = vec![b'A'; 100];
+        data.extend_from_slice(b"\r\n\r\n");
+        let mut io = std::io::Cursor::new(data);
+        let result = read_http_head(&mut io, 64).await;
+        assert!(matches!(
+            result,
+            Err(Error::HandshakeTooLarge { max: 64, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_read_head_eof() {
+        let mut io = std::io::Cursor::new(b"GET / HTTP/1.1\r\n".to_vec());
+        let result = read_http_head(&mut io, 8192).await;
+        assert!(matches!(result, Err(Error::ConnectionClosed(None))));
+    }
+}
@@ -4,6 +4,10 @@
 
 #[cfg(feature = "async-tokio")]
 mod framed;
+#[cfg(feature = "async-tokio")]
+pub(crate) mod handshake;
 
 #[cfg(feature = "async-tokio")]
 pub use framed::WebSocketCodec;
+#[cfg(feature = "async-tokio")]
+pub(crate) use framed::poll_deadline;
@@ -1,7 +1,12 @@
 //! Configuration and limits for WebSocket connections.
 
+use std::fmt;
+use std::sync::Arc;
 use std::time::Duration;
 
+use crate::message::CloseCode;
+use crate::protocol::frame::MAX_CONTROL_FRAME_PAYLOAD;
+
 /// Configuration limits for WebSocket connections.
 ///
 /// These limits prevent resource exhaustion attacks and ensure
@@ -190,31 +195,34 @@ impl Limits {
 
 /// Timeout configuration for WebSocket connections.
 ///
-/// These timeouts help prevent DoS attacks and resource exhaustion.
-/// Note: Enforcement is the caller's responsibility; this is configuration only.
+/// These timeouts help prevent DoS attacks and resource exhaustion. They are
+/// enforced by [`WebSocketCodec`](crate::WebSocketCodec) and the handshake
+/// drivers in [`client`](crate::client) and [`server`](crate::server).
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Timeouts {
     /// Handshake timeout.
     ///
-    /// Maximum time to complete the WebSocket handshake.
+    /// Maximum time to exchange the HTTP upgrade request and response.
     /// Default: 30 seconds
     pub handshake: Duration,
 
     /// Read timeout.
     ///
-    /// Maximum time to wait for incoming data.
+    /// Maximum time to receive the rest of a frame once its first byte
+    /// has arrived.
     /// Default: 60 seconds
     pub read: Duration,
 
     /// Write timeout.
     ///
-    /// Maximum time to wait for outgoing data to be sent.
+    /// Maximum time for a single write or flush to complete.
     /// Default: 60 seconds
     pub write: Duration,
 
     /// Idle timeout.
     ///
-    /// Maximum time a connection can remain idle without activity.
+    /// Maximum time between complete incoming frames (including pings and
+    /// pongs). On expiry the connection sends a `GoingAway` close frame.
     /// Default: 300 seconds (5 minutes)
     pub idle: Duration,
 }
@@ -243,6 +251,168 @@ impl Timeouts {
     }
 }
 
+/// Payload carried by keepalive pings.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum PingPayload {
+    /// Empty payload.
+    #[default]
+    Empty,
+    /// An 8-byte big-endian counter, incremented for every ping.
+    ///
+    /// Makes each ping distinguishable so a late pong for an earlier ping is
+    /// not mistaken for the answer to the current one.
+    Counter,
+    /// The same bytes in every ping (at most 125 bytes).
+    Fixed(Vec<u8>),
+}
+
+/// Automatic keepalive pings.
+///
+/// When set on [`Config`], `Connection::recv` sends a ping every `interval`
+/// and expects a pong with the same payload within `pong_timeout`. If none
+/// arrives, a close frame with `close_code` is sent and `recv` fails with
+/// `Error::KeepaliveTimeout`.
+///
+/// Pings are only sent while `recv` is being awaited.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keepalive {
+    /// Time between pings.
+    ///
+    /// Default: 30 seconds
+    pub interval: Duration,
+
+    /// Maximum time to wait for the matching pong.
+    ///
+    /// Default: 10 seconds
+    pub pong_timeout: Duration,
+
+    /// Ping payload policy.
+    ///
+    /// Default: [`PingPayload::Empty`]
+    pub payload: PingPayload,
+
+    /// Close code sent when the pong deadline passes, typically
+    /// `GoingAway` (1001) or `InternalError` (1011).
+    ///
+    /// Default: `CloseCode::GoingAway`
+    pub close_code: CloseCode,
+}
+
+impl Default for Keepalive {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+            pong_timeout: Duration::from_secs(10),
+            payload: PingPayload::Empty,
+            close_code: CloseCode::GoingAway,
+        }
+    }
+}
+
+impl Keepalive {
+    /// Create a keepalive policy with the given ping interval and pong timeout.
+    #[must_use]
+    pub fn new(interval: Duration, pong_timeout: Duration) -> Self {
+        Self {
+            interval,
+            pong_timeout,
+            ..Default::default()
+        }
+    }
+
+    /// Set the ping payload policy.
+    ///
+    /// A [`PingPayload::Fixed`] payload must fit in a control frame (125
+    /// bytes); see [`Self::validate`].
+    #[must_use]
+    pub fn with_payload(mut self, payload: PingPayload) -> Self {
+        self.payload = payload;
+        self
+    }
+
+    /// Set the close code sent when a pong is missed.
+    ///
+    /// The code must be one that may be sent in a close frame; see
+    /// [`Self::validate`].
+    #[must_use]
+    pub fn with_close_code(mut self, code: CloseCode) -> Self {
+        self.close_code = code;
+        self
+    }
+
+    /// Validate that the pings and close frame this policy sends are valid
+    /// control frames. Connections check this before their first keepalive
+    /// ping, failing `recv` with the same error.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidConfig`](crate::Error::InvalidConfig) if a
+    /// [`PingPayload::Fixed`] payload is longer than 125 bytes, or if
+    /// `close_code` may not be sent in a close frame, such as 1005 (No Status
+    /// Received) or 1006 (Abnormal Closure); see [`CloseCode::is_valid`].
+    pub fn validate(&self) -> Result<(), crate::Error> {
+        if let PingPayload::Fixed(bytes) = &self.payload
+            && bytes.len() > MAX_CONTROL_FRAME_PAYLOAD
+        {
+            return Err(crate::Error::InvalidConfig(format!(
+                "keepalive ping payload is {} bytes; control frames allow at most {}",
+                bytes.len(),
+                MAX_CONTROL_FRAME_PAYLOAD
+            )));
+        }
+        if !self.close_code.is_valid() {
+            return Err(crate::Error::InvalidConfig(format!(
+                "keepalive close code {} cannot be sent in a close frame",
+                self.close_code.as_u16()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Callback choosing a subprotocol from the client's offers (server only).
+///
+/// The callback receives the protocols listed in the request's
+/// `Sec-WebSocket-Protocol` header, in the client's order, and returns the
+/// one to use. Returning a protocol the client did not offer is treated as
+/// no match.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// use rsws::config::SubprotocolSelector;
+///
+/// // Pick the newest "chat.vN" the client supports.
+/// let selector = SubprotocolSelector::new(|offered| {
+///     offered.iter().filter(|p| p.starts_with("chat.v")).max().cloned()
+/// });
+/// ```
+#[derive(Clone)]
+pub struct SubprotocolSelector(Arc<SelectFn>);
+
+type SelectFn = dyn Fn(&[String]) -> Option<String> + Send + Sync;
+
+impl SubprotocolSelector {
+    /// Wrap a selection callback.
+    pub fn new<F>(select: F) -> Self
+    where
+        F: Fn(&[String]) -> Option<String> + Send + Sync + 'static,
+    {
+        Self(Arc::new(select))
+    }
+
+    /// Run the callback on the client's offered protocols.
+    pub fn select(&self, offered: &[String]) -> Option<String> {
+        (self.0)(offered)
+    }
+}
+
+impl fmt::Debug for SubprotocolSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SubprotocolSelector").finish_non_exhaustive()
+    }
+}
+
 /// WebSocket connection configuration.
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -284,16 +454,52 @@ pub struct Config {
 
     /// Timeout configuration.
     ///
-    /// If `None`, no timeouts are configured (caller must implement their own).
+    /// If `None`, reads, writes and the handshake may wait indefinitely.
     /// Default: None
     pub timeouts: Option<Timeouts>,
 
+    /// Keepalive ping configuration.
+    ///
+    /// If `None`, no pings are sent automatically.
+    /// Default: None
+    pub keepalive: Option<Keepalive>,
+
     /// Allowed origins for CSWSH protection.
     ///
     /// If `Some`, only connections from these origins are allowed.
     /// If `None`, origin validation is disabled (not recommended for production).
     /// Default: None
     pub allowed_origins: Option<Vec<String>>,
+
+    /// Subprotocols the server supports, most preferred first (server only).
+    ///
+    /// The first of these that the client offered is selected. Ignored when
+    /// `subprotocol_selector` is set.
+    /// Default: empty (no subprotocol is selected)
+    pub subprotocols: Vec<String>,
+
+    /// Custom subprotocol selection (server only).
+    ///
+    /// Default: None
+    pub subprotocol_selector: Option<SubprotocolSelector>,
+
+    /// Reject the upgrade when no subprotocol matches (server only).
+    ///
+    /// If `true`, a request whose offers do not match gets
+    /// `400 Bad Request` and [`Error::UnsupportedSubprotocol`](crate::Error::UnsupportedSubprotocol).
+    /// Default: false
+    pub require_subprotocol: bool,
+
+    /// Fail the connection when the peer's data causes a receive error.
+    ///
+    /// If `true`, an error with a [`close_code`](crate::Error::close_code)
+    /// (protocol violation, invalid UTF-8, oversized message, ...) sends a
+    /// close frame with that code and moves the connection to `Closed`
+    /// before the error is returned, as RFC 6455 Section 7.1.7 requires.
+    /// Set to `false` to handle these errors yourself.
+    ///
+    /// Default: true
+    pub fail_on_protocol_error: bool,
 }
 
 impl Default for Config {
@@ -306,7 +512,12 @@ impl Default for Config {
             read_buffer_size: 8192,
             write_buffer_size: 8192,
             timeouts: None,
+            keepalive: None,
             allowed_origins: None,
+            subprotocols: Vec::new(),
+            subprotocol_selector: None,
+            require_subprotocol: false,
+            fail_on_protocol_error: true,
         }
     }
 }
@@ -353,6 +564,13 @@ impl Config {
         self
     }
 
+    /// Enable automatic keepalive pings.
+    #[must_use]
+    pub fn with_keepalive(mut self, keepalive: Keepalive) -> Self {
+        self.keepalive = Some(keepalive);
+        self
+    }
+
     /// Set allowed origins for CSWSH protection.
     ///
     /// Only connections with an Origin header matching one of these values
@@ -364,6 +582,41 @@ impl Config {
         self
     }
 
+    /// Set the subprotocols the server supports, most preferred first.
+    #[must_use]
+    pub fn with_subprotocols<I, S>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.subprotocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Choose subprotocols with a callback instead of the preference list.
+    #[must_use]
+    pub fn with_subprotocol_selector<F>(mut self, select: F) -> Self
+    where
+        F: Fn(&[String]) -> Option<String> + Send + Sync + 'static,
+    {
+        self.subprotocol_selector = Some(SubprotocolSelector::new(select));
+        self
+    }
+
+    /// Set whether the upgrade is rejected when no subprotocol matches.
+    #[must_use]
+    pub const fn with_require_subprotocol(mut self, require: bool) -> Self {
+        self.require_subprotocol = require;
+        self
+    }
+
+    /// Set whether receive errors caused by the peer fail the connection.
+    #[must_use]
+    pub const fn with_fail_on_protocol_error(mut self, fail: bool) -> Self {
+        self.fail_on_protocol_error = fail;
+        self
+    }
+
     /// Configure for server role (no masking, reject unmasked client frames).
     #[must_use]
     pub fn server() -> Self {
@@ -511,4 +764,57 @@ mod tests {
         let config = Config::default();
         assert!(config.timeouts.is_none());
     }
+
+    #[test]
+    fn test_keepalive_default() {
+        let keepalive = Keepalive::default();
+        assert_eq!(keepalive.interval, Duration::from_secs(30));
+        assert_eq!(keepalive.pong_timeout, Duration::from_secs(10));
+        assert_eq!(keepalive.payload, PingPayload::Empty);
+        assert_eq!(keepalive.close_code, CloseCode::GoingAway);
+        assert!(Config::default().keepalive.is_none());
+    }
+
+    #[test]
+    fn test_config_with_keepalive() {
+        let keepalive = Keepalive::new(Duration::from_secs(5), Duration::from_secs(2))
+            .with_payload(PingPayload::Counter)
+            .with_close_code(CloseCode::InternalError);
+        let config = Config::server().with_keepalive(keepalive.clone());
+        assert_eq!(config.keepalive, Some(keepalive));
+    }
+
+    #[test]
+    fn test_keepalive_accepts_max_control_payload() {
+        let payload = PingPayload::Fixed(vec![0u8; 125]);
+        let keepalive = Keepalive::default().with_payload(payload.clone());
+        assert_eq!(keepalive.payload, payload);
+    }
+
+    #[test]
+    fn test_keepalive_validate() {
+        assert_eq!(Keepalive::default().validate(), Ok(()));
+        let max = Keepalive::default().with_payload(PingPayload::Fixed(vec![0u8; 125]));
+        assert_eq!(max.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_keepalive_rejects_oversized_payload() {
+        let keepalive = Keepalive::default().with_payload(PingPayload::Fixed(vec![0u8; 126]));
+        assert!(matches!(
+            keepalive.validate(),
+            Err(crate::Error::InvalidConfig(ref msg)) if msg.contains("at most 125")
+        ));
+    }
+
+    #[test]
+    fn test_keepalive_rejects_unsendable_close_code() {
+        for code in [1005, 1006] {
+            let keepalive = Keepalive::default().with_close_code(CloseCode::Other(code));
+            assert!(matches!(
+                keepalive.validate(),
+                Err(crate::Error::InvalidConfig(ref msg)) if msg.contains(&code.to_string())
+            ));
+        }
+    }
 }
@@ -1,15 +1,26 @@
+use std::future::poll_fn;
+use std::pin::Pin;
+use std::task::{Context, Poll, ready};
+use std::time::Duration;
+
 use bytes::Bytes;
+use futures_core::Stream;
+use futures_sink::Sink;
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::{Instant, Sleep};
 
-use crate::codec::WebSocketCodec;
+use crate::codec::{WebSocketCodec, poll_deadline};
 use crate::config::Config;
 use crate::connection::fragmenter::MessageFragmenter;
+use crate::connection::keepalive::{KeepaliveAction, KeepaliveState};
+use crate::connection::shutdown::{CloseInFlight, ShutdownSignal};
+use crate::connection::split::{self, Parts, WsReader, WsWriter};
 use crate::connection::{ConnectionState, Role};
 use crate::error::{Error, Result};
-use crate::extensions::ExtensionRegistry;
-use crate::message::{CloseCode, CloseFrame, Message};
+use crate::extensions::{ExtensionMessage, ExtensionRegistry, ExtensionScope, RsvBits};
+use crate::message::{CloseCode, CloseFrame, CloseOutcome, Message};
 use crate::protocol::assembler::{AssembledMessage, MessageAssembler};
-use crate::protocol::{Frame, OpCode};
+use crate::protocol::{Frame, HandshakeRequest, OpCode};
 
 /// A WebSocket connection wrapping an async I/O stream.
 ///
@@ -38,12 +49,36 @@ use crate::protocol::{Frame, OpCode};
 ///     println!("Received: {:?}", msg);
 /// }
 /// ```
+///
+/// `Connection` is also a [`Stream`] of received messages and a [`Sink`] of
+/// outgoing ones, so it works with `StreamExt`/`SinkExt` combinators:
+///
+/// ```rust,ignore
+/// use futures::{StreamExt, TryStreamExt, future};
+///
+/// // `conn.split()` would pick `Connection::split`; name the futures one.
+/// let (sink, stream) = StreamExt::split(conn);
+/// stream
+///     .try_filter(|msg| future::ready(msg.is_text()))
+///     .forward(sink)
+///     .await?;
+/// ```
 pub struct Connection<T> {
     codec: WebSocketCodec<T>,
     state: ConnectionState,
     assembler: MessageAssembler,
     current_message_rsv_bits: u8,
     extensions: ExtensionRegistry,
+    keepalive: Option<KeepaliveState>,
+    keepalive_timer: Option<Pin<Box<Sleep>>>,
+    close_outcome: Option<CloseOutcome>,
+    // Answer to the latest ping, waiting for room in the write buffer. Older
+    // ones are dropped, as RFC 6455 Section 5.5.3 allows.
+    pending_pong: Option<Frame>,
+    subprotocol: Option<String>,
+    request: Option<HandshakeRequest>,
+    shutdown: Option<ShutdownSignal>,
+    going_away: Option<CloseInFlight>,
 }
 
 impl<T> Connection<T> {
@@ -77,17 +112,87 @@ impl<T> Connection<T> {
         role: Role,
         config: Config,
         extensions: ExtensionRegistry,
+    ) -> Self {
+        Self::from_parts(io, Bytes::new(), role, config, extensions)
+    }
+
+    /// Create a connection from a stream and bytes already read past the handshake.
+    ///
+    /// HTTP parsers usually read in chunks, so the peer's first frames can end
+    /// up in the same buffer as the handshake head. Pass those bytes as
+    /// `leftover`; they are decoded before anything is read from `io`.
+    ///
+    /// ## Arguments
+    ///
+    /// - `io`: The underlying async I/O stream
+    /// - `leftover`: Bytes received after the end of the HTTP head
+    /// - `role`: The connection role (Client or Server)
+    /// - `config`: Connection configuration
+    /// - `extensions`: Pre-configured extension registry
+    pub fn from_parts(
+        io: T,
+        leftover: Bytes,
+        role: Role,
+        config: Config,
+        extensions: ExtensionRegistry,
     ) -> Self {
         let assembler = MessageAssembler::new(config.clone());
+        let keepalive = config.keepalive.clone().map(KeepaliveState::new);
         Self {
-            codec: WebSocketCodec::new(io, role, config),
+            codec: WebSocketCodec::from_parts(io, leftover, role, config),
             state: ConnectionState::Open,
             assembler,
             current_message_rsv_bits: 0,
             extensions,
+            keepalive,
+            keepalive_timer: None,
+            close_outcome: None,
+            pending_pong: None,
+            subprotocol: None,
+            request: None,
+            shutdown: None,
+            going_away: None,
         }
     }
 
+    /// Record the subprotocol agreed on in the handshake.
+    ///
+    /// The client and server handshakes set this; use it when the handshake
+    /// was done elsewhere.
+    #[must_use]
+    pub fn with_subprotocol(mut self, protocol: Option<String>) -> Self {
+        self.subprotocol = protocol;
+        self
+    }
+
+    /// The subprotocol agreed on in the handshake, if any.
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.subprotocol.as_deref()
+    }
+
+    /// Keep the client's upgrade request with the connection.
+    ///
+    /// The server handshake sets this; use it when the handshake was done
+    /// elsewhere.
+    #[must_use]
+    pub fn with_request(mut self, request: HandshakeRequest) -> Self {
+        self.request = Some(request);
+        self
+    }
+
+    /// The client's upgrade request (server side only).
+    ///
+    /// Gives access to the request's headers, query parameters and cookies.
+    pub fn request(&self) -> Option<&HandshakeRequest> {
+        self.request.as_ref()
+    }
+
+    /// Close with `CloseCode::GoingAway` once `signal` fires.
+    pub(crate) fn with_shutdown(mut self, signal: ShutdownSignal) -> Self {
+        self.shutdown = Some(signal);
+        self
+    }
+
     /// Get the current connection state.
     pub fn state(&self) -> ConnectionState {
         self.state
@@ -100,18 +205,91 @@ impl<T> Connection<T> {
         self.state == ConnectionState::Open
     }
 
+    /// Round-trip time measured by the most recent answered keepalive ping.
+    ///
+    /// Returns `None` if keepalive is disabled or no pong has arrived yet.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.keepalive.as_ref().and_then(KeepaliveState::rtt)
+    }
+
+    /// Get a reference to the underlying stream, e.g. to read the TLS peer
+    /// certificates. Reading or writing through it would corrupt the framing.
+    pub fn get_ref(&self) -> &T {
+        self.codec.get_ref()
+    }
+
     /// Get mutable access to the extension registry.
     pub fn extensions_mut(&mut self) -> &mut ExtensionRegistry {
         &mut self.extensions
     }
 
+    pub(super) fn from_split_parts(parts: Parts<T>) -> Self {
+        Self {
+            codec: parts.codec,
+            state: parts.state,
+            assembler: parts.assembler,
+            current_message_rsv_bits: parts.current_message_rsv_bits,
+            extensions: parts.extensions,
+            keepalive: parts.keepalive,
+            keepalive_timer: None,
+            close_outcome: None,
+            pending_pong: None,
+            subprotocol: parts.subprotocol,
+            request: parts.request,
+            shutdown: parts.shutdown,
+            going_away: parts.going_away,
+        }
+    }
+
     fn sync_validator_extensions(&mut self) {
         self.codec
             .set_allowed_rsv_bits(self.extensions.negotiated_rsv_mask());
     }
+}
 
-    fn frame_rsv_bits(frame: &Frame) -> u8 {
-        ((frame.rsv1 as u8) << 6) | ((frame.rsv2 as u8) << 5) | ((frame.rsv3 as u8) << 4)
+impl<T: AsyncRead + AsyncWrite> Connection<T> {
+    /// Split the connection into independent reader and writer halves.
+    ///
+    /// The halves can be moved to different tasks: one awaiting
+    /// [`WsReader::recv`] while the other calls [`WsWriter::send`]. Pongs,
+    /// close replies and keepalive pings from the reader are sent between the
+    /// writer's messages, so frames are never interleaved, and the reader
+    /// never waits for a writer blocked on a full socket.
+    /// Use [`WsReader::reunite`] to get the connection back.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,ignore
+    /// let (mut reader, mut writer) = conn.split();
+    ///
+    /// tokio::spawn(async move {
+    ///     while let Some(text) = outgoing.recv().await {
+    ///         writer.send(Message::text(text)).await?;
+    ///     }
+    ///     Ok::<_, rsws::Error>(())
+    /// });
+    ///
+    /// while let Some(msg) = reader.recv().await? {
+    ///     println!("Received: {:?}", msg);
+    /// }
+    /// ```
+    pub fn split(mut self) -> (WsReader<T>, WsWriter<T>) {
+        self.sync_validator_extensions();
+        if let Some(pong) = self.pending_pong.take() {
+            let _ = self.codec.buffer_frame(&pong);
+        }
+        split::split(Parts {
+            codec: self.codec,
+            state: self.state,
+            assembler: self.assembler,
+            current_message_rsv_bits: self.current_message_rsv_bits,
+            extensions: self.extensions,
+            keepalive: self.keepalive,
+            subprotocol: self.subprotocol,
+            request: self.request,
+            shutdown: self.shutdown,
+            going_away: self.going_away,
+        })
     }
 }
 
@@ -129,127 +307,22 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
     /// - `Error::FrameTooLarge` if a fragment exceeds `limits.max_frame_size`
     /// - I/O errors from the underlying stream
     pub async fn send(&mut self, message: Message) -> Result<()> {
-        if !self.state.can_send() {
-            return Err(Error::ConnectionClosed(None));
-        }
-
-        self.sync_validator_extensions();
-
-        if let Some(code) = match &message {
-            Message::Close(Some(cf)) if !cf.code.is_valid() || cf.code.is_reserved() => {
-                Some(cf.code.as_u16())
-            }
-            _ => None,
-        } {
-            return Err(Error::InvalidCloseCode(code));
-        }
-
-        // Control frames are never fragmented
-        if message.is_control() {
-            let frame = Frame::from(message);
-            frame.validate()?;
-            self.codec.write_frame(&frame).await?;
-            self.codec.flush().await?;
-            return Ok(());
-        }
-
-        // Validate message size before processing
-        let payload = message.payload();
-        self.codec
-            .config()
-            .limits
-            .check_message_size(payload.len())?;
-
-        let opcode = if message.is_text() {
-            OpCode::Text
-        } else {
-            OpCode::Binary
-        };
-
-        let fragment_size = self.codec.config().fragment_size;
-
-        if payload.len() <= fragment_size {
-            // Small message: single frame with extension encoding
-            let mut frame = Frame::from(message);
-            self.extensions.encode(&mut frame)?;
-            self.codec.write_frame(&frame).await?;
-        } else {
-            // Large message: fragment into multiple frames
-            let fragmenter = MessageFragmenter::new(payload, opcode, fragment_size);
-            let mut is_first = true;
-
-            for mut frame in fragmenter {
-                // RFC 7692: Extension encoding only on first frame
-                if is_first && frame.opcode.is_data() {
-                    self.extensions.encode(&mut frame)?;
-                    is_first = false;
-                }
-                self.codec.write_frame(&frame).await?;
-            }
-        }
-
-        self.codec.flush().await?;
-        Ok(())
+        self.send_no_flush(message).await?;
+        poll_fn(|cx| self.poll_flush_all(cx)).await
     }
 
     /// Send message without flushing. Call flush() when ready.
     pub async fn send_no_flush(&mut self, message: Message) -> Result<()> {
+        poll_fn(|cx| Poll::Ready(self.start_going_away(cx))).await?;
+        if self.going_away.is_some() {
+            poll_fn(|cx| self.poll_flush_all(cx)).await?;
+        }
         if !self.state.can_send() {
             return Err(Error::ConnectionClosed(None));
         }
 
         self.sync_validator_extensions();
-
-        if let Some(code) = match &message {
-            Message::Close(Some(cf)) if !cf.code.is_valid() || cf.code.is_reserved() => {
-                Some(cf.code.as_u16())
-            }
-            _ => None,
-        } {
-            return Err(Error::InvalidCloseCode(code));
-        }
-
-        // Control frames are never fragmented
-        if message.is_control() {
-            let frame = Frame::from(message);
-            frame.validate()?;
-            self.codec.write_frame(&frame).await?;
-            return Ok(());
-        }
-
-        // Validate message size before processing
-        let payload = message.payload();
-        self.codec
-            .config()
-            .limits
-            .check_message_size(payload.len())?;
-
-        let opcode = if message.is_text() {
-            OpCode::Text
-        } else {
-            OpCode::Binary
-        };
-
-        let fragment_size = self.codec.config().fragment_size;
-
-        if payload.len() <= fragment_size {
-            let mut frame = Frame::from(message);
-            self.extensions.encode(&mut frame)?;
-            self.codec.write_frame(&frame).await?;
-        } else {
-            let fragmenter = MessageFragmenter::new(payload, opcode, fragment_size);
-            let mut is_first = true;
-
-            for mut frame in fragmenter {
-                if is_first && frame.opcode.is_data() {
-                    self.extensions.encode(&mut frame)?;
-                    is_first = false;
-                }
-                self.codec.write_frame(&frame).await?;
-            }
-        }
-
-        Ok(())
+        write_message(&mut self.codec, &mut self.extensions, message).await
     }
 
     /// Send multiple messages with single flush at end.
@@ -262,13 +335,14 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
 
     /// Flush pending writes to the underlying stream.
     pub async fn flush(&mut self) -> Result<()> {
-        self.codec.flush().await
+        poll_fn(|cx| self.poll_flush_all(cx)).await
     }
 
     /// Receive the next message from the WebSocket connection.
     ///
     /// This method handles:
     /// - Automatic pong response to ping frames
+    /// - Keepalive pings, if `config.keepalive` is set
     /// - Message reassembly from fragments
     /// - Close frame handling and response
     ///
@@ -277,41 +351,103 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
     ///
     /// ## Errors
     ///
-    /// - Protocol errors (invalid frame, UTF-8 violation, etc.)
+    /// - Protocol errors (invalid frame, UTF-8 violation, etc.); unless
+    ///   `config.fail_on_protocol_error` is off, a close frame with the
+    ///   error's [`close_code`](Error::close_code) is sent first and the
+    ///   connection is closed
+    /// - `Error::IdleTimeout` if no frame arrives within `timeouts.idle`; a
+    ///   `GoingAway` close frame is sent and the connection is closed
+    /// - `Error::ReadTimeout` if a frame is not completed within `timeouts.read`
+    /// - `Error::KeepaliveTimeout` if a keepalive ping is not answered in time;
+    ///   a close frame with `keepalive.close_code` is sent and the connection
+    ///   is closed
     /// - I/O errors from the underlying stream
     pub async fn recv(&mut self) -> Result<Option<Message>> {
+        let result = poll_fn(|cx| self.poll_recv(cx)).await;
+
+        // Pongs and close replies queued while receiving go out before returning.
+        if self.codec.needs_flush() || self.pending_pong.is_some() {
+            let flushed = poll_fn(|cx| self.poll_flush_all(cx)).await;
+            if result.is_ok() && self.state != ConnectionState::Closed {
+                flushed?;
+            }
+        }
+        result
+    }
+
+    /// Poll-based core of [`Self::recv`].
+    ///
+    /// Replies to the peer (pongs, close echoes, keepalive pings) are queued
+    /// in the codec's write buffer; they are written out on a best-effort
+    /// basis here and fully flushed by the caller.
+    fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Message>>> {
+        let result = ready!(self.poll_read_message(cx));
+        if let Err(ref e) = result
+            && let Some(code) = fail_code(self.codec.config(), e)
+        {
+            if self.state == ConnectionState::Open {
+                let frame = Frame::close(Some(code.as_u16()), &fail_reason(e));
+                let _ = self.codec.buffer_frame(&frame);
+                let _ = self.codec.poll_flush(cx);
+            }
+            self.state = ConnectionState::Closed;
+        }
+        Poll::Ready(result)
+    }
+
+    fn poll_read_message(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Message>>> {
         if !self.state.can_receive() {
-            return Ok(None);
+            return Poll::Ready(Ok(None));
+        }
+
+        // A blocked writer must not hold up reading, but write errors are reported.
+        if (self.codec.needs_flush() || self.pending_pong.is_some())
+            && let Poll::Ready(Err(e)) = self.poll_flush_all(cx)
+        {
+            return Poll::Ready(Err(e));
         }
 
         loop {
             self.sync_validator_extensions();
 
-            let frame = match self.codec.read_frame().await {
+            let mut frame = match ready!(self.poll_next_frame(cx)) {
                 Ok(f) => f,
                 Err(Error::ConnectionClosed(_)) => {
                     self.state = ConnectionState::Closed;
-                    return Ok(None);
+                    return Poll::Ready(Ok(None));
                 }
-                Err(e) => return Err(e),
+                Err(Error::IdleTimeout) => {
+                    if self.state == ConnectionState::Open {
+                        let frame =
+                            Frame::close(Some(CloseCode::GoingAway.as_u16()), "idle timeout");
+                        let _ = self.codec.buffer_frame(&frame);
+                        let _ = self.codec.poll_flush(cx);
+                    }
+                    self.state = ConnectionState::Closed;
+                    return Poll::Ready(Err(Error::IdleTimeout));
+                }
+                Err(e) => return Poll::Ready(Err(e)),
             };
 
             match frame.opcode {
                 OpCode::Ping => {
                     frame.validate()?;
                     let payload = frame.into_payload_bytes();
-                    let pong_frame = Frame::pong(payload.to_vec());
-                    self.codec.write_frame(&pong_frame).await?;
-                    self.codec.flush().await?;
-                    return Ok(Some(Message::Ping(payload)));
+                    self.pending_pong = Some(Frame::pong(payload.to_vec()));
+                    self.buffer_pong()?;
+                    let _ = self.poll_flush_all(cx);
+                    return Poll::Ready(Ok(Some(Message::Ping(payload))));
                 }
                 OpCode::Pong => {
                     frame.validate()?;
-                    return Ok(Some(Message::Pong(frame.into_payload_bytes())));
+                    if let Some(keepalive) = self.keepalive.as_mut() {
+                        keepalive.on_pong(frame.payload(), Instant::now());
+                    }
+                    return Poll::Ready(Ok(Some(Message::Pong(frame.into_payload_bytes()))));
                 }
                 OpCode::Close => {
                     frame.validate()?;
-                    let close_frame = self.parse_close_frame(&frame);
+                    let close_frame = parse_close_frame(&frame);
 
                     if self.state == ConnectionState::Open {
                         self.state = ConnectionState::Closing;
@@ -320,16 +456,23 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
                         } else {
                             Frame::close(None, "")
                         };
-                        let _ = self.codec.write_frame(&response).await;
-                        let _ = self.codec.flush().await;
+                        let _ = self.codec.buffer_frame(&response);
+                        let _ = self.codec.poll_flush(cx);
                     }
 
                     self.state = ConnectionState::Closed;
-                    return Ok(Some(Message::Close(close_frame)));
+                    self.close_outcome = Some(CloseOutcome::Clean(close_frame.clone()));
+                    return Poll::Ready(Ok(Some(Message::Close(close_frame))));
                 }
                 OpCode::Text | OpCode::Binary | OpCode::Continuation => {
-                    let frame_rsv_bits = Self::frame_rsv_bits(&frame);
                     frame.validate()?;
+                    if self.extensions.has_negotiated(ExtensionScope::PerFrame) {
+                        self.extensions.decode_frame(&mut frame)?;
+                    }
+                    if self.extensions.has_negotiated(ExtensionScope::PerFragment) {
+                        self.extensions.decode_fragment(&mut frame)?;
+                    }
+                    let frame_rsv_bits = frame_rsv_bits(&frame);
                     if frame.opcode != OpCode::Continuation && !self.assembler.is_assembling() {
                         self.current_message_rsv_bits = frame_rsv_bits;
                     }
@@ -338,20 +481,113 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
                         Ok(v) => v,
                         Err(e) => {
                             self.current_message_rsv_bits = 0;
-                            return Err(e);
+                            return Poll::Ready(Err(e));
                         }
                     };
 
                     if let Some(assembled) = assembled {
                         let rsv_bits = self.current_message_rsv_bits;
                         self.current_message_rsv_bits = 0;
-                        return Ok(Some(self.assembled_to_message(assembled, rsv_bits)?));
+                        return Poll::Ready(Ok(Some(decode_message(
+                            &mut self.extensions,
+                            assembled,
+                            rsv_bits,
+                        )?)));
                     }
                 }
             }
         }
     }
 
+    /// Queue the pending pong once the write buffer is below
+    /// `write_buffer_size`, so a peer that sends pings without reading the
+    /// pongs cannot grow the buffer without bound.
+    fn buffer_pong(&mut self) -> Result<()> {
+        if self.codec.buffered_len() < self.codec.config().write_buffer_size
+            && let Some(pong) = self.pending_pong.take()
+        {
+            self.codec.buffer_frame(&pong)?;
+        }
+        Ok(())
+    }
+
+    /// Flush the write buffer, followed by the pending pong if there is one.
+    fn poll_flush_all(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        loop {
+            ready!(self.codec.poll_flush(cx))?;
+            let Some(pong) = self.pending_pong.take() else {
+                // Anything queued before, including a `GoingAway`, is written.
+                self.going_away = None;
+                return Poll::Ready(Ok(()));
+            };
+            self.codec.buffer_frame(&pong)?;
+        }
+    }
+
+    /// Start the closing handshake with `GoingAway` once the shutdown signal
+    /// fired. The frame is only queued; the caller flushes it.
+    fn start_going_away(&mut self, cx: &mut Context<'_>) -> Result<()> {
+        if self.state == ConnectionState::Open
+            && let Some(shutdown) = self.shutdown.take_if(|s| s.poll(cx).is_ready())
+        {
+            let (frame, in_flight) = shutdown.going_away();
+            self.state = ConnectionState::Closing;
+            self.codec.buffer_frame(&frame)?;
+            self.going_away = Some(in_flight);
+        }
+        Ok(())
+    }
+
+    /// Poll for the next frame, sending keepalive pings while waiting.
+    ///
+    /// `poll_read_frame` keeps partially received data buffered, so the
+    /// keepalive timer and the shutdown signal can fire between reads without
+    /// losing anything.
+    fn poll_next_frame(&mut self, cx: &mut Context<'_>) -> Poll<Result<Frame>> {
+        loop {
+            if let Poll::Ready(result) = self.codec.poll_read_frame(cx) {
+                return Poll::Ready(result);
+            }
+
+            if self.state == ConnectionState::Open && self.shutdown.is_some() {
+                self.start_going_away(cx)?;
+                if self.going_away.is_some() {
+                    if let Poll::Ready(Err(e)) = self.poll_flush_all(cx) {
+                        return Poll::Ready(Err(e));
+                    }
+                    continue;
+                }
+            }
+
+            let keepalive = match self.keepalive.as_mut() {
+                Some(keepalive) if self.state == ConnectionState::Open => keepalive,
+                _ => return Poll::Pending,
+            };
+            ready!(poll_deadline(
+                &mut self.keepalive_timer,
+                keepalive.deadline(),
+                cx
+            ));
+
+            match keepalive.on_deadline(Instant::now())? {
+                KeepaliveAction::Ping(payload) => {
+                    self.codec.buffer_frame(&Frame::ping(payload.to_vec()))?;
+                    if let Poll::Ready(Err(e)) = self.codec.poll_flush(cx) {
+                        return Poll::Ready(Err(e));
+                    }
+                }
+                KeepaliveAction::Expired => {
+                    let code = keepalive.close_code();
+                    let frame = Frame::close(Some(code.as_u16()), "keepalive timeout");
+                    let _ = self.codec.buffer_frame(&frame);
+                    let _ = self.codec.poll_flush(cx);
+                    self.state = ConnectionState::Closed;
+                    return Poll::Ready(Err(Error::KeepaliveTimeout));
+                }
+            }
+        }
+    }
+
     /// Send a ping frame.
     ///
     /// This is a convenience method that wraps `send(Message::Ping(...))`.
@@ -377,7 +613,7 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
     /// - `reason`: Human-readable reason for closing
     ///
     /// This does not close the underlying stream; you should drop the
-    /// `Connection` after calling this.
+    /// `Connection` after calling this, or use [`Self::close_and_wait`].
     pub async fn close(&mut self, code: CloseCode, reason: &str) -> Result<()> {
         if self.state != ConnectionState::Open {
             return Ok(());
@@ -398,58 +634,310 @@ impl<T: AsyncRead + AsyncWrite + Unpin> Connection<T> {
         Ok(())
     }
 
-    fn parse_close_frame(&self, frame: &Frame) -> Option<CloseFrame> {
-        let payload = frame.payload();
-        if payload.len() >= 2 {
-            let code = u16::from_be_bytes([payload[0], payload[1]]);
-            match std::str::from_utf8(&payload[2..]) {
-                Ok(reason) => Some(CloseFrame::new(
-                    CloseCode::from_u16(code),
-                    reason.to_owned(),
-                )),
-                Err(_) => Some(CloseFrame::new(CloseCode::InvalidPayload, "")),
+    /// Close the connection and wait for the closing handshake to finish.
+    ///
+    /// Sends a close frame (unless one was already sent), discards incoming
+    /// messages until the peer's close frame arrives, then shuts down the
+    /// underlying stream. Per RFC 6455 Section 7.1.1 the server closes the
+    /// TCP connection first: a server shuts down right away, while a client
+    /// waits for the server's EOF before shutting down its side.
+    ///
+    /// All of this, including the client's wait for EOF, is bounded by
+    /// `timeout`. The connection is `Closed` afterwards.
+    ///
+    /// ## Errors
+    ///
+    /// - `Error::InvalidCloseCode` if `code` may not be sent on the wire
+    ///
+    /// Any later failure (I/O or protocol error, EOF, the timeout passing)
+    /// is reported as `CloseOutcome::Abnormal` rather than an error.
+    pub async fn close_and_wait(
+        &mut self,
+        code: CloseCode,
+        reason: &str,
+        timeout: Duration,
+    ) -> Result<CloseOutcome> {
+        if code.is_reserved() || !code.is_valid() {
+            return Err(Error::InvalidCloseCode(code.as_u16()));
+        }
+
+        let deadline = Instant::now() + timeout;
+        let outcome = tokio::time::timeout_at(deadline, self.finish_close(code, reason))
+            .await
+            .unwrap_or(CloseOutcome::Abnormal);
+        self.state = ConnectionState::Closed;
+
+        if self.codec.role() == Role::Client && outcome.is_clean() {
+            let _ = tokio::time::timeout_at(deadline, async {
+                while self.codec.read_frame().await.is_ok() {}
+            })
+            .await;
+        }
+        let _ = tokio::time::timeout_at(deadline, self.codec.shutdown()).await;
+
+        Ok(outcome)
+    }
+
+    /// Send our close frame if needed and read until the peer's arrives.
+    async fn finish_close(&mut self, code: CloseCode, reason: &str) -> CloseOutcome {
+        if self.close(code, reason).await.is_err() {
+            return CloseOutcome::Abnormal;
+        }
+
+        loop {
+            match self.recv().await {
+                Ok(Some(Message::Close(frame))) => return CloseOutcome::Clean(frame),
+                Ok(Some(_)) => {}
+                // Already closed, either by an earlier close frame or abnormally.
+                Ok(None) => return self.close_outcome.clone().unwrap_or(CloseOutcome::Abnormal),
+                Err(_) => return CloseOutcome::Abnormal,
             }
-        } else if payload.is_empty() {
-            None
-        } else {
-            Some(CloseFrame::new(
-                CloseCode::ProtocolError,
-                "Invalid close frame",
-            ))
         }
     }
+}
 
-    fn assembled_to_message(
-        &mut self,
-        assembled: AssembledMessage,
-        rsv_bits: u8,
-    ) -> Result<Message> {
-        let payload = if rsv_bits != 0 && self.extensions.negotiated_count() > 0 {
-            let mut frame = Frame::new_from_bytes(true, assembled.opcode, assembled.payload);
-            frame.rsv1 = (rsv_bits & 0x40) != 0;
-            frame.rsv2 = (rsv_bits & 0x20) != 0;
-            frame.rsv3 = (rsv_bits & 0x10) != 0;
-            self.extensions.decode(&mut frame)?;
-            frame.into_payload_bytes()
-        } else {
-            assembled.payload
-        };
+/// Messages received from the peer.
+///
+/// Each item is what [`Connection::recv`] would return; the stream ends once
+/// the connection is closed. Pongs and close replies are written out while
+/// polling, but a peer that stops reading does not stall the stream. While
+/// `config.write_buffer_size` bytes are waiting, only the pong for the latest
+/// ping is kept.
+impl<T: AsyncRead + AsyncWrite + Unpin> Stream for Connection<T> {
+    type Item = Result<Message>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let item = ready!(this.poll_recv(cx)).transpose();
+
+        // Write errors surface from the next poll or send.
+        if this.codec.needs_flush() || this.pending_pong.is_some() {
+            let _ = this.poll_flush_all(cx);
+        }
+        Poll::Ready(item)
+    }
+}
+
+/// Messages sent to the peer.
+///
+/// `start_send` encodes the message into the write buffer; `poll_ready`
+/// applies backpressure once `config.write_buffer_size` bytes are queued.
+/// Sending `Message::Close` starts the closing handshake, and `poll_close`
+/// sends a `Normal` close frame if none was sent yet before flushing. The
+/// underlying stream is left open so the peer's close reply can still be
+/// received.
+impl<T: AsyncRead + AsyncWrite + Unpin> Sink<Message> for Connection<T> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        this.start_going_away(cx)?;
+        if this.going_away.is_some() {
+            ready!(this.poll_flush_all(cx))?;
+        }
+        if this.codec.buffered_len() >= this.codec.config().write_buffer_size {
+            ready!(this.codec.poll_write_buffered(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: Message) -> Result<()> {
+        let this = self.get_mut();
+        if !this.state.can_send() {
+            return Err(Error::ConnectionClosed(None));
+        }
+
+        let is_close = matches!(message, Message::Close(_));
+        this.sync_validator_extensions();
+        buffer_message(&mut this.codec, &mut this.extensions, message)?;
+        if is_close && this.state == ConnectionState::Open {
+            this.state = ConnectionState::Closing;
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().poll_flush_all(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        if this.state == ConnectionState::Open {
+            this.codec
+                .buffer_frame(&Frame::close(Some(CloseCode::Normal.as_u16()), ""))?;
+            this.state = ConnectionState::Closing;
+        }
+        this.codec.poll_flush(cx)
+    }
+}
+
+/// Write a data or control message as one or more frames (does not flush).
+///
+/// Shared by [`Connection`] and [`WsWriter`](super::WsWriter).
+pub(super) async fn write_message<W: AsyncWrite + Unpin>(
+    codec: &mut WebSocketCodec<W>,
+    extensions: &mut ExtensionRegistry,
+    message: Message,
+) -> Result<()> {
+    buffer_message(codec, extensions, message)?;
+    poll_fn(|cx| codec.poll_write_buffered(cx)).await
+}
 
-        match assembled.opcode {
-            OpCode::Text => {
-                let text = String::from_utf8(payload.to_vec()).map_err(|_| Error::InvalidUtf8)?;
-                Ok(Message::Text(text))
+/// Encode a data or control message into the codec's write buffer.
+pub(super) fn buffer_message<W>(
+    codec: &mut WebSocketCodec<W>,
+    extensions: &mut ExtensionRegistry,
+    message: Message,
+) -> Result<()> {
+    if let Some(code) = match &message {
+        Message::Close(Some(cf)) if !cf.code.is_valid() || cf.code.is_reserved() => {
+            Some(cf.code.as_u16())
+        }
+        _ => None,
+    } {
+        return Err(Error::InvalidCloseCode(code));
+    }
+
+    // Control frames are never fragmented
+    if message.is_control() {
+        let frame = Frame::from(message);
+        frame.validate()?;
+        codec.buffer_frame(&frame)?;
+        return Ok(());
+    }
+
+    // Validate message size before processing
+    codec
+        .config()
+        .limits
+        .check_message_size(message.payload().len())?;
+
+    // RFC 7692 Section 6.1: per-message extensions transform the whole
+    // message, and the result is fragmented. Only the first fragment carries
+    // their RSV bits. Per-fragment, then per-frame extensions see each
+    // fragment.
+    let mut message = ExtensionMessage::from_frame(Frame::from(message));
+    extensions.encode_message(&mut message)?;
+
+    let fragment_size = codec.config().fragment_size;
+    if message.payload.len() <= fragment_size {
+        let mut frame = message.into_frame();
+        extensions.encode_fragment(&mut frame)?;
+        extensions.encode_frame(&mut frame)?;
+        codec.buffer_frame(&frame)?;
+    } else {
+        let fragmenter = MessageFragmenter::new(&message.payload, message.opcode, fragment_size);
+        let mut is_first = true;
+
+        for mut fragment in fragmenter {
+            if is_first {
+                fragment.rsv1 = message.rsv.rsv1;
+                fragment.rsv2 = message.rsv.rsv2;
+                fragment.rsv3 = message.rsv.rsv3;
+                is_first = false;
             }
-            OpCode::Binary => Ok(Message::Binary(payload)),
-            _ => Err(Error::ProtocolViolation("Unexpected opcode".into())),
+            extensions.encode_fragment(&mut fragment)?;
+            extensions.encode_frame(&mut fragment)?;
+            codec.buffer_frame(&fragment)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Close code for failing the connection after a receive error, if the
+/// configuration asks for it.
+pub(super) fn fail_code(config: &Config, error: &Error) -> Option<CloseCode> {
+    if config.fail_on_protocol_error {
+        error.close_code()
+    } else {
+        None
+    }
+}
+
+/// Close reason for a failed connection: the error message, cut to fit in a
+/// control frame.
+pub(super) fn fail_reason(error: &Error) -> String {
+    let mut reason = error.to_string();
+    if reason.len() > 123 {
+        let mut end = 123;
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        reason.truncate(end);
+    }
+    reason
+}
+
+pub(super) fn frame_rsv_bits(frame: &Frame) -> u8 {
+    RsvBits {
+        rsv1: frame.rsv1,
+        rsv2: frame.rsv2,
+        rsv3: frame.rsv3,
+    }
+    .mask()
+}
+
+pub(super) fn parse_close_frame(frame: &Frame) -> Option<CloseFrame> {
+    let payload = frame.payload();
+    if payload.len() >= 2 {
+        let code = u16::from_be_bytes([payload[0], payload[1]]);
+        match std::str::from_utf8(&payload[2..]) {
+            Ok(reason) => Some(CloseFrame::new(
+                CloseCode::from_u16(code),
+                reason.to_owned(),
+            )),
+            Err(_) => Some(CloseFrame::new(CloseCode::InvalidPayload, "")),
+        }
+    } else if payload.is_empty() {
+        None
+    } else {
+        Some(CloseFrame::new(
+            CloseCode::ProtocolError,
+            "Invalid close frame",
+        ))
+    }
+}
+
+/// Turn a reassembled message into a [`Message`], running it through the
+/// negotiated per-message extensions. `rsv_bits` are the first frame's.
+pub(super) fn decode_message(
+    extensions: &mut ExtensionRegistry,
+    assembled: AssembledMessage,
+    rsv_bits: u8,
+) -> Result<Message> {
+    let payload = if extensions.has_negotiated(ExtensionScope::PerMessage) {
+        let mut message = ExtensionMessage {
+            opcode: assembled.opcode,
+            payload: assembled.payload,
+            rsv: RsvBits::from_mask(rsv_bits),
+        };
+        extensions.decode_message(&mut message)?;
+        message.payload
+    } else {
+        assembled.payload
+    };
+
+    into_message(assembled.opcode, payload)
+}
+
+pub(super) fn into_message(opcode: OpCode, payload: Bytes) -> Result<Message> {
+    match opcode {
+        OpCode::Text => {
+            let text = String::from_utf8(payload.to_vec()).map_err(|_| Error::InvalidUtf8)?;
+            Ok(Message::Text(text))
         }
+        OpCode::Binary => Ok(Message::Binary(payload)),
+        _ => Err(Error::ProtocolViolation("Unexpected opcode".into())),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::extensions::{Extension, ExtensionOffer, ExtensionParam, RsvBits};
+    use crate::connection::PendingCloses;
+    use crate::connection::shutdown::SHUTDOWN_REASON;
+    use crate::extensions::{Direction, Extension, ExtensionOffer, ExtensionParam};
     use std::io::Cursor;
     use std::pin::Pin;
     use std::sync::Arc;
@@ -519,6 +1007,65 @@ mod tests {
         }
     }
 
+    /// Flags messages with RSV2 and XORs every byte with a counter running
+    /// across the message's fragments.
+    #[derive(Default)]
+    struct FragmentXorExtension {
+        counter: u8,
+    }
+
+    impl Extension for FragmentXorExtension {
+        fn name(&self) -> &str {
+            "x-fragment-xor"
+        }
+
+        fn rsv_bits(&self) -> RsvBits {
+            RsvBits::from_mask(0x20)
+        }
+
+        fn scope(&self) -> ExtensionScope {
+            ExtensionScope::PerFragment
+        }
+
+        fn negotiate(&mut self, _params: &[ExtensionParam]) -> Result<Vec<ExtensionParam>> {
+            Ok(vec![])
+        }
+
+        fn encode(&mut self, _frame: &mut Frame) -> Result<()> {
+            Ok(())
+        }
+
+        fn decode(&mut self, _frame: &mut Frame) -> Result<()> {
+            Ok(())
+        }
+
+        fn begin_message(
+            &mut self,
+            direction: Direction,
+            _opcode: OpCode,
+            rsv: &mut RsvBits,
+        ) -> Result<()> {
+            self.counter = 0;
+            rsv.rsv2 = direction == Direction::Encode;
+            Ok(())
+        }
+
+        fn fragment(
+            &mut self,
+            _direction: Direction,
+            payload: &mut Bytes,
+            _fin: bool,
+        ) -> Result<()> {
+            let mut data = payload.to_vec();
+            for byte in &mut data {
+                *byte ^= self.counter;
+                self.counter = self.counter.wrapping_add(1);
+            }
+            *payload = data.into();
+            Ok(())
+        }
+    }
+
     impl MockStream {
         fn new(data: Vec<u8>) -> Self {
             Self {
@@ -822,4 +1369,533 @@ mod tests {
         assert!(matches!(msg, Message::Text(ref s) if s == "Hello"));
         assert!(decoded.load(Ordering::SeqCst));
     }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_idle_timeout_sends_going_away() {
+        use tokio::io::AsyncReadExt;
+
+        let (mut peer, io) = tokio::io::duplex(256);
+        let timeouts = crate::config::Timeouts::new(
+            std::time::Duration::from_secs(30),
+            std::time::Duration::from_secs(5),
+            std::time::Duration::from_secs(5),
+            std::time::Duration::from_secs(10),
+        );
+        let config = Config::server().with_timeouts(timeouts);
+        let mut conn = Connection::new(io, Role::Server, config);
+
+        assert_eq!(conn.recv().await, Err(Error::IdleTimeout));
+        assert_eq!(conn.state(), ConnectionState::Closed);
+
+        let mut buf = [0u8; 16];
+        let n = peer.read(&mut buf).await.unwrap();
+        assert_eq!(&buf[..2], &[0x88, 14]);
+        assert_eq!(u16::from_be_bytes([buf[2], buf[3]]), 1001);
+        assert_eq!(&buf[4..n], b"idle timeout");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_ping_pong_rtt() {
+        use crate::config::{Keepalive, PingPayload};
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (mut peer, io) = tokio::io::duplex(256);
+        let keepalive = Keepalive::new(
+            std::time::Duration::from_secs(30),
+            std::time::Duration::from_secs(10),
+        )
+        .with_payload(PingPayload::Counter);
+        let config = Config::server().with_keepalive(keepalive);
+        let mut conn = Connection::new(io, Role::Server, config);
+        assert!(conn.rtt().is_none());
+
+        let peer_task = tokio::spawn(async move {
+            let mut ping = [0u8; 10];
+            peer.read_exact(&mut ping).await.unwrap();
+            assert_eq!(&ping[..2], &[0x89, 0x08]);
+            assert_eq!(&ping[2..], &1u64.to_be_bytes());
+
+            tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+            let mut pong = vec![0x8a, 0x88, 0, 0, 0, 0];
+            pong.extend_from_slice(&ping[2..]);
+            peer.write_all(&pong).await.unwrap();
+            peer
+        });
+
+        let msg = conn.recv().await.unwrap();
+        assert_eq!(
+            msg,
+            Some(Message::Pong(Bytes::copy_from_slice(&1u64.to_be_bytes())))
+        );
+        assert_eq!(conn.rtt(), Some(std::time::Duration::from_millis(250)));
+        drop(peer_task.await.unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_keepalive_missing_pong_closes() {
+        use crate::config::Keepalive;
+        use tokio::io::AsyncReadExt;
+
+        let (mut peer, io) = tokio::io::duplex(256);
+        let keepalive = Keepalive::new(
+            std::time::Duration::from_secs(30),
+            std::time::Duration::from_secs(10),
+        )
+        .with_close_code(CloseCode::InternalError);
+        let config = Config::server().with_keepalive(keepalive);
+        let mut conn = Connection::new(io, Role::Server, config);
+
+        let start = Instant::now();
+        assert_eq!(conn.recv().await, Err(Error::KeepaliveTimeout));
+        assert_eq!(start.elapsed(), std::time::Duration::from_secs(40));
+        assert_eq!(conn.state(), ConnectionState::Closed);
+
+        let mut buf = [0u8; 64];
+        let n = peer.read(&mut buf).await.unwrap();
+        // Empty ping, then the close frame.
+        assert_eq!(&buf[..2], &[0x89, 0x00]);
+        assert_eq!(buf[2], 0x88);
+        assert_eq!(u16::from_be_bytes([buf[4], buf[5]]), 1011);
+        assert_eq!(&buf[6..n], b"keepalive timeout");
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_signal_sends_going_away() {
+        let (mut client, server) = duplex_pair();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let mut server = server.with_shutdown(ShutdownSignal::new(shutdown_rx, Default::default()));
+
+        client.send(Message::text("before")).await.unwrap();
+        assert_eq!(server.recv().await, Ok(Some(Message::text("before"))));
+
+        shutdown_tx.send(true).unwrap();
+        let server_task = tokio::spawn(async move {
+            let msg = server.recv().await;
+            (server, msg)
+        });
+
+        // The client echoes the close frame while reading it.
+        assert_eq!(
+            client.recv().await.unwrap(),
+            Some(Message::Close(Some(CloseFrame::new(
+                CloseCode::GoingAway,
+                SHUTDOWN_REASON
+            ))))
+        );
+        let (mut server, msg) = server_task.await.unwrap();
+        assert!(matches!(msg, Ok(Some(Message::Close(_)))));
+        assert_eq!(server.state(), ConnectionState::Closed);
+        assert_eq!(server.recv().await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_signal_sends_going_away_from_send() {
+        let (mut client, server) = duplex_pair();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let pending = Arc::new(PendingCloses::default());
+        let mut server =
+            server.with_shutdown(ShutdownSignal::new(shutdown_rx, Arc::clone(&pending)));
+
+        shutdown_tx.send(true).unwrap();
+        assert_eq!(
+            server.send(Message::text("late")).await,
+            Err(Error::ConnectionClosed(None))
+        );
+        assert_eq!(server.state(), ConnectionState::Closing);
+        // The close frame is written, so the server need not wait for it.
+        pending.wait().await;
+
+        assert_eq!(
+            client.recv().await.unwrap(),
+            Some(Message::Close(Some(CloseFrame::new(
+                CloseCode::GoingAway,
+                SHUTDOWN_REASON
+            ))))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_per_fragment_extension_roundtrip() {
+        let registry = || {
+            let mut registry = ExtensionRegistry::new();
+            registry
+                .add(Box::new(FragmentXorExtension::default()))
+                .unwrap();
+            registry
+                .configure(&[ExtensionOffer::new("x-fragment-xor")])
+                .unwrap();
+            registry
+        };
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let mut client = Connection::with_extensions(
+            client_io,
+            Role::Client,
+            Config::client().with_fragment_size(4),
+            registry(),
+        );
+        let mut server =
+            Connection::with_extensions(server_io, Role::Server, Config::server(), registry());
+
+        for text in ["fragmented across frames", "again"] {
+            client.send(Message::text(text)).await.unwrap();
+            assert_eq!(server.recv().await, Ok(Some(Message::text(text))));
+        }
+    }
+
+    fn duplex_pair() -> (
+        Connection<tokio::io::DuplexStream>,
+        Connection<tokio::io::DuplexStream>,
+    ) {
+        let (client, server) = tokio::io::duplex(4096);
+        (
+            Connection::new(client, Role::Client, Config::client()),
+            Connection::new(server, Role::Server, Config::server()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_stream_yields_messages_until_close() {
+        use futures::StreamExt;
+
+        let (mut client, server) = duplex_pair();
+        client.send(Message::text("one")).await.unwrap();
+        client.send(Message::binary(vec![2u8])).await.unwrap();
+        client.close(CloseCode::Normal, "done").await.unwrap();
+
+        let messages: Vec<_> = server.collect().await;
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0], Ok(Message::text("one")));
+        assert_eq!(messages[1], Ok(Message::binary(vec![2u8])));
+        assert!(
+            matches!(messages[2], Ok(Message::Close(Some(ref cf))) if cf.code == CloseCode::Normal)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_answers_ping() {
+        use futures::StreamExt;
+
+        let (mut client, mut server) = duplex_pair();
+        client.ping(&b"hb"[..]).await.unwrap();
+
+        let msg = server.next().await.unwrap().unwrap();
+        assert_eq!(msg, Message::Ping(Bytes::from_static(b"hb")));
+        let pong = client.recv().await.unwrap();
+        assert_eq!(pong, Some(Message::Pong(Bytes::from_static(b"hb"))));
+    }
+
+    #[tokio::test]
+    async fn test_ping_flood_keeps_only_latest_pong() {
+        use futures::StreamExt;
+
+        // The peer sends pings but does not read the pongs until the end.
+        let (peer, io) = tokio::io::duplex(1024);
+        let mut server = Connection::new(io, Role::Server, Config::server());
+        let mut peer = WebSocketCodec::new(peer, Role::Client, Config::client());
+        const PINGS: usize = 500;
+        for i in 0..PINGS {
+            let payload = format!("{i:0>100}");
+            peer.buffer_frame(&Frame::ping(payload)).unwrap();
+        }
+        let pinger = tokio::spawn(async move {
+            peer.flush().await.unwrap();
+            peer
+        });
+
+        for _ in 0..PINGS {
+            let msg = server.next().await.unwrap().unwrap();
+            assert!(matches!(msg, Message::Ping(_)));
+        }
+        let write_buffer_size = server.codec.config().write_buffer_size;
+        assert!(server.codec.buffered_len() < write_buffer_size + 128);
+
+        // Once the peer reads again, the answer to the last ping goes out.
+        let mut peer = pinger.await.unwrap();
+        let flush = tokio::spawn(async move { server.flush().await });
+        let last = format!("{:0>100}", PINGS - 1);
+        let mut pongs = 0;
+        loop {
+            let frame = peer.read_frame().await.unwrap();
+            assert_eq!(frame.opcode, OpCode::Pong);
+            pongs += 1;
+            if frame.payload() == last.as_bytes() {
+                break;
+            }
+        }
+        assert!(pongs < PINGS);
+        flush.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sink_send_all_and_close() {
+        use futures::{SinkExt, StreamExt, stream};
+
+        let (mut client, mut server) = duplex_pair();
+        let mut outgoing = stream::iter(vec![Ok(Message::text("a")), Ok(Message::text("b"))]);
+        client.send_all(&mut outgoing).await.unwrap();
+        SinkExt::close(&mut client).await.unwrap();
+        assert_eq!(client.state(), ConnectionState::Closing);
+
+        assert_eq!(server.next().await, Some(Ok(Message::text("a"))));
+        assert_eq!(server.next().await, Some(Ok(Message::text("b"))));
+        assert!(matches!(
+            server.next().await,
+            Some(Ok(Message::Close(Some(ref cf)))) if cf.code == CloseCode::Normal
+        ));
+        assert_eq!(server.next().await, None);
+
+        // The close reply completes the handshake on the sink side.
+        assert!(matches!(client.next().await, Some(Ok(Message::Close(_)))));
+        assert_eq!(client.state(), ConnectionState::Closed);
+        assert_eq!(
+            SinkExt::send(&mut client, Message::text("late")).await,
+            Err(Error::ConnectionClosed(None))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sink_close_message_is_not_repeated() {
+        use futures::{SinkExt, StreamExt};
+
+        let (mut client, mut server) = duplex_pair();
+        let close = Message::Close(Some(CloseFrame::new(CloseCode::GoingAway, "bye")));
+        SinkExt::send(&mut client, close).await.unwrap();
+        SinkExt::close(&mut client).await.unwrap();
+        drop(client);
+
+        assert!(matches!(
+            server.next().await,
+            Some(Ok(Message::Close(Some(ref cf)))) if cf.code == CloseCode::GoingAway
+        ));
+        assert_eq!(server.next().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_stream_forward_echo() {
+        use futures::{StreamExt, TryStreamExt};
+
+        let (mut client, server) = duplex_pair();
+        let echo = tokio::spawn(async move {
+            let (sink, stream) = StreamExt::split(server);
+            stream
+                .try_take_while(|msg| futures::future::ready(Ok(msg.is_text())))
+                .forward(sink)
+                .await
+        });
+
+        client.send(Message::text("ping")).await.unwrap();
+        assert_eq!(client.next().await, Some(Ok(Message::text("ping"))));
+        client.send(Message::binary(vec![0u8])).await.unwrap();
+
+        // Forwarding ends and closes the echo sink with a Normal close frame.
+        echo.await.unwrap().unwrap();
+        assert!(matches!(
+            client.next().await,
+            Some(Ok(Message::Close(Some(ref cf)))) if cf.code == CloseCode::Normal
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_close_and_wait_clean() {
+        let (mut client, mut server) = duplex_pair();
+        let server_task = tokio::spawn(async move {
+            server.send(Message::text("in flight")).await.unwrap();
+            while let Some(msg) = server.recv().await.unwrap() {
+                if matches!(msg, Message::Close(_)) {
+                    break;
+                }
+            }
+            let outcome = server
+                .close_and_wait(CloseCode::Normal, "", Duration::from_secs(5))
+                .await
+                .unwrap();
+            assert!(outcome.is_clean());
+        });
+
+        let start = Instant::now();
+        let outcome = client
+            .close_and_wait(CloseCode::GoingAway, "bye", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(
+            outcome,
+            CloseOutcome::Clean(Some(CloseFrame::new(CloseCode::GoingAway, "bye")))
+        );
+        assert_eq!(client.state(), ConnectionState::Closed);
+        // The server closed TCP first, so the client did not sit out the timeout.
+        assert!(start.elapsed() < Duration::from_secs(5));
+        server_task.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_close_and_wait_times_out_abnormal() {
+        let (mut client, _server) = duplex_pair();
+
+        let start = Instant::now();
+        let outcome = client
+            .close_and_wait(CloseCode::Normal, "", Duration::from_secs(2))
+            .await
+            .unwrap();
+        assert_eq!(outcome, CloseOutcome::Abnormal);
+        assert_eq!(outcome.code().as_u16(), 1006);
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        assert_eq!(client.state(), ConnectionState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_close_and_wait_server_shuts_down_first() {
+        use tokio::io::AsyncReadExt;
+
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let mut server = Connection::new(server_io, Role::Server, Config::server());
+        let mut peer = WebSocketCodec::new(client_io, Role::Client, Config::client());
+
+        let start = Instant::now();
+        let close = tokio::spawn(async move {
+            server
+                .close_and_wait(CloseCode::Normal, "", Duration::from_secs(5))
+                .await
+        });
+
+        let frame = peer.read_frame().await.unwrap();
+        assert_eq!(frame.opcode, OpCode::Close);
+        peer.write_frame(&Frame::close(Some(1000), ""))
+            .await
+            .unwrap();
+        peer.flush().await.unwrap();
+
+        let outcome = close.await.unwrap().unwrap();
+        assert!(outcome.is_clean());
+        let mut rest = Vec::new();
+        peer.into_inner().read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_close_and_wait_rejects_reserved_code() {
+        let (mut client, _server) = duplex_pair();
+        let result = client
+            .close_and_wait(CloseCode::Other(1006), "", Duration::from_secs(1))
+            .await;
+        assert_eq!(result, Err(Error::InvalidCloseCode(1006)));
+        assert!(client.is_open());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_utf8_fails_connection() {
+        // Masked text frame with zero mask carrying 0xff (never valid UTF-8)
+        let data = vec![0x81, 0x81, 0x00, 0x00, 0x00, 0x00, 0xff];
+        let stream = MockStream::new(data);
+        let mut conn = Connection::new(stream, Role::Server, Config::server());
+
+        assert_eq!(conn.recv().await, Err(Error::InvalidUtf8));
+        assert_eq!(conn.state(), ConnectionState::Closed);
+        assert_eq!(conn.recv().await, Ok(None));
+
+        let written = conn.codec.into_inner().written().to_vec();
+        assert_eq!(written[0], 0x88);
+        assert_eq!(u16::from_be_bytes([written[2], written[3]]), 1007);
+        assert_eq!(&written[4..], b"Invalid UTF-8 in text frame");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_message_fails_with_1009() {
+        let mut data = vec![0x82, 0xfe, 0x00, 0x82, 0x00, 0x00, 0x00, 0x00];
+        data.extend_from_slice(&[0u8; 130]);
+        let stream = MockStream::new(data);
+        let config = Config::server().with_limits(crate::config::Limits::new(128, 128, 4, 8192));
+        let mut conn = Connection::new(stream, Role::Server, config);
+
+        assert!(matches!(
+            conn.recv().await,
+            Err(Error::FrameTooLarge { .. })
+        ));
+        assert_eq!(conn.state(), ConnectionState::Closed);
+        let written = conn.codec.into_inner().written().to_vec();
+        assert_eq!(u16::from_be_bytes([written[2], written[3]]), 1009);
+    }
+
+    #[tokio::test]
+    async fn test_fail_on_protocol_error_disabled() {
+        let data = vec![0x81, 0x81, 0x00, 0x00, 0x00, 0x00, 0xff];
+        let stream = MockStream::new(data);
+        let config = Config::server().with_fail_on_protocol_error(false);
+        let mut conn = Connection::new(stream, Role::Server, config);
+
+        assert_eq!(conn.recv().await, Err(Error::InvalidUtf8));
+        assert!(conn.is_open());
+        assert!(conn.codec.into_inner().written().is_empty());
+    }
+
+    #[test]
+    fn test_fail_reason_fits_control_frame() {
+        let reason = fail_reason(&Error::ProtocolViolation("é".repeat(100)));
+        assert!(reason.len() <= 123);
+        assert!(reason.starts_with("Protocol violation: "));
+    }
+
+    #[cfg(feature = "compression")]
+    fn deflate_registry(is_server: bool) -> ExtensionRegistry {
+        use crate::extensions::deflate::{DeflateConfig, DeflateExtension};
+
+        let mut registry = ExtensionRegistry::new();
+        let extension = DeflateExtension::new(DeflateConfig::default(), is_server);
+        registry.add(Box::new(extension)).unwrap();
+        registry
+            .configure(&[ExtensionOffer::new("permessage-deflate")])
+            .unwrap();
+        registry
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_large_message_compressed_before_fragmenting() {
+        let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+        let config = Config::server().with_fragment_size(64);
+        let mut server =
+            Connection::with_extensions(server_io, Role::Server, config, deflate_registry(true));
+        let mut peer = WebSocketCodec::new(client_io, Role::Client, Config::client());
+        peer.set_allowed_rsv_bits(0x40);
+
+        let text: String = (0..800).map(|i| format!("line {i}; ")).collect();
+        server.send(Message::text(text.clone())).await.unwrap();
+
+        // RSV1 only on the first fragment; the rest carry compressed bytes.
+        let mut frames = vec![peer.read_frame().await.unwrap()];
+        while !frames.last().unwrap().fin {
+            frames.push(peer.read_frame().await.unwrap());
+        }
+        assert!(frames.len() > 1);
+        assert!(frames[0].rsv1 && frames[0].opcode == OpCode::Text);
+        assert!(
+            frames[1..]
+                .iter()
+                .all(|f| !f.rsv1 && f.opcode == OpCode::Continuation)
+        );
+        let compressed: usize = frames.iter().map(|f| f.payload().len()).sum();
+        assert!(compressed < text.len() / 2);
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_fragmented_compressed_messages_roundtrip() {
+        let (server_io, client_io) = tokio::io::duplex(64 * 1024);
+        let config = Config::server().with_fragment_size(64);
+        let mut server =
+            Connection::with_extensions(server_io, Role::Server, config, deflate_registry(true));
+        let mut client = Connection::with_extensions(
+            client_io,
+            Role::Client,
+            Config::client(),
+            deflate_registry(false),
+        );
+
+        // Two messages, so the second relies on the shared deflate context.
+        for round in 0..2 {
+            let text: String = (0..800).map(|i| format!("{round}:{i}; ")).collect();
+            server.send(Message::text(text.clone())).await.unwrap();
+            assert_eq!(client.recv().await.unwrap(), Some(Message::text(text)));
+        }
+    }
 }
@@ -0,0 +1,171 @@
+//! Keepalive ping scheduling and pong tracking.
+
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::time::Instant;
+
+use crate::config::{Keepalive, PingPayload};
+use crate::error::Result;
+use crate::message::CloseCode;
+
+/// What to do when the keepalive deadline passes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum KeepaliveAction {
+    /// Send a ping with this payload.
+    Ping(Bytes),
+    /// The outstanding ping was not answered in time.
+    Expired,
+}
+
+/// Per-connection keepalive state.
+#[derive(Debug)]
+pub(crate) struct KeepaliveState {
+    config: Keepalive,
+    next_ping_at: Instant,
+    outstanding: Option<(Bytes, Instant)>,
+    counter: u64,
+    rtt: Option<Duration>,
+}
+
+impl KeepaliveState {
+    pub(crate) fn new(config: Keepalive) -> Self {
+        let next_ping_at = Instant::now() + config.interval;
+        Self {
+            config,
+            next_ping_at,
+            outstanding: None,
+            counter: 0,
+            rtt: None,
+        }
+    }
+
+    /// When the next ping is due, or when the outstanding ping expires.
+    pub(crate) fn deadline(&self) -> Instant {
+        match self.outstanding {
+            Some((_, sent_at)) => sent_at + self.config.pong_timeout,
+            None => self.next_ping_at,
+        }
+    }
+
+    /// Advance the state once [`Self::deadline`] has passed.
+    ///
+    /// Fails with `Error::InvalidConfig` instead of sending a ping if the
+    /// policy would produce an invalid ping or close frame.
+    pub(crate) fn on_deadline(&mut self, now: Instant) -> Result<KeepaliveAction> {
+        if self.outstanding.is_some() {
+            return Ok(KeepaliveAction::Expired);
+        }
+
+        self.config.validate()?;
+        let payload = self.next_payload();
+        self.outstanding = Some((payload.clone(), now));
+        self.next_ping_at = now + self.config.interval;
+        Ok(KeepaliveAction::Ping(payload))
+    }
+
+    /// Record a received pong. Pongs that do not answer the outstanding
+    /// ping (unsolicited or stale) are ignored.
+    pub(crate) fn on_pong(&mut self, payload: &[u8], now: Instant) {
+        if let Some((ref expected, sent_at)) = self.outstanding
+            && expected.as_ref() == payload
+        {
+            self.rtt = Some(now - sent_at);
+            self.outstanding = None;
+        }
+    }
+
+    pub(crate) fn rtt(&self) -> Option<Duration> {
+        self.rtt
+    }
+
+    pub(crate) fn close_code(&self) -> CloseCode {
+        self.config.close_code
+    }
+
+    fn next_payload(&mut self) -> Bytes {
+        match self.config.payload {
+            PingPayload::Empty => Bytes::new(),
+            PingPayload::Counter => {
+                self.counter = self.counter.wrapping_add(1);
+                Bytes::copy_from_slice(&self.counter.to_be_bytes())
+            }
+            PingPayload::Fixed(ref bytes) => Bytes::copy_from_slice(bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    fn state(payload: PingPayload) -> KeepaliveState {
+        KeepaliveState::new(
+            Keepalive::new(Duration::from_secs(30), Duration::from_secs(10)).with_payload(payload),
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_ping_then_pong_measures_rtt() {
+        let mut ka = state(PingPayload::Empty);
+        let start = Instant::now();
+        assert_eq!(ka.deadline(), start + Duration::from_secs(30));
+
+        let sent_at = ka.deadline();
+        assert_eq!(
+            ka.on_deadline(sent_at),
+            Ok(KeepaliveAction::Ping(Bytes::new()))
+        );
+        assert_eq!(ka.deadline(), sent_at + Duration::from_secs(10));
+
+        ka.on_pong(b"", sent_at + Duration::from_millis(40));
+        assert_eq!(ka.rtt(), Some(Duration::from_millis(40)));
+        assert_eq!(ka.deadline(), sent_at + Duration::from_secs(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_missing_pong_expires() {
+        let mut ka = state(PingPayload::Empty);
+        let now = ka.deadline();
+        assert!(matches!(ka.on_deadline(now), Ok(KeepaliveAction::Ping(_))));
+        assert_eq!(ka.on_deadline(ka.deadline()), Ok(KeepaliveAction::Expired));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_counter_payload_ignores_stale_pong() {
+        let mut ka = state(PingPayload::Counter);
+        let now = ka.deadline();
+        let Ok(KeepaliveAction::Ping(payload)) = ka.on_deadline(now) else {
+            panic!("expected ping");
+        };
+        assert_eq!(&payload[..], &1u64.to_be_bytes());
+
+        ka.on_pong(&0u64.to_be_bytes(), now);
+        assert!(ka.rtt().is_none());
+        ka.on_pong(&1u64.to_be_bytes(), now);
+        assert_eq!(ka.rtt(), Some(Duration::ZERO));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fixed_payload() {
+        let mut ka = state(PingPayload::Fixed(b"hb".to_vec()));
+        let now = ka.deadline();
+        assert_eq!(
+            ka.on_deadline(now),
+            Ok(KeepaliveAction::Ping(Bytes::from_static(b"hb")))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_invalid_policy_fails_before_ping() {
+        let mut ka = state(PingPayload::Fixed(vec![0u8; 126]));
+        let now = ka.deadline();
+        assert!(matches!(ka.on_deadline(now), Err(Error::InvalidConfig(_))));
+
+        let mut ka =
+            KeepaliveState::new(Keepalive::default().with_close_code(CloseCode::Other(1006)));
+        let now = ka.deadline();
+        assert!(matches!(ka.on_deadline(now), Err(Error::InvalidConfig(_))));
+    }
+}
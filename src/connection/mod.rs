@@ -35,12 +35,27 @@ pub use state::ConnectionState;
 #[cfg(feature = "async-tokio")]
 mod fragmenter;
 
+#[cfg(feature = "async-tokio")]
+mod keepalive;
+
 #[cfg(feature = "async-tokio")]
 #[allow(clippy::module_inception)]
 mod connection;
 
+#[cfg(feature = "async-tokio")]
+mod shutdown;
+
+#[cfg(feature = "async-tokio")]
+mod split;
+
 #[cfg(feature = "async-tokio")]
 pub use connection::Connection;
 
+#[cfg(feature = "async-tokio")]
+pub use split::{ReuniteError, WsReader, WsWriter};
+
 #[cfg(feature = "async-tokio")]
 pub use fragmenter::MessageFragmenter;
+
+#[cfg(feature = "async-tokio")]
+pub(crate) use shutdown::{PendingCloses, ShutdownSignal};
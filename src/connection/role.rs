@@ -71,6 +71,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::clone_on_copy)]
     fn test_role_clone_and_copy() {
         let role = Role::Client;
         let cloned = role.clone();
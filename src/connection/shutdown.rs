@@ -0,0 +1,93 @@
+//! Server-initiated shutdown of live connections.
+
+use std::future::Future;
+use std::pin::{Pin, pin};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+
+use tokio::sync::{Notify, watch};
+
+use crate::message::CloseCode;
+use crate::protocol::Frame;
+
+/// Close reason sent with `CloseCode::GoingAway` when the signal fires.
+pub(crate) const SHUTDOWN_REASON: &str = "server shutting down";
+
+/// Fires once the owning server starts shutting down.
+///
+/// A connection holding one starts the closing handshake with
+/// `CloseCode::GoingAway` the next time it sends or waits for a frame after
+/// the signal fired, then keeps reading until the peer's close frame arrives.
+pub(crate) struct ShutdownSignal {
+    rx: watch::Receiver<bool>,
+    fired: Pin<Box<dyn Future<Output = ()> + Send + Sync>>,
+    pending: Arc<PendingCloses>,
+}
+
+impl ShutdownSignal {
+    /// Fire once `rx` sees `true`, or once its sender is gone.
+    ///
+    /// Connections report the close frames they have queued but not yet
+    /// written to `pending`.
+    pub(crate) fn new(rx: watch::Receiver<bool>, pending: Arc<PendingCloses>) -> Self {
+        let mut waiting = rx.clone();
+        Self {
+            rx,
+            fired: Box::pin(async move {
+                let _ = waiting.wait_for(|shutting_down| *shutting_down).await;
+            }),
+            pending,
+        }
+    }
+
+    pub(crate) fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        self.fired.as_mut().poll(cx)
+    }
+
+    /// Check the signal without registering for a wakeup, so a task other
+    /// than the one waiting in [`Self::poll`] can look at it.
+    pub(crate) fn has_fired(&self) -> bool {
+        *self.rx.borrow() || self.rx.has_changed().is_err()
+    }
+
+    /// The close frame to send once the signal fired, and the guard to hold
+    /// until it has been flushed.
+    pub(crate) fn going_away(self) -> (Frame, CloseInFlight) {
+        self.pending.count.fetch_add(1, Ordering::AcqRel);
+        let frame = Frame::close(Some(CloseCode::GoingAway.as_u16()), SHUTDOWN_REASON);
+        (frame, CloseInFlight(self.pending))
+    }
+}
+
+/// Counts `GoingAway` frames queued by connections but not yet written.
+#[derive(Default)]
+pub(crate) struct PendingCloses {
+    count: AtomicUsize,
+    idle: Notify,
+}
+
+impl PendingCloses {
+    /// Wait until every queued `GoingAway` frame has been written.
+    pub(crate) async fn wait(&self) {
+        loop {
+            let mut notified = pin!(self.idle.notified());
+            notified.as_mut().enable();
+            if self.count.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Held by a connection while its `GoingAway` frame waits to be flushed.
+pub(crate) struct CloseInFlight(Arc<PendingCloses>);
+
+impl Drop for CloseInFlight {
+    fn drop(&mut self) {
+        if self.0.count.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.0.idle.notify_waiters();
+        }
+    }
+}
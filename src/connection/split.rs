@@ -0,0 +1,1019 @@
+//! Independent reader and writer halves of a [`Connection`].
+
+use std::fmt;
+use std::future::poll_fn;
+use std::sync::{Arc, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncWrite, ReadHalf, WriteHalf};
+use tokio::sync::{Mutex, MutexGuard};
+use tokio::time::Instant;
+
+use crate::codec::WebSocketCodec;
+use crate::connection::ConnectionState;
+use crate::connection::connection::{
+    Connection, buffer_message, decode_message, fail_code, fail_reason, frame_rsv_bits,
+    into_message, parse_close_frame,
+};
+use crate::connection::keepalive::{KeepaliveAction, KeepaliveState};
+use crate::connection::shutdown::{CloseInFlight, ShutdownSignal};
+use crate::error::{Error, Result};
+use crate::extensions::{ExtensionRegistry, ExtensionScope};
+use crate::message::{CloseCode, Message};
+use crate::protocol::assembler::MessageAssembler;
+use crate::protocol::{Frame, HandshakeRequest, OpCode};
+
+/// Write side plus the state machine, shared by both halves.
+///
+/// Every frame goes out while holding `writer`, so a reply triggered by the
+/// reader (pong, close echo) never interleaves with a fragmented message
+/// from the writer.
+///
+/// The reader never waits for `writer`: a [`WsWriter`] blocked on a full
+/// socket holds it, and a reader stuck behind it would stop reading, so two
+/// split peers could deadlock waiting for each other to read. The reader
+/// queues its replies in `replies` instead, close frames included, and sends
+/// them itself only when the lock is free; otherwise the writer sends them
+/// with its own frames. Extensions sit behind their own lock, which is never
+/// held across a write.
+///
+/// A `GoingAway` close started by the shutdown signal is queued in `replies`
+/// as well; `going_away` is held until a flush has written it.
+struct Shared<T> {
+    writer: Mutex<WebSocketCodec<WriteHalf<T>>>,
+    extensions: std::sync::Mutex<ExtensionRegistry>,
+    replies: std::sync::Mutex<Replies>,
+    state: std::sync::Mutex<ConnectionState>,
+    shutdown: std::sync::Mutex<Option<ShutdownSignal>>,
+    going_away: std::sync::Mutex<Option<CloseInFlight>>,
+    subprotocol: Option<String>,
+    request: Option<HandshakeRequest>,
+}
+
+/// Control frames queued by the reader for the writer to send.
+#[derive(Default)]
+struct Replies {
+    frames: Vec<Frame>,
+    // Woken when the writer releases its lock with frames still queued.
+    reader: Option<Waker>,
+}
+
+/// The writer lock, handing queued replies back to the reader on release.
+struct WriterGuard<'a, T> {
+    codec: Option<MutexGuard<'a, WebSocketCodec<WriteHalf<T>>>>,
+    shared: &'a Shared<T>,
+}
+
+impl<T> Shared<T> {
+    fn state(&self) -> ConnectionState {
+        *self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn set_state(&self, state: ConnectionState) {
+        *self.state.lock().unwrap_or_else(PoisonError::into_inner) = state;
+    }
+
+    /// Mark the connection closed and, if it was open, queue `frame` for
+    /// the writer. Returns whether the frame was queued.
+    ///
+    /// Both happen under the state lock, so a writer that finds the
+    /// connection closed also finds the frame queued or already buffered.
+    fn close_with(&self, frame: Frame) -> bool {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let was_open =
+            std::mem::replace(&mut *state, ConnectionState::Closed) == ConnectionState::Open;
+        if was_open {
+            self.queue_reply(frame);
+        }
+        was_open
+    }
+
+    fn extensions(&self) -> std::sync::MutexGuard<'_, ExtensionRegistry> {
+        self.extensions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn replies(&self) -> std::sync::MutexGuard<'_, Replies> {
+        self.replies.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Queue a control frame for whichever half holds the writer lock next.
+    fn queue_reply(&self, frame: Frame) {
+        self.replies().frames.push(frame);
+    }
+
+    /// Queue a pong, dropping the queued ones once they reach `limit` bytes.
+    ///
+    /// Only the latest ping needs an answer (RFC 6455 Section 5.5.3), so a
+    /// peer flooding pings cannot grow the queue while the writer is stuck.
+    fn queue_pong(&self, pong: Frame, limit: usize) {
+        let mut replies = self.replies();
+        let queued: usize = replies.frames.iter().map(|f| f.wire_size(false)).sum();
+        if queued >= limit {
+            replies
+                .frames
+                .retain(|queued| queued.opcode != OpCode::Pong);
+        }
+        replies.frames.push(pong);
+    }
+
+    /// Move queued replies into the write buffer; needs the writer lock.
+    fn buffer_replies(&self, codec: &mut WebSocketCodec<WriteHalf<T>>) -> Result<()> {
+        let frames = std::mem::take(&mut self.replies().frames);
+        for frame in &frames {
+            codec.buffer_frame(frame)?;
+        }
+        Ok(())
+    }
+
+    /// Start closing with `GoingAway` if the shutdown signal fired, waking
+    /// the reader when it does.
+    fn poll_shutdown(&self, cx: &mut Context<'_>) {
+        self.go_away_if(|signal| signal.poll(cx).is_ready());
+    }
+
+    /// Like [`Self::poll_shutdown`], for the writer, which must not take
+    /// the wakeup away from the reader.
+    fn check_shutdown(&self) {
+        self.go_away_if(|signal| signal.has_fired());
+    }
+
+    fn go_away_if(&self, fired: impl FnOnce(&mut ShutdownSignal) -> bool) {
+        let mut shutdown = self.shutdown.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(signal) = shutdown.take_if(|signal| fired(signal)) else {
+            return;
+        };
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if *state == ConnectionState::Open {
+            *state = ConnectionState::Closing;
+            let (frame, in_flight) = signal.going_away();
+            *self.going_away() = Some(in_flight);
+            self.queue_reply(frame);
+        }
+    }
+
+    fn going_away(&self) -> std::sync::MutexGuard<'_, Option<CloseInFlight>> {
+        self.going_away
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Called after a flush under the writer lock completed: a queued
+    /// `GoingAway` is written unless it is still waiting in `replies`.
+    fn on_flushed(&self) {
+        if self.replies().frames.is_empty() {
+            self.going_away().take();
+        }
+    }
+
+    async fn lock_writer(&self) -> WriterGuard<'_, T> {
+        WriterGuard {
+            codec: Some(self.writer.lock().await),
+            shared: self,
+        }
+    }
+}
+
+impl<T> std::ops::Deref for WriterGuard<'_, T> {
+    type Target = WebSocketCodec<WriteHalf<T>>;
+
+    fn deref(&self) -> &Self::Target {
+        self.codec.as_ref().expect("guard is held until drop")
+    }
+}
+
+impl<T> std::ops::DerefMut for WriterGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.codec.as_mut().expect("guard is held until drop")
+    }
+}
+
+impl<T> Drop for WriterGuard<'_, T> {
+    fn drop(&mut self) {
+        // Unlock first, so the woken reader can take the lock.
+        self.codec = None;
+        let mut replies = self.shared.replies();
+        if !replies.frames.is_empty()
+            && let Some(reader) = replies.reader.take()
+        {
+            reader.wake();
+        }
+    }
+}
+
+impl<T: AsyncWrite> WriterGuard<'_, T> {
+    /// Write out queued replies and buffered frames, then flush.
+    async fn flush(&mut self) -> Result<()> {
+        let shared = self.shared;
+        poll_fn(|cx| {
+            shared.buffer_replies(self)?;
+            self.poll_flush(cx)
+        })
+        .await?;
+        shared.on_flushed();
+        Ok(())
+    }
+}
+
+impl<T: AsyncWrite> Shared<T> {
+    /// Send a close frame if the connection is still open and wait for the reply.
+    async fn start_close(&self, code: CloseCode, reason: &str) -> Result<()> {
+        let mut writer = self.lock_writer().await;
+        if self.state() != ConnectionState::Open {
+            return Ok(());
+        }
+
+        self.set_state(ConnectionState::Closing);
+        self.buffer_replies(&mut writer)?;
+        let frame = Frame::close(Some(code.as_u16()), reason);
+        writer.write_frame(&frame).await?;
+        writer.flush().await
+    }
+
+    /// Send queued replies if the writer lock is free, without waiting for
+    /// the stream. `flushing` tracks a flush this reader left unfinished.
+    fn poll_replies(&self, cx: &mut Context<'_>, flushing: &mut bool) -> Result<()> {
+        {
+            let mut replies = self.replies();
+            if replies.frames.is_empty() && !*flushing {
+                return Ok(());
+            }
+            replies.reader = Some(cx.waker().clone());
+        }
+        // A busy writer sends the replies with its own frames, or wakes us.
+        let Ok(mut writer) = self.writer.try_lock() else {
+            return Ok(());
+        };
+        self.buffer_replies(&mut writer)?;
+        match writer.poll_flush(cx) {
+            Poll::Ready(result) => {
+                *flushing = false;
+                result?;
+                self.on_flushed();
+                Ok(())
+            }
+            Poll::Pending => {
+                *flushing = true;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Receiving half of a [`Connection`](super::Connection), created by
+/// [`Connection::split`](super::Connection::split).
+///
+/// Pings are answered and close frames echoed after whatever the
+/// [`WsWriter`] is sending; the reader keeps reading while it waits.
+pub struct WsReader<T> {
+    codec: WebSocketCodec<ReadHalf<T>>,
+    assembler: MessageAssembler,
+    current_message_rsv_bits: u8,
+    // Which negotiated extension scopes need the shared registry on receive.
+    frame_extensions: bool,
+    fragment_extensions: bool,
+    message_extensions: bool,
+    keepalive: Option<KeepaliveState>,
+    // Replies this reader started writing but could not finish flushing.
+    flushing: bool,
+    shared: Arc<Shared<T>>,
+}
+
+/// Sending half of a [`Connection`](super::Connection), created by
+/// [`Connection::split`](super::Connection::split).
+pub struct WsWriter<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Error returned by [`WsReader::reunite`] when the halves come from
+/// different connections. Both halves are handed back unchanged.
+pub struct ReuniteError<T>(pub WsReader<T>, pub WsWriter<T>);
+
+impl<T> fmt::Debug for ReuniteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ReuniteError").finish()
+    }
+}
+
+impl<T> fmt::Display for ReuniteError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("tried to reunite halves that are not from the same connection")
+    }
+}
+
+impl<T> std::error::Error for ReuniteError<T> {}
+
+/// The pieces of a [`Connection`](super::Connection) that move into the halves.
+pub(super) struct Parts<T> {
+    pub(super) codec: WebSocketCodec<T>,
+    pub(super) state: ConnectionState,
+    pub(super) assembler: MessageAssembler,
+    pub(super) current_message_rsv_bits: u8,
+    pub(super) extensions: ExtensionRegistry,
+    pub(super) keepalive: Option<KeepaliveState>,
+    pub(super) subprotocol: Option<String>,
+    pub(super) request: Option<HandshakeRequest>,
+    pub(super) shutdown: Option<ShutdownSignal>,
+    pub(super) going_away: Option<CloseInFlight>,
+}
+
+pub(super) fn split<T: AsyncRead + AsyncWrite>(parts: Parts<T>) -> (WsReader<T>, WsWriter<T>) {
+    let (read_codec, write_codec) = parts.codec.split();
+    let frame_extensions = parts.extensions.has_negotiated(ExtensionScope::PerFrame);
+    let fragment_extensions = parts.extensions.has_negotiated(ExtensionScope::PerFragment);
+    let message_extensions = parts.extensions.has_negotiated(ExtensionScope::PerMessage);
+    let shared = Arc::new(Shared {
+        writer: Mutex::new(write_codec),
+        extensions: std::sync::Mutex::new(parts.extensions),
+        replies: std::sync::Mutex::new(Replies::default()),
+        state: std::sync::Mutex::new(parts.state),
+        shutdown: std::sync::Mutex::new(parts.shutdown),
+        going_away: std::sync::Mutex::new(parts.going_away),
+        subprotocol: parts.subprotocol,
+        request: parts.request,
+    });
+    let reader = WsReader {
+        codec: read_codec,
+        assembler: parts.assembler,
+        current_message_rsv_bits: parts.current_message_rsv_bits,
+        frame_extensions,
+        fragment_extensions,
+        message_extensions,
+        keepalive: parts.keepalive,
+        flushing: false,
+        shared: Arc::clone(&shared),
+    };
+    (reader, WsWriter { shared })
+}
+
+impl<T> WsReader<T> {
+    /// Get the current connection state (shared with the writer).
+    pub fn state(&self) -> ConnectionState {
+        self.shared.state()
+    }
+
+    /// The subprotocol agreed on in the handshake, if any.
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.shared.subprotocol.as_deref()
+    }
+
+    /// The client's upgrade request (server side only).
+    pub fn request(&self) -> Option<&HandshakeRequest> {
+        self.shared.request.as_ref()
+    }
+
+    /// Check if the connection is in an open state.
+    pub fn is_open(&self) -> bool {
+        self.state() == ConnectionState::Open
+    }
+
+    /// Round-trip time measured by the most recent answered keepalive ping.
+    pub fn rtt(&self) -> Option<Duration> {
+        self.keepalive.as_ref().and_then(KeepaliveState::rtt)
+    }
+
+    /// Returns `true` if both halves come from the same connection.
+    pub fn is_pair_of(&self, writer: &WsWriter<T>) -> bool {
+        Arc::ptr_eq(&self.shared, &writer.shared)
+    }
+
+    /// Rejoin the halves into the original connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ReuniteError`] with both halves if they were not produced by
+    /// the same [`Connection::split`](super::Connection::split) call.
+    // The error hands both halves back, so it is as large as the connection.
+    #[allow(clippy::result_large_err)]
+    pub fn reunite(self, writer: WsWriter<T>) -> std::result::Result<Connection<T>, ReuniteError<T>>
+    where
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        if !self.is_pair_of(&writer) {
+            return Err(ReuniteError(self, writer));
+        }
+        drop(writer);
+
+        let shared = Arc::try_unwrap(self.shared)
+            .unwrap_or_else(|_| unreachable!("the two halves are the only owners"));
+        let state = shared.state();
+        let mut write_codec = shared.writer.into_inner();
+        // Queued replies go out with the connection's next write.
+        for frame in shared
+            .replies
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .frames
+        {
+            let _ = write_codec.buffer_frame(&frame);
+        }
+
+        Ok(Connection::from_split_parts(Parts {
+            codec: WebSocketCodec::unsplit(self.codec, write_codec),
+            state,
+            assembler: self.assembler,
+            current_message_rsv_bits: self.current_message_rsv_bits,
+            extensions: shared
+                .extensions
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner),
+            keepalive: self.keepalive,
+            subprotocol: shared.subprotocol,
+            request: shared.request,
+            shutdown: shared
+                .shutdown
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner),
+            going_away: shared
+                .going_away
+                .into_inner()
+                .unwrap_or_else(PoisonError::into_inner),
+        }))
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite> WsReader<T> {
+    /// Receive the next message.
+    ///
+    /// Behaves like [`Connection::recv`](super::Connection::recv): pings are
+    /// answered, close frames are echoed and keepalive pings are sent. Those
+    /// replies are queued behind any message the [`WsWriter`] is in the
+    /// middle of sending, and go out without blocking the reader.
+    ///
+    /// ## Errors
+    ///
+    /// Same as [`Connection::recv`](super::Connection::recv).
+    pub async fn recv(&mut self) -> Result<Option<Message>> {
+        let result = self.read_message().await;
+        if let Err(ref e) = result
+            && let Some(code) = fail_code(self.codec.config(), e)
+        {
+            self.fail(code, &fail_reason(e)).await;
+        }
+        result
+    }
+
+    async fn read_message(&mut self) -> Result<Option<Message>> {
+        if !self.state().can_receive() {
+            return Ok(None);
+        }
+
+        loop {
+            let mut frame = match self.read_frame().await {
+                Ok(f) => f,
+                Err(Error::ConnectionClosed(_)) => {
+                    self.shared.set_state(ConnectionState::Closed);
+                    return Ok(None);
+                }
+                Err(Error::IdleTimeout) => {
+                    self.fail(CloseCode::GoingAway, "idle timeout").await;
+                    return Err(Error::IdleTimeout);
+                }
+                Err(e) => return Err(e),
+            };
+
+            match frame.opcode {
+                OpCode::Ping => {
+                    frame.validate()?;
+                    let payload = frame.into_payload_bytes();
+                    let limit = self.codec.config().write_buffer_size;
+                    self.shared.queue_pong(Frame::pong(payload.to_vec()), limit);
+                    self.send_replies().await?;
+                    return Ok(Some(Message::Ping(payload)));
+                }
+                OpCode::Pong => {
+                    frame.validate()?;
+                    if let Some(keepalive) = self.keepalive.as_mut() {
+                        keepalive.on_pong(frame.payload(), Instant::now());
+                    }
+                    return Ok(Some(Message::Pong(frame.into_payload_bytes())));
+                }
+                OpCode::Close => {
+                    frame.validate()?;
+                    let close_frame = parse_close_frame(&frame);
+
+                    // Closed along with queuing the echo: a writer that sends
+                    // the echo sees the state change on its next message.
+                    let response = if let Some(ref cf) = close_frame {
+                        Frame::close(Some(cf.code.as_u16()), &cf.reason)
+                    } else {
+                        Frame::close(None, "")
+                    };
+                    if self.shared.close_with(response) {
+                        let _ = self.send_replies().await;
+                    }
+
+                    return Ok(Some(Message::Close(close_frame)));
+                }
+                OpCode::Text | OpCode::Binary | OpCode::Continuation => {
+                    frame.validate()?;
+                    if self.frame_extensions {
+                        self.shared.extensions().decode_frame(&mut frame)?;
+                    }
+                    if self.fragment_extensions {
+                        self.shared.extensions().decode_fragment(&mut frame)?;
+                    }
+                    let frame_rsv_bits = frame_rsv_bits(&frame);
+                    if frame.opcode != OpCode::Continuation && !self.assembler.is_assembling() {
+                        self.current_message_rsv_bits = frame_rsv_bits;
+                    }
+
+                    let assembled = match self.assembler.push(frame) {
+                        Ok(v) => v,
+                        Err(e) => {
+                            self.current_message_rsv_bits = 0;
+                            return Err(e);
+                        }
+                    };
+
+                    if let Some(assembled) = assembled {
+                        let rsv_bits = self.current_message_rsv_bits;
+                        self.current_message_rsv_bits = 0;
+                        // Only per-message extensions need the shared registry.
+                        let message = if !self.message_extensions {
+                            into_message(assembled.opcode, assembled.payload)?
+                        } else {
+                            decode_message(&mut self.shared.extensions(), assembled, rsv_bits)?
+                        };
+                        return Ok(Some(message));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Try once to send queued replies; what is left goes out later.
+    async fn send_replies(&mut self) -> Result<()> {
+        poll_fn(|cx| Poll::Ready(self.shared.poll_replies(cx, &mut self.flushing))).await
+    }
+
+    /// Queue a close frame if the connection is still open, then mark it
+    /// closed. Like other replies, the frame waits for a busy writer.
+    async fn fail(&mut self, code: CloseCode, reason: &str) {
+        if self
+            .shared
+            .close_with(Frame::close(Some(code.as_u16()), reason))
+        {
+            let _ = self.send_replies().await;
+        }
+    }
+
+    /// Read the next frame, sending keepalive pings while waiting.
+    async fn read_frame(&mut self) -> Result<Frame> {
+        loop {
+            let keepalive = match self.keepalive.as_mut() {
+                Some(keepalive) if self.shared.state() == ConnectionState::Open => keepalive,
+                _ => {
+                    return read_with_replies(&mut self.codec, &self.shared, &mut self.flushing)
+                        .await;
+                }
+            };
+
+            let deadline = keepalive.deadline();
+            let read = read_with_replies(&mut self.codec, &self.shared, &mut self.flushing);
+            if let Ok(result) = tokio::time::timeout_at(deadline, read).await {
+                return result;
+            }
+
+            match keepalive.on_deadline(Instant::now())? {
+                KeepaliveAction::Ping(payload) => {
+                    self.shared.queue_reply(Frame::ping(payload.to_vec()));
+                }
+                KeepaliveAction::Expired => {
+                    let code = keepalive.close_code();
+                    self.fail(code, "keepalive timeout").await;
+                    return Err(Error::KeepaliveTimeout);
+                }
+            }
+        }
+    }
+}
+
+/// Read a frame, sending queued replies whenever the writer lock is free,
+/// and starting the closing handshake once the shutdown signal fires.
+async fn read_with_replies<T: AsyncRead + AsyncWrite>(
+    codec: &mut WebSocketCodec<ReadHalf<T>>,
+    shared: &Shared<T>,
+    flushing: &mut bool,
+) -> Result<Frame> {
+    poll_fn(|cx| {
+        shared.poll_shutdown(cx);
+        shared.poll_replies(cx, flushing)?;
+        codec.poll_read_frame(cx)
+    })
+    .await
+}
+
+impl<T> WsWriter<T> {
+    /// Get the current connection state (shared with the reader).
+    pub fn state(&self) -> ConnectionState {
+        self.shared.state()
+    }
+
+    /// The subprotocol agreed on in the handshake, if any.
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.shared.subprotocol.as_deref()
+    }
+
+    /// The client's upgrade request (server side only).
+    pub fn request(&self) -> Option<&HandshakeRequest> {
+        self.shared.request.as_ref()
+    }
+
+    /// Check if the connection is in an open state.
+    pub fn is_open(&self) -> bool {
+        self.state() == ConnectionState::Open
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite> WsWriter<T> {
+    /// Send a message and flush.
+    ///
+    /// ## Errors
+    ///
+    /// Same as [`Connection::send`](super::Connection::send).
+    pub async fn send(&mut self, message: Message) -> Result<()> {
+        let mut writer = self.shared.lock_writer().await;
+        self.write_locked(&mut writer, message).await?;
+        writer.flush().await
+    }
+
+    /// Send a message without flushing. Call [`Self::flush`] when ready.
+    pub async fn send_no_flush(&mut self, message: Message) -> Result<()> {
+        let mut writer = self.shared.lock_writer().await;
+        self.write_locked(&mut writer, message).await
+    }
+
+    /// Send multiple messages with a single flush at the end.
+    ///
+    /// The writer lock is held for the whole batch, so automatic replies from
+    /// the reader are sent before or after it, never in between.
+    pub async fn send_batch(&mut self, messages: impl IntoIterator<Item = Message>) -> Result<()> {
+        let mut writer = self.shared.lock_writer().await;
+        for message in messages {
+            self.write_locked(&mut writer, message).await?;
+        }
+        writer.flush().await
+    }
+
+    /// Flush pending writes to the underlying stream.
+    pub async fn flush(&mut self) -> Result<()> {
+        self.shared.lock_writer().await.flush().await
+    }
+
+    /// Send a ping frame.
+    pub async fn ping(&mut self, data: impl Into<Bytes>) -> Result<()> {
+        self.send(Message::Ping(data.into())).await
+    }
+
+    /// Send a pong frame.
+    pub async fn pong(&mut self, data: impl Into<Bytes>) -> Result<()> {
+        self.send(Message::Pong(data.into())).await
+    }
+
+    /// Initiate a close handshake.
+    ///
+    /// Sends a close frame; the peer's reply is delivered to the
+    /// [`WsReader`] as `Message::Close`.
+    ///
+    /// ## Errors
+    ///
+    /// - `Error::InvalidCloseCode` if `code` may not be sent on the wire
+    /// - I/O errors from the underlying stream
+    pub async fn close(&mut self, code: CloseCode, reason: &str) -> Result<()> {
+        if code.is_reserved() || !code.is_valid() {
+            return Err(Error::InvalidCloseCode(code.as_u16()));
+        }
+
+        self.shared.start_close(code, reason).await
+    }
+
+    async fn write_locked(&self, writer: &mut WriterGuard<'_, T>, message: Message) -> Result<()> {
+        // Replies are buffered before the check: the reader closes the state
+        // before queuing a close echo, so no message follows the echo.
+        self.shared.check_shutdown();
+        self.shared.buffer_replies(writer)?;
+        if !self.shared.state().can_send() {
+            // The close frame that ended the connection may still wait here.
+            if writer.buffered_len() > 0 || self.shared.going_away().is_some() {
+                writer.flush().await?;
+            }
+            return Err(Error::ConnectionClosed(None));
+        }
+        buffer_message(writer, &mut self.shared.extensions(), message)?;
+        poll_fn(|cx| writer.poll_write_buffered(cx)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::connection::Role;
+    use crate::connection::shutdown::SHUTDOWN_REASON;
+    use tokio::io::{DuplexStream, duplex};
+
+    fn pair() -> (Connection<DuplexStream>, Connection<DuplexStream>) {
+        let (a, b) = duplex(64 * 1024);
+        (
+            Connection::new(a, Role::Server, Config::server()),
+            Connection::new(b, Role::Client, Config::client()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_split_send_and_recv_concurrently() {
+        let (server, mut client) = pair();
+        let (mut reader, mut writer) = server.split();
+
+        let writer_task = tokio::spawn(async move {
+            writer.send(Message::text("from writer")).await.unwrap();
+            writer
+        });
+
+        client.send(Message::text("to reader")).await.unwrap();
+        assert_eq!(
+            reader.recv().await.unwrap(),
+            Some(Message::text("to reader"))
+        );
+        assert_eq!(
+            client.recv().await.unwrap(),
+            Some(Message::text("from writer"))
+        );
+        drop(writer_task.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_reader_pongs_while_writer_streams() {
+        let (server, mut client) = pair();
+        let (mut reader, mut writer) = server.split();
+        const MESSAGES: usize = 50;
+
+        let writer_task = tokio::spawn(async move {
+            let payload = vec![7u8; 40 * 1024];
+            for _ in 0..MESSAGES {
+                writer.send(Message::binary(payload.clone())).await.unwrap();
+            }
+            writer
+        });
+        let reader_task = tokio::spawn(async move {
+            for _ in 0..MESSAGES {
+                let msg = reader.recv().await.unwrap();
+                assert!(matches!(msg, Some(Message::Ping(_))));
+            }
+            reader
+        });
+
+        let mut binaries = 0;
+        let mut pongs = 0;
+        let mut pings_sent = 0;
+        while binaries < MESSAGES || pongs < MESSAGES {
+            if pings_sent < MESSAGES {
+                client.ping(vec![pings_sent as u8]).await.unwrap();
+                pings_sent += 1;
+            }
+            match client.recv().await.unwrap() {
+                Some(Message::Binary(data)) => {
+                    assert_eq!(data.len(), 40 * 1024);
+                    binaries += 1;
+                }
+                Some(Message::Pong(_)) => pongs += 1,
+                other => panic!("unexpected message: {:?}", other),
+            }
+        }
+
+        let writer = writer_task.await.unwrap();
+        let reader = reader_task.await.unwrap();
+        assert!(reader.reunite(writer).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_close_from_peer_stops_writer() {
+        let (server, mut client) = pair();
+        let (mut reader, mut writer) = server.split();
+
+        client.close(CloseCode::Normal, "bye").await.unwrap();
+        let msg = reader.recv().await.unwrap();
+        assert!(matches!(msg, Some(Message::Close(Some(ref cf))) if cf.code == CloseCode::Normal));
+        assert_eq!(writer.state(), ConnectionState::Closed);
+        assert_eq!(
+            writer.send(Message::text("late")).await,
+            Err(Error::ConnectionClosed(None))
+        );
+
+        // The echo was sent through the shared writer.
+        let echo = client.recv().await.unwrap();
+        assert!(matches!(echo, Some(Message::Close(Some(ref cf))) if cf.code == CloseCode::Normal));
+    }
+
+    #[tokio::test]
+    async fn test_writer_close_sets_shared_state() {
+        let (server, mut client) = pair();
+        let (reader, mut writer) = server.split();
+
+        writer.close(CloseCode::Normal, "done").await.unwrap();
+        assert_eq!(reader.state(), ConnectionState::Closing);
+        assert!(matches!(
+            client.recv().await.unwrap(),
+            Some(Message::Close(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reunite_keeps_buffered_data() {
+        let (server, mut client) = pair();
+        client.send(Message::text("one")).await.unwrap();
+        client.send(Message::text("two")).await.unwrap();
+
+        let (mut reader, writer) = server.split();
+        assert_eq!(reader.recv().await.unwrap(), Some(Message::text("one")));
+
+        let mut conn = reader.reunite(writer).unwrap();
+        assert!(conn.is_open());
+        assert_eq!(conn.recv().await.unwrap(), Some(Message::text("two")));
+    }
+
+    #[tokio::test]
+    async fn test_reunite_mismatched_halves() {
+        let (a, _a_peer) = pair();
+        let (b, _b_peer) = pair();
+        let (reader_a, writer_a) = a.split();
+        let (reader_b, writer_b) = b.split();
+
+        let Err(ReuniteError(reader_a, writer_b)) = reader_a.reunite(writer_b) else {
+            panic!("halves from different connections must not reunite");
+        };
+        assert!(reader_a.is_pair_of(&writer_a));
+        assert!(reader_b.is_pair_of(&writer_b));
+        assert!(reader_a.reunite(writer_a).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_reader_protocol_error_fails_connection() {
+        use tokio::io::AsyncWriteExt;
+
+        let (io, mut peer) = duplex(1024);
+        let conn = Connection::new(io, Role::Server, Config::server());
+        let (mut reader, writer) = conn.split();
+
+        // Masked text frame with RSV1 set and no extension negotiated
+        peer.write_all(&[0xc1, 0x80, 0, 0, 0, 0]).await.unwrap();
+        assert_eq!(reader.recv().await, Err(Error::ReservedBitsSet));
+        assert_eq!(writer.state(), ConnectionState::Closed);
+
+        let mut peer = WebSocketCodec::new(peer, Role::Client, Config::client());
+        let frame = peer.read_frame().await.unwrap();
+        assert_eq!(frame.opcode, OpCode::Close);
+        assert_eq!(
+            u16::from_be_bytes([frame.payload()[0], frame.payload()[1]]),
+            1002
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reader_protocol_error_while_writer_blocked() {
+        use tokio::io::AsyncWriteExt;
+
+        let (io, mut peer) = duplex(1024);
+        let conn = Connection::new(io, Role::Server, Config::server());
+        let (mut reader, mut writer) = conn.split();
+
+        let writer_task = tokio::spawn(async move {
+            let result = writer.send(Message::binary(vec![1u8; 64 * 1024])).await;
+            (writer, result)
+        });
+        // Wait until the writer is stuck on the full stream, holding the lock.
+        while reader.shared.writer.try_lock().is_ok() {
+            tokio::task::yield_now().await;
+        }
+
+        peer.write_all(&[0xc1, 0x80, 0, 0, 0, 0]).await.unwrap();
+        let result = tokio::time::timeout(Duration::from_secs(5), reader.recv())
+            .await
+            .expect("reader waited for the blocked writer");
+        assert_eq!(result, Err(Error::ReservedBitsSet));
+        assert_eq!(reader.state(), ConnectionState::Closed);
+
+        // The writer sends the close frame after its message.
+        let mut peer = WebSocketCodec::new(peer, Role::Client, Config::client());
+        let frame = loop {
+            let frame = peer.read_frame().await.unwrap();
+            if frame.opcode == OpCode::Close {
+                break frame;
+            }
+            assert!(matches!(
+                frame.opcode,
+                OpCode::Binary | OpCode::Continuation
+            ));
+        };
+        assert_eq!(
+            u16::from_be_bytes([frame.payload()[0], frame.payload()[1]]),
+            1002
+        );
+        let (_writer, result) = writer_task.await.unwrap();
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_reader_shutdown_signal_sends_going_away() {
+        let (server, mut client) = pair();
+        let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+        let server = server.with_shutdown(ShutdownSignal::new(shutdown_rx, Default::default()));
+        let (mut reader, writer) = server.split();
+
+        let reader_task = tokio::spawn(async move {
+            let msg = reader.recv().await;
+            (reader, msg)
+        });
+        shutdown_tx.send(true).unwrap();
+
+        let Some(Message::Close(Some(frame))) = client.recv().await.unwrap() else {
+            panic!("expected a close frame");
+        };
+        assert_eq!(frame.code, CloseCode::GoingAway);
+        assert_eq!(frame.reason, SHUTDOWN_REASON);
+
+        let (_reader, msg) = reader_task.await.unwrap();
+        assert!(matches!(msg, Ok(Some(Message::Close(_)))));
+        assert_eq!(writer.state(), ConnectionState::Closed);
+    }
+
+    /// Both peers split, both writers blocked on a full stream: each reader
+    /// must keep reading while its pongs wait for the writer.
+    #[tokio::test]
+    async fn test_split_peers_with_full_buffers_do_not_deadlock() {
+        const MESSAGES: usize = 20;
+
+        async fn run(conn: Connection<DuplexStream>) -> Connection<DuplexStream> {
+            let (mut reader, mut writer) = conn.split();
+            let writer_task = tokio::spawn(async move {
+                for i in 0..MESSAGES {
+                    writer.ping(vec![i as u8]).await.unwrap();
+                    writer
+                        .send(Message::binary(vec![1u8; 16 * 1024]))
+                        .await
+                        .unwrap();
+                }
+                writer
+            });
+
+            let mut binaries = 0;
+            while binaries < MESSAGES {
+                match reader.recv().await.unwrap() {
+                    Some(Message::Binary(_)) => binaries += 1,
+                    Some(Message::Ping(_) | Message::Pong(_)) => {}
+                    other => panic!("unexpected message: {:?}", other),
+                }
+            }
+            reader.reunite(writer_task.await.unwrap()).unwrap()
+        }
+
+        let (a, b) = duplex(1024);
+        let server = Connection::new(a, Role::Server, Config::server());
+        let client = Connection::new(b, Role::Client, Config::client());
+        let (server, client) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(tokio::spawn(run(server)), tokio::spawn(run(client)))
+        })
+        .await
+        .expect("split peers deadlocked");
+        server.unwrap();
+        client.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reader_keeps_only_latest_pong_while_writer_busy() {
+        const PINGS: u8 = 200;
+        let (server, mut client) = pair();
+        let (mut reader, writer) = server.split();
+
+        // Stands in for a writer blocked on a full stream.
+        let busy = writer.shared.writer.lock().await;
+        for i in 0..PINGS {
+            client.ping(vec![i; 100]).await.unwrap();
+        }
+        for _ in 0..PINGS {
+            let msg = reader.recv().await.unwrap();
+            assert!(matches!(msg, Some(Message::Ping(_))));
+        }
+        let queued: usize = (reader.shared.replies().frames.iter())
+            .map(|f| f.wire_size(false))
+            .sum();
+        assert!(queued < reader.codec.config().write_buffer_size + 128);
+        drop(busy);
+
+        // The answer to the last ping is still sent once the writer is free.
+        let reader_task = tokio::spawn(async move { reader.recv().await });
+        loop {
+            let Some(Message::Pong(payload)) = client.recv().await.unwrap() else {
+                panic!("expected a pong");
+            };
+            if payload[0] == PINGS - 1 {
+                break;
+            }
+        }
+        reader_task.abort();
+    }
+}
@@ -108,6 +108,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::clone_on_copy)]
     fn test_state_clone_and_copy() {
         let state = ConnectionState::Open;
         let cloned = state.clone();
@@ -5,6 +5,8 @@
 
 use thiserror::Error;
 
+use crate::message::CloseCode;
+
 /// Result type alias for WebSocket operations.
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -63,10 +65,15 @@ pub enum Error {
     #[error("I/O error: {0}")]
     Io(String),
 
-    /// Extension-related error.
+    /// Extension-related error, such as payload data that does not decode.
     #[error("Extension error: {0}")]
     Extension(String),
 
+    /// An extension failed for a local reason, such as a compressor that
+    /// could not be set up, rather than because of the peer's data.
+    #[error("Internal extension error: {0}")]
+    ExtensionInternal(String),
+
     /// Invalid close code.
     #[error("Invalid close code: {0}")]
     InvalidCloseCode(u16),
@@ -135,6 +142,14 @@ pub enum Error {
         origin: String,
     },
 
+    /// None of the client's subprotocols is supported, and the server
+    /// requires one.
+    #[error("No supported subprotocol among {offered:?}")]
+    UnsupportedSubprotocol {
+        /// The subprotocols the client offered.
+        offered: Vec<String>,
+    },
+
     /// Handshake data too large (DoS protection).
     #[error("Handshake too large: {size} bytes (max: {max})")]
     HandshakeTooLarge {
@@ -143,6 +158,100 @@ pub enum Error {
         /// Maximum allowed size.
         max: usize,
     },
+
+    /// The client requested a WebSocket version other than 13.
+    #[error("Unsupported WebSocket version: {version} (expected 13)")]
+    UnsupportedVersion {
+        /// The requested `Sec-WebSocket-Version`.
+        version: u8,
+    },
+
+    /// Upgrade request refused by the server's handshake callback.
+    #[error("Handshake rejected: {status} {reason}")]
+    HandshakeRejected {
+        /// HTTP status code sent to the peer.
+        status: u16,
+        /// Reason phrase sent to the peer.
+        reason: String,
+    },
+
+    /// Opening handshake did not complete within `timeouts.handshake`.
+    #[error("Handshake timed out")]
+    HandshakeTimeout,
+
+    /// A started frame was not fully received within `timeouts.read`.
+    #[error("Read timed out")]
+    ReadTimeout,
+
+    /// A write or flush did not complete within `timeouts.write`.
+    #[error("Write timed out")]
+    WriteTimeout,
+
+    /// No frame was received within `timeouts.idle`.
+    #[error("Connection idle timeout")]
+    IdleTimeout,
+
+    /// No pong answered a keepalive ping within `keepalive.pong_timeout`.
+    #[error("Keepalive timeout: no pong received")]
+    KeepaliveTimeout,
+
+    /// Malformed or unsupported WebSocket URL.
+    #[error("Invalid URL: {0}")]
+    InvalidUrl(String),
+
+    /// TLS handshake or configuration failure.
+    #[error("TLS error: {0}")]
+    Tls(String),
+
+    /// A proxy refused or failed to open the tunnel.
+    #[error("Proxy error: {0}")]
+    Proxy(String),
+
+    /// A configuration value cannot be used, e.g. a keepalive ping payload
+    /// too large for a control frame.
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+}
+
+impl Error {
+    /// Close code to send when this error fails the WebSocket connection.
+    ///
+    /// Errors caused by the peer's frames map to the RFC 6455 Section 7.4.1
+    /// code describing them:
+    ///
+    /// - 1002 (`ProtocolError`) for malformed frames and protocol violations
+    /// - 1007 (`InvalidPayload`) for invalid UTF-8 and undecodable extension data
+    /// - 1009 (`MessageTooBig`) for frames and messages over the configured limits
+    ///
+    /// Extension failures on this side, e.g. in the compressor, map to 1011
+    /// (`InternalError`).
+    ///
+    /// Returns `None` for errors that do not call for a close frame: I/O
+    /// failures, timeouts (the connection sends its own close frame where
+    /// appropriate), handshake errors and local misuse.
+    #[must_use]
+    pub const fn close_code(&self) -> Option<CloseCode> {
+        match self {
+            Error::InvalidFrame(_)
+            | Error::ProtocolViolation(_)
+            | Error::InvalidCloseCode(_)
+            | Error::ReservedOpcode(_)
+            | Error::FragmentedControlFrame
+            | Error::ControlFrameTooLarge(_)
+            | Error::UnmaskedClientFrame
+            | Error::MaskedServerFrame
+            | Error::ReservedBitsSet
+            | Error::IncompleteFrame { .. }
+            | Error::InvalidOpcode(_) => Some(CloseCode::ProtocolError),
+            Error::InvalidUtf8 | Error::Extension(_) => Some(CloseCode::InvalidPayload),
+            Error::FrameTooLarge { .. }
+            | Error::MessageTooLarge { .. }
+            | Error::TooManyFragments { .. }
+            | Error::PayloadTooLargeForPlatform { .. } => Some(CloseCode::MessageTooBig),
+            Error::ExtensionInternal(_) => Some(CloseCode::InternalError),
+            _ => None,
+        }
+    }
 }
 
 impl From<std::io::Error> for Error {
@@ -216,4 +325,35 @@ mod tests {
         };
         assert!(err.to_string().contains("10000"));
     }
+
+    #[test]
+    fn test_close_code_mapping() {
+        assert_eq!(
+            Error::ProtocolViolation("x".into()).close_code(),
+            Some(CloseCode::ProtocolError)
+        );
+        assert_eq!(
+            Error::ReservedBitsSet.close_code(),
+            Some(CloseCode::ProtocolError)
+        );
+        assert_eq!(
+            Error::InvalidUtf8.close_code(),
+            Some(CloseCode::InvalidPayload)
+        );
+        assert_eq!(
+            Error::Extension("Decompression failed".into()).close_code(),
+            Some(CloseCode::InvalidPayload)
+        );
+        assert_eq!(
+            Error::ExtensionInternal("Compression failed".into()).close_code(),
+            Some(CloseCode::InternalError)
+        );
+        assert_eq!(
+            Error::MessageTooLarge { size: 2, max: 1 }.close_code(),
+            Some(CloseCode::MessageTooBig)
+        );
+        assert_eq!(Error::Io("reset".into()).close_code(), None);
+        assert_eq!(Error::IdleTimeout.close_code(), None);
+        assert_eq!(Error::ConnectionClosed(None).close_code(), None);
+    }
 }
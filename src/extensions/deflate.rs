@@ -1,7 +1,7 @@
 //! Permessage-deflate WebSocket compression extension (RFC 7692).
 
 use crate::error::{Error, Result};
-use crate::extensions::{Extension, ExtensionParam, RsvBits};
+use crate::extensions::{Extension, ExtensionMessage, ExtensionParam, ExtensionScope, RsvBits};
 use crate::protocol::Frame;
 use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress};
 
@@ -171,7 +171,7 @@ impl DeflateExtension {
         }
         self.encoder
             .as_mut()
-            .ok_or_else(|| Error::Extension("Failed to initialize encoder".into()))
+            .ok_or_else(|| Error::ExtensionInternal("Failed to initialize encoder".into()))
     }
 
     pub(crate) fn ensure_decoder(&mut self) -> Result<&mut Decompress> {
@@ -188,7 +188,7 @@ impl DeflateExtension {
         }
         self.decoder
             .as_mut()
-            .ok_or_else(|| Error::Extension("Failed to initialize decoder".into()))
+            .ok_or_else(|| Error::ExtensionInternal("Failed to initialize decoder".into()))
     }
 
     fn compress(&mut self, data: &[u8]) -> Result<Vec<u8>> {
@@ -204,7 +204,7 @@ impl DeflateExtension {
         loop {
             iterations += 1;
             if iterations > MAX_COMPRESSION_ITERATIONS {
-                return Err(Error::Extension(
+                return Err(Error::ExtensionInternal(
                     "Compression exceeded max iterations".into(),
                 ));
             }
@@ -227,7 +227,7 @@ impl DeflateExtension {
 
             encoder
                 .compress(remaining, &mut compressed[old_len..], flush)
-                .map_err(|e| Error::Extension(format!("Compression failed: {}", e)))?;
+                .map_err(|e| Error::ExtensionInternal(format!("Compression failed: {}", e)))?;
 
             let consumed = (encoder.total_in() - before_in) as usize;
             let produced = (encoder.total_out() - before_out) as usize;
@@ -304,11 +304,10 @@ impl DeflateExtension {
             input_pos += consumed;
 
             if decompressed.len() > max_size {
-                return Err(Error::Extension(format!(
-                    "Decompressed size {} exceeds limit {}",
-                    decompressed.len(),
-                    max_size
-                )));
+                return Err(Error::MessageTooLarge {
+                    size: decompressed.len(),
+                    max: max_size,
+                });
             }
 
             if decompressed.len() > max_ratio_size {
@@ -382,22 +381,29 @@ impl Extension for DeflateExtension {
         RsvBits::RSV1
     }
 
+    fn scope(&self) -> ExtensionScope {
+        ExtensionScope::PerMessage
+    }
+
     fn negotiate(&mut self, params: &[ExtensionParam]) -> Result<Vec<ExtensionParam>> {
+        // Work on a copy so a rejected offer leaves nothing behind for the
+        // client's fallback offers.
+        let mut config = self.config.clone();
         let mut response = Vec::new();
 
         for param in params {
             match param.name.as_str() {
                 "server_no_context_takeover" => {
-                    self.config.server_no_context_takeover = true;
+                    config.server_no_context_takeover = true;
                     response.push(ExtensionParam::flag("server_no_context_takeover"));
                 }
                 "client_no_context_takeover" => {
-                    self.config.client_no_context_takeover = true;
+                    config.client_no_context_takeover = true;
                     response.push(ExtensionParam::flag("client_no_context_takeover"));
                 }
                 "server_max_window_bits" => {
                     let bits = Self::parse_window_bits(param.value.as_deref())?;
-                    self.config.server_max_window_bits = bits;
+                    config.server_max_window_bits = bits;
                     response.push(ExtensionParam::new(
                         "server_max_window_bits",
                         bits.to_string(),
@@ -407,9 +413,9 @@ impl Extension for DeflateExtension {
                     let bits = if param.value.is_some() {
                         Self::parse_window_bits(param.value.as_deref())?
                     } else {
-                        self.config.client_max_window_bits
+                        config.client_max_window_bits
                     };
-                    self.config.client_max_window_bits = bits;
+                    config.client_max_window_bits = bits;
                     response.push(ExtensionParam::new(
                         "client_max_window_bits",
                         bits.to_string(),
@@ -424,6 +430,7 @@ impl Extension for DeflateExtension {
             }
         }
 
+        self.config = config;
         self.negotiated = true;
         Ok(response)
     }
@@ -485,6 +492,32 @@ impl Extension for DeflateExtension {
         Ok(())
     }
 
+    fn encode_message(&mut self, message: &mut ExtensionMessage) -> Result<()> {
+        if message.opcode.is_control() || message.payload.is_empty() {
+            return Ok(());
+        }
+
+        message.payload = self.compress(&message.payload)?.into();
+        message.rsv.rsv1 = true;
+
+        Ok(())
+    }
+
+    fn decode_message(&mut self, message: &mut ExtensionMessage) -> Result<()> {
+        if !message.rsv.rsv1 {
+            return Ok(());
+        }
+
+        if message.opcode.is_control() {
+            return Err(Error::Extension("RSV1 set on control frame".to_string()));
+        }
+
+        message.payload = self.decompress(&message.payload)?.into();
+        message.rsv.rsv1 = false;
+
+        Ok(())
+    }
+
     fn offer_params(&self) -> Vec<ExtensionParam> {
         let mut params = Vec::new();
 
@@ -514,6 +547,7 @@ impl Extension for DeflateExtension {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::message::CloseCode;
     use crate::protocol::OpCode;
 
     #[test]
@@ -556,6 +590,21 @@ mod tests {
         assert!(response.iter().any(|p| p.name == "client_max_window_bits"));
     }
 
+    #[test]
+    fn test_rejected_negotiation_leaves_config_unchanged() {
+        let mut ext = DeflateExtension::new(DeflateConfig::default(), true);
+
+        let params = vec![
+            ExtensionParam::flag("server_no_context_takeover"),
+            ExtensionParam::flag("unknown"),
+        ];
+        assert!(ext.negotiate(&params).is_err());
+        assert!(!ext.config.server_no_context_takeover);
+
+        ext.negotiate(&[]).unwrap();
+        assert!(!ext.config.server_no_context_takeover);
+    }
+
     #[test]
     fn test_control_frame_bypass() {
         let mut ext = DeflateExtension::new(DeflateConfig::default(), false);
@@ -675,6 +724,44 @@ mod tests {
         assert!(ext.rsv_bits().rsv1);
         assert!(!ext.rsv_bits().rsv2);
         assert!(!ext.rsv_bits().rsv3);
+        assert_eq!(ext.scope(), ExtensionScope::PerMessage);
+    }
+
+    #[test]
+    fn test_message_compression_roundtrip() {
+        let mut client_ext = DeflateExtension::client(DeflateConfig::default());
+        let mut server_ext = DeflateExtension::server(DeflateConfig::default());
+
+        let original: Vec<u8> = (0..2000u32).map(|i| (i % 251) as u8).collect();
+        let mut message = ExtensionMessage::new(OpCode::Binary, original.clone());
+
+        client_ext.encode_message(&mut message).unwrap();
+        assert!(message.rsv.rsv1);
+        assert!(message.payload.len() < original.len());
+
+        server_ext.decode_message(&mut message).unwrap();
+        assert!(!message.rsv.rsv1);
+        assert_eq!(&message.payload[..], &original[..]);
+    }
+
+    #[test]
+    fn test_corrupt_message_maps_to_invalid_payload() {
+        let mut ext = DeflateExtension::server(DeflateConfig::default());
+        let mut message = ExtensionMessage::new(OpCode::Binary, vec![0xffu8; 8]);
+        message.rsv.rsv1 = true;
+
+        let err = ext.decode_message(&mut message).unwrap_err();
+        assert!(matches!(err, Error::Extension(_)));
+        assert_eq!(err.close_code(), Some(CloseCode::InvalidPayload));
+    }
+
+    #[test]
+    fn test_message_without_rsv1_passes_through() {
+        let mut ext = DeflateExtension::server(DeflateConfig::default());
+        let mut message = ExtensionMessage::new(OpCode::Text, "plain");
+
+        ext.decode_message(&mut message).unwrap();
+        assert_eq!(&message.payload[..], b"plain");
     }
 
     #[test]
@@ -4,6 +4,12 @@
 //! Extensions can modify frames during encoding (before sending) and decoding (after receiving),
 //! and participate in the handshake negotiation process.
 //!
+//! An extension works either on individual frames or on whole messages, as
+//! declared by [`Extension::scope`]. Per-message extensions (such as
+//! permessage-deflate, RFC 7692) see each data message exactly once: outgoing
+//! messages are encoded before fragmentation, and incoming messages are
+//! decoded after reassembly.
+//!
 //! # Example
 //!
 //! ```rust,ignore
@@ -25,7 +31,9 @@
 pub mod deflate;
 
 use crate::error::{Error, Result};
-use crate::protocol::Frame;
+use crate::protocol::handshake::is_token;
+use crate::protocol::{Frame, OpCode};
+use bytes::Bytes;
 use std::fmt;
 
 /// Represents a single extension parameter.
@@ -58,15 +66,29 @@ impl ExtensionParam {
     }
 
     /// Parse a single parameter from a string (e.g., "param=value" or "param").
+    ///
+    /// Quoted values (`param="value"`) are unquoted. A malformed quoted value
+    /// is kept with its quotes stripped; use [`ExtensionOffer::parse`] to
+    /// reject it instead.
     pub fn parse(s: &str) -> Self {
+        Self::try_parse(s).unwrap_or_else(|_| {
+            let s = s.trim();
+            match s.split_once('=') {
+                Some((name, value)) => Self::new(name.trim(), value.trim().trim_matches('"')),
+                None => Self::flag(s),
+            }
+        })
+    }
+
+    fn try_parse(s: &str) -> Result<Self> {
         let s = s.trim();
         if let Some((name, value)) = s.split_once('=') {
-            Self {
+            Ok(Self {
                 name: name.trim().to_string(),
-                value: Some(value.trim().trim_matches('"').to_string()),
-            }
+                value: Some(unquote(value.trim())?),
+            })
         } else {
-            Self::flag(s)
+            Ok(Self::flag(s))
         }
     }
 }
@@ -74,12 +96,76 @@ impl ExtensionParam {
 impl fmt::Display for ExtensionParam {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.value {
-            Some(v) => write!(f, "{}={}", self.name, v),
+            Some(v) if is_token(v) => write!(f, "{}={}", self.name, v),
+            Some(v) => {
+                write!(f, "{}=\"", self.name)?;
+                for c in v.chars() {
+                    if c == '"' || c == '\\' {
+                        f.write_str("\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+                f.write_str("\"")
+            }
             None => write!(f, "{}", self.name),
         }
     }
 }
 
+/// Remove the quotes and backslash escapes from a quoted-string value.
+/// Unquoted values are returned unchanged.
+fn unquote(value: &str) -> Result<String> {
+    let Some(inner) = value.strip_prefix('"') else {
+        return Ok(value.to_string());
+    };
+
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped) => out.push(escaped),
+                None => break,
+            },
+            '"' if chars.as_str().is_empty() => return Ok(out),
+            '"' => break,
+            c => out.push(c),
+        }
+    }
+
+    Err(Error::InvalidExtension(format!(
+        "Malformed quoted value: {}",
+        value
+    )))
+}
+
+/// Split a header value on `delimiter`, ignoring delimiters inside quoted
+/// strings (e.g. the `,` in `ext; p="a,b"`).
+pub(crate) fn split_quoted(value: &str, delimiter: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_quotes = false;
+    let mut escaped = false;
+
+    for (i, c) in value.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c == delimiter && !in_quotes => {
+                parts.push(&value[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
 /// Parsed extension offer/response from Sec-WebSocket-Extensions header.
 ///
 /// Represents a single extension with its name and parameters.
@@ -111,13 +197,14 @@ impl ExtensionOffer {
 
     /// Parse a single extension offer from a string.
     ///
-    /// Format: `extension-name; param1=value1; param2`
+    /// Format: `extension-name; param1=value1; param2="quoted value"`
     ///
     /// # Errors
     ///
-    /// Returns [`Error::InvalidExtension`] if the extension string or name is empty.
+    /// Returns [`Error::InvalidExtension`] if the extension string or name is
+    /// empty, or a quoted parameter value is malformed.
     pub fn parse(s: &str) -> Result<Self> {
-        let mut parts = s.split(';');
+        let mut parts = split_quoted(s, ';').into_iter();
         let name = parts
             .next()
             .ok_or_else(|| Error::InvalidExtension("Empty extension string".into()))?
@@ -128,7 +215,9 @@ impl ExtensionOffer {
             return Err(Error::InvalidExtension("Empty extension name".into()));
         }
 
-        let params: Vec<ExtensionParam> = parts.map(ExtensionParam::parse).collect();
+        let params = parts
+            .map(ExtensionParam::try_parse)
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(Self { name, params })
     }
@@ -136,12 +225,16 @@ impl ExtensionOffer {
     /// Parse multiple extension offers from a Sec-WebSocket-Extensions header value.
     ///
     /// Extensions are comma-separated, parameters are semicolon-separated.
+    /// Delimiters inside quoted parameter values are not split on.
     ///
     /// # Errors
     ///
     /// Returns [`Error::InvalidExtension`] if any extension offer in the header is invalid.
     pub fn parse_header(header: &str) -> Result<Vec<Self>> {
-        header.split(',').map(|s| Self::parse(s.trim())).collect()
+        split_quoted(header, ',')
+            .into_iter()
+            .map(|s| Self::parse(s.trim()))
+            .collect()
     }
 
     /// Get a parameter value by name.
@@ -197,6 +290,93 @@ impl RsvBits {
     pub fn conflicts_with(&self, other: &RsvBits) -> bool {
         (self.rsv1 && other.rsv1) || (self.rsv2 && other.rsv2) || (self.rsv3 && other.rsv3)
     }
+
+    /// The bits as they appear in the first byte of a frame header.
+    pub const fn mask(&self) -> u8 {
+        ((self.rsv1 as u8) << 6) | ((self.rsv2 as u8) << 5) | ((self.rsv3 as u8) << 4)
+    }
+
+    /// Read the bits from the first byte of a frame header.
+    pub const fn from_mask(mask: u8) -> Self {
+        Self {
+            rsv1: mask & 0x40 != 0,
+            rsv2: mask & 0x20 != 0,
+            rsv3: mask & 0x10 != 0,
+        }
+    }
+}
+
+/// Unit of data an extension transforms.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ExtensionScope {
+    /// Each data frame is encoded and decoded on its own, via
+    /// [`Extension::encode`] and [`Extension::decode`].
+    #[default]
+    PerFrame,
+    /// Each data message is encoded before fragmentation and decoded after
+    /// reassembly, via [`Extension::encode_message`] and
+    /// [`Extension::decode_message`]. RSV bits apply to the first frame only.
+    PerMessage,
+    /// Each data message is streamed through the extension one frame at a
+    /// time: [`Extension::begin_message`] on its first frame,
+    /// [`Extension::fragment`] on every frame and [`Extension::end_message`]
+    /// after the final one. RSV bits apply to the first frame only.
+    PerFragment,
+}
+
+/// Whether a per-fragment extension hook sees an outgoing or an incoming
+/// message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// An outgoing message, being encoded.
+    Encode,
+    /// An incoming message, being decoded.
+    Decode,
+}
+
+/// A complete data message as seen by per-message extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionMessage {
+    /// `OpCode::Text` or `OpCode::Binary`.
+    pub opcode: OpCode,
+    /// The message payload.
+    pub payload: Bytes,
+    /// RSV bits of the message's first frame.
+    pub rsv: RsvBits,
+}
+
+impl ExtensionMessage {
+    /// Create a message with no RSV bits set.
+    pub fn new(opcode: OpCode, payload: impl Into<Bytes>) -> Self {
+        Self {
+            opcode,
+            payload: payload.into(),
+            rsv: RsvBits::NONE,
+        }
+    }
+
+    /// Take the opcode, payload and RSV bits of a single-frame message.
+    pub fn from_frame(frame: Frame) -> Self {
+        let rsv = RsvBits {
+            rsv1: frame.rsv1,
+            rsv2: frame.rsv2,
+            rsv3: frame.rsv3,
+        };
+        Self {
+            opcode: frame.opcode,
+            payload: frame.into_payload_bytes(),
+            rsv,
+        }
+    }
+
+    /// Build a single final frame carrying this message.
+    pub fn into_frame(self) -> Frame {
+        let mut frame = Frame::new_from_bytes(true, self.opcode, self.payload);
+        frame.rsv1 = self.rsv.rsv1;
+        frame.rsv2 = self.rsv.rsv2;
+        frame.rsv3 = self.rsv.rsv3;
+        frame
+    }
 }
 
 /// WebSocket extension trait.
@@ -204,6 +384,10 @@ impl RsvBits {
 /// Extensions can:
 /// - Negotiate parameters during the handshake
 /// - Transform frames before sending (encode) and after receiving (decode)
+/// - Transform whole messages instead, by returning
+///   [`ExtensionScope::PerMessage`] from [`Extension::scope`]
+/// - Transform each message as a stream of fragments, by returning
+///   [`ExtensionScope::PerFragment`]
 /// - Declare which RSV bits they use
 ///
 /// # Thread Safety
@@ -246,6 +430,13 @@ pub trait Extension: Send + Sync {
         RsvBits::NONE
     }
 
+    /// Returns whether this extension transforms frames or whole messages.
+    ///
+    /// Default is [`ExtensionScope::PerFrame`].
+    fn scope(&self) -> ExtensionScope {
+        ExtensionScope::PerFrame
+    }
+
     /// Negotiate extension parameters during handshake.
     ///
     /// Called when the extension is offered by the peer. The extension should:
@@ -323,6 +514,93 @@ pub trait Extension: Send + Sync {
     /// - Clear RSV bits after processing to prevent validation errors
     fn decode(&mut self, frame: &mut Frame) -> Result<()>;
 
+    /// Encode a whole data message before it is fragmented.
+    ///
+    /// Called instead of [`Self::encode`] for [`ExtensionScope::PerMessage`]
+    /// extensions. Set the RSV bits this extension uses on `message.rsv`;
+    /// they are sent on the first frame only.
+    ///
+    /// The default implementation runs [`Self::encode`] on a single frame
+    /// holding the whole message.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Extension`] if the message cannot be transformed.
+    fn encode_message(&mut self, message: &mut ExtensionMessage) -> Result<()> {
+        let mut frame = message.clone().into_frame();
+        self.encode(&mut frame)?;
+        *message = ExtensionMessage::from_frame(frame);
+        Ok(())
+    }
+
+    /// Decode a whole data message after all of its fragments arrived.
+    ///
+    /// Called instead of [`Self::decode`] for [`ExtensionScope::PerMessage`]
+    /// extensions. `message.rsv` holds the first frame's RSV bits; clear the
+    /// ones this extension handled.
+    ///
+    /// The default implementation runs [`Self::decode`] on a single frame
+    /// holding the whole message.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Extension`] if the message cannot be transformed
+    /// (e.g., decompression failure).
+    fn decode_message(&mut self, message: &mut ExtensionMessage) -> Result<()> {
+        let mut frame = message.clone().into_frame();
+        self.decode(&mut frame)?;
+        *message = ExtensionMessage::from_frame(frame);
+        Ok(())
+    }
+
+    /// Start a data message, before its first frame goes through
+    /// [`Self::fragment`].
+    ///
+    /// Called for [`ExtensionScope::PerFragment`] extensions. `rsv` holds
+    /// the first frame's RSV bits: when encoding, set the bits this
+    /// extension uses; when decoding, clear the ones it handles.
+    ///
+    /// Default implementation does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Extension`] if the message cannot be transformed.
+    fn begin_message(
+        &mut self,
+        _direction: Direction,
+        _opcode: OpCode,
+        _rsv: &mut RsvBits,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// Transform the payload of one frame of the current data message.
+    /// `fin` is set on the message's final frame.
+    ///
+    /// Called for [`ExtensionScope::PerFragment`] extensions, in frame
+    /// order. Default implementation leaves the payload unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Extension`] if the fragment cannot be transformed
+    /// (e.g., undecodable data).
+    fn fragment(&mut self, _direction: Direction, _payload: &mut Bytes, _fin: bool) -> Result<()> {
+        Ok(())
+    }
+
+    /// Finish the current data message, after its final frame went through
+    /// [`Self::fragment`].
+    ///
+    /// Called for [`ExtensionScope::PerFragment`] extensions. Default
+    /// implementation does nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Extension`] if the message cannot be completed.
+    fn end_message(&mut self, _direction: Direction) -> Result<()> {
+        Ok(())
+    }
+
     /// Generate parameters to offer during client handshake.
     ///
     /// Returns the parameters to include in the Sec-WebSocket-Extensions
@@ -398,20 +676,16 @@ impl ExtensionRegistry {
 
     /// Return combined RSV bitmask used by negotiated extensions.
     pub fn negotiated_rsv_mask(&self) -> u8 {
-        let mut mask = 0u8;
-        for &idx in &self.negotiated {
-            let bits = self.extensions[idx].rsv_bits();
-            if bits.rsv1 {
-                mask |= 0x40;
-            }
-            if bits.rsv2 {
-                mask |= 0x20;
-            }
-            if bits.rsv3 {
-                mask |= 0x10;
-            }
-        }
-        mask
+        self.negotiated.iter().fold(0, |mask, &idx| {
+            mask | self.extensions[idx].rsv_bits().mask()
+        })
+    }
+
+    /// Check if any negotiated extension works at the given scope.
+    pub fn has_negotiated(&self, scope: ExtensionScope) -> bool {
+        self.negotiated
+            .iter()
+            .any(|&idx| self.extensions[idx].scope() == scope)
     }
 
     /// Generate the Sec-WebSocket-Extensions header value for client handshake.
@@ -435,7 +709,11 @@ impl ExtensionRegistry {
 
     /// Negotiate extensions based on offers from the peer (server-side).
     ///
-    /// Processes each offer and returns the accepted extensions.
+    /// Processes offers in the client's preference order and returns the
+    /// accepted extensions. A client may offer the same extension several
+    /// times with different parameters; the first offer the extension accepts
+    /// wins and the rest are fallbacks that are skipped. Offers for
+    /// extensions that are not registered are ignored.
     ///
     /// # Arguments
     ///
@@ -478,38 +756,61 @@ impl ExtensionRegistry {
 
     /// Configure extensions based on server response (client-side).
     ///
+    /// Per RFC 6455 Section 9.1, the server may only accept extensions the
+    /// client offered, and each at most once. On error no extension is left
+    /// negotiated.
+    ///
     /// # Arguments
     ///
     /// * `responses` - Extension responses from server's Sec-WebSocket-Extensions header
     ///
     /// # Errors
     ///
-    /// Returns [`Error::InvalidExtension`] if any extension fails to configure with
-    /// the provided parameters.
+    /// Returns [`Error::InvalidExtension`] if:
+    /// - A response names an extension that is not registered (not offered).
+    /// - An extension appears more than once.
+    /// - An extension fails to configure with the provided parameters.
     pub fn configure(&mut self, responses: &[ExtensionOffer]) -> Result<()> {
         self.negotiated.clear();
 
+        let result = self.configure_all(responses);
+        if result.is_err() {
+            self.negotiated.clear();
+        }
+        result
+    }
+
+    fn configure_all(&mut self, responses: &[ExtensionOffer]) -> Result<()> {
         for response in responses {
-            if let Some((idx, ext)) = self
+            let Some((idx, ext)) = self
                 .extensions
                 .iter_mut()
                 .enumerate()
                 .find(|(_, e)| e.name() == response.name)
-            {
-                if self.negotiated.contains(&idx) {
-                    continue;
-                }
-                ext.configure(&response.params)?;
-                self.negotiated.push(idx);
+            else {
+                return Err(Error::InvalidExtension(format!(
+                    "Server accepted extension '{}' that was not offered",
+                    response.name
+                )));
+            };
+            if self.negotiated.contains(&idx) {
+                return Err(Error::InvalidExtension(format!(
+                    "Server accepted extension '{}' more than once",
+                    response.name
+                )));
             }
+            ext.configure(&response.params)?;
+            self.negotiated.push(idx);
         }
 
         Ok(())
     }
 
-    /// Encode a frame through all negotiated extensions.
+    /// Encode a frame through all negotiated extensions, regardless of scope.
     ///
-    /// Extensions are applied in registration order.
+    /// Extensions are applied in registration order. Use this only for a
+    /// frame that holds a complete message; connections use
+    /// [`Self::encode_message`] and [`Self::encode_frame`].
     ///
     /// # Errors
     ///
@@ -521,9 +822,11 @@ impl ExtensionRegistry {
         Ok(())
     }
 
-    /// Decode a frame through all negotiated extensions.
+    /// Decode a frame through all negotiated extensions, regardless of scope.
     ///
-    /// Extensions are applied in reverse registration order.
+    /// Extensions are applied in reverse registration order. Use this only
+    /// for a frame that holds a complete message; connections use
+    /// [`Self::decode_frame`] and [`Self::decode_message`].
     ///
     /// # Errors
     ///
@@ -535,6 +838,107 @@ impl ExtensionRegistry {
         Ok(())
     }
 
+    /// Encode an outgoing data message through negotiated per-message
+    /// extensions, in registration order. Call before fragmenting.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Extension`] if any extension fails to encode the message.
+    pub fn encode_message(&mut self, message: &mut ExtensionMessage) -> Result<()> {
+        for &idx in &self.negotiated {
+            let ext = &mut self.extensions[idx];
+            if ext.scope() == ExtensionScope::PerMessage {
+                ext.encode_message(message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode a reassembled data message through negotiated per-message
+    /// extensions, in reverse registration order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Extension`] if any extension fails to decode the message.
+    pub fn decode_message(&mut self, message: &mut ExtensionMessage) -> Result<()> {
+        for &idx in self.negotiated.iter().rev() {
+            let ext = &mut self.extensions[idx];
+            if ext.scope() == ExtensionScope::PerMessage {
+                ext.decode_message(message)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode one outgoing data frame through negotiated per-fragment
+    /// extensions, in registration order. Call for every frame of a message,
+    /// in order, before [`Self::encode_frame`].
+    ///
+    /// A frame that starts a message (any opcode but `Continuation`) begins
+    /// it, and a final frame ends it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Extension`] if any extension fails to encode the frame.
+    pub fn encode_fragment(&mut self, frame: &mut Frame) -> Result<()> {
+        for &idx in &self.negotiated {
+            let ext = &mut self.extensions[idx];
+            if ext.scope() == ExtensionScope::PerFragment {
+                stream_fragment(ext.as_mut(), Direction::Encode, frame)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode one incoming data frame through negotiated per-fragment
+    /// extensions, in reverse registration order. Call for every frame of a
+    /// message, in order, after [`Self::decode_frame`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Extension`] if any extension fails to decode the frame.
+    pub fn decode_fragment(&mut self, frame: &mut Frame) -> Result<()> {
+        for &idx in self.negotiated.iter().rev() {
+            let ext = &mut self.extensions[idx];
+            if ext.scope() == ExtensionScope::PerFragment {
+                stream_fragment(ext.as_mut(), Direction::Decode, frame)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode one outgoing data frame through negotiated per-frame
+    /// extensions, in registration order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Extension`] if any extension fails to encode the frame.
+    pub fn encode_frame(&mut self, frame: &mut Frame) -> Result<()> {
+        for &idx in &self.negotiated {
+            let ext = &mut self.extensions[idx];
+            if ext.scope() == ExtensionScope::PerFrame {
+                ext.encode(frame)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Decode one incoming data frame through negotiated per-frame
+    /// extensions, in reverse registration order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Extension`] if any extension fails to decode the frame.
+    pub fn decode_frame(&mut self, frame: &mut Frame) -> Result<()> {
+        for &idx in self.negotiated.iter().rev() {
+            let ext = &mut self.extensions[idx];
+            if ext.scope() == ExtensionScope::PerFrame {
+                ext.decode(frame)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Format accepted extensions for Sec-WebSocket-Extensions response header.
     pub fn response_header(&self, accepted: &[ExtensionOffer]) -> String {
         accepted
@@ -545,6 +949,35 @@ impl ExtensionRegistry {
     }
 }
 
+/// Run one data frame through a per-fragment extension's hooks.
+fn stream_fragment(ext: &mut dyn Extension, direction: Direction, frame: &mut Frame) -> Result<()> {
+    if frame.opcode.is_control() {
+        return Ok(());
+    }
+    let mut rsv = RsvBits {
+        rsv1: frame.rsv1,
+        rsv2: frame.rsv2,
+        rsv3: frame.rsv3,
+    };
+    if frame.opcode != OpCode::Continuation {
+        ext.begin_message(direction, frame.opcode, &mut rsv)?;
+    }
+
+    let (fin, opcode) = (frame.fin, frame.opcode);
+    let mut payload =
+        std::mem::replace(frame, Frame::new(fin, opcode, Vec::new())).into_payload_bytes();
+    ext.fragment(direction, &mut payload, fin)?;
+    *frame = Frame::new_from_bytes(fin, opcode, payload);
+    frame.rsv1 = rsv.rsv1;
+    frame.rsv2 = rsv.rsv2;
+    frame.rsv3 = rsv.rsv3;
+
+    if fin {
+        ext.end_message(direction)?;
+    }
+    Ok(())
+}
+
 impl fmt::Debug for ExtensionRegistry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("ExtensionRegistry")
@@ -562,6 +995,7 @@ impl fmt::Debug for ExtensionRegistry {
 mod tests {
     use super::*;
     use crate::protocol::OpCode;
+    use std::sync::Arc;
 
     // ==========================================================================
     // Mock/NoOp Extension for testing
@@ -714,6 +1148,53 @@ mod tests {
         assert_eq!(offers[1].name, "x-webkit-deflate-frame");
     }
 
+    #[test]
+    fn test_extension_offer_parse_quoted_values() {
+        let offers =
+            ExtensionOffer::parse_header(r#"ext; a="x,y"; b="semi;colon"; c="q\"d", other"#)
+                .unwrap();
+        assert_eq!(offers.len(), 2);
+        assert_eq!(offers[0].params.len(), 3);
+        assert_eq!(
+            offers[0].get_param("a").unwrap().value.as_deref(),
+            Some("x,y")
+        );
+        assert_eq!(
+            offers[0].get_param("b").unwrap().value.as_deref(),
+            Some("semi;colon")
+        );
+        assert_eq!(
+            offers[0].get_param("c").unwrap().value.as_deref(),
+            Some("q\"d")
+        );
+        assert_eq!(offers[1].name, "other");
+    }
+
+    #[test]
+    fn test_extension_offer_rejects_unterminated_quote() {
+        assert!(matches!(
+            ExtensionOffer::parse(r#"ext; a="open"#),
+            Err(Error::InvalidExtension(_))
+        ));
+        assert!(matches!(
+            ExtensionOffer::parse(r#"ext; a="x"y""#),
+            Err(Error::InvalidExtension(_))
+        ));
+    }
+
+    #[test]
+    fn test_extension_param_display_quotes_non_tokens() {
+        assert_eq!(ExtensionParam::new("bits", "15").to_string(), "bits=15");
+        assert_eq!(
+            ExtensionParam::new("name", r#"a "b""#).to_string(),
+            r#"name="a \"b\"""#
+        );
+
+        let offer = ExtensionOffer::with_params("ext", vec![ExtensionParam::new("p", "x,y")]);
+        let reparsed = ExtensionOffer::parse(&offer.to_string()).unwrap();
+        assert_eq!(reparsed, offer);
+    }
+
     #[test]
     fn test_extension_offer_get_param() {
         let offer = ExtensionOffer::parse("ext; param1=value1; param2").unwrap();
@@ -944,14 +1425,31 @@ mod tests {
     }
 
     #[test]
-    fn test_registry_configure_deduplicates_same_extension() {
+    fn test_registry_configure_rejects_duplicate_extension() {
         let mut registry = ExtensionRegistry::new();
         registry.add(Box::new(NoOpExtension::new("ext1"))).unwrap();
 
         let responses = vec![ExtensionOffer::new("ext1"), ExtensionOffer::new("ext1")];
-        registry.configure(&responses).unwrap();
+        assert!(matches!(
+            registry.configure(&responses),
+            Err(Error::InvalidExtension(msg)) if msg.contains("more than once")
+        ));
 
-        assert_eq!(registry.negotiated_count(), 1);
+        assert_eq!(registry.negotiated_count(), 0);
+    }
+
+    #[test]
+    fn test_registry_configure_rejects_unoffered_extension() {
+        let mut registry = ExtensionRegistry::new();
+        registry.add(Box::new(NoOpExtension::new("ext1"))).unwrap();
+
+        let responses = vec![ExtensionOffer::new("ext1"), ExtensionOffer::new("ext2")];
+        assert!(matches!(
+            registry.configure(&responses),
+            Err(Error::InvalidExtension(msg)) if msg.contains("not offered")
+        ));
+
+        assert_eq!(registry.negotiated_count(), 0);
     }
 
     #[test]
@@ -1025,4 +1523,304 @@ mod tests {
         // NoOp should not change the payload
         assert_eq!(frame.payload(), &original_payload[..]);
     }
+
+    // ==========================================================================
+    // Extension Scope Tests
+    // ==========================================================================
+
+    /// Appends its tag to the payload on encode and strips it on decode, only
+    /// implementing the frame-level hooks.
+    struct TagExtension {
+        name: &'static str,
+        tag: u8,
+        scope: ExtensionScope,
+    }
+
+    impl Extension for TagExtension {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn scope(&self) -> ExtensionScope {
+            self.scope
+        }
+
+        fn negotiate(&mut self, _params: &[ExtensionParam]) -> Result<Vec<ExtensionParam>> {
+            Ok(vec![])
+        }
+
+        fn encode(&mut self, frame: &mut Frame) -> Result<()> {
+            let mut payload = frame.payload().to_vec();
+            payload.push(self.tag);
+            *frame = Frame::new(frame.fin, frame.opcode, payload);
+            Ok(())
+        }
+
+        fn decode(&mut self, frame: &mut Frame) -> Result<()> {
+            let payload = frame.payload();
+            if payload.last() != Some(&self.tag) {
+                return Err(Error::Extension(format!("missing tag {}", self.tag)));
+            }
+            let payload = payload[..payload.len() - 1].to_vec();
+            *frame = Frame::new(frame.fin, frame.opcode, payload);
+            Ok(())
+        }
+    }
+
+    fn scoped_registry() -> ExtensionRegistry {
+        let mut registry = ExtensionRegistry::new();
+        registry
+            .add(Box::new(TagExtension {
+                name: "x-frame",
+                tag: b'f',
+                scope: ExtensionScope::PerFrame,
+            }))
+            .unwrap();
+        registry
+            .add(Box::new(TagExtension {
+                name: "x-message",
+                tag: b'm',
+                scope: ExtensionScope::PerMessage,
+            }))
+            .unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_rsv_bits_mask_roundtrip() {
+        assert_eq!(RsvBits::NONE.mask(), 0);
+        assert_eq!(RsvBits::RSV1.mask(), 0x40);
+        let all = RsvBits {
+            rsv1: true,
+            rsv2: true,
+            rsv3: true,
+        };
+        assert_eq!(all.mask(), 0x70);
+        assert_eq!(RsvBits::from_mask(0x70), all);
+        assert_eq!(RsvBits::from_mask(0x20).mask(), 0x20);
+    }
+
+    #[test]
+    fn test_default_scope_is_per_frame() {
+        let ext = NoOpExtension::new("noop");
+        assert_eq!(ext.scope(), ExtensionScope::PerFrame);
+    }
+
+    #[test]
+    fn test_has_negotiated_scope() {
+        let mut registry = scoped_registry();
+        assert!(!registry.has_negotiated(ExtensionScope::PerFrame));
+        assert!(!registry.has_negotiated(ExtensionScope::PerMessage));
+
+        registry.negotiate(&[ExtensionOffer::new("x-message")]);
+        assert!(!registry.has_negotiated(ExtensionScope::PerFrame));
+        assert!(registry.has_negotiated(ExtensionScope::PerMessage));
+    }
+
+    #[test]
+    fn test_registry_routes_by_scope() {
+        let mut registry = scoped_registry();
+        registry.negotiate(&[
+            ExtensionOffer::new("x-frame"),
+            ExtensionOffer::new("x-message"),
+        ]);
+
+        let mut message = ExtensionMessage::new(OpCode::Binary, &b"data"[..]);
+        registry.encode_message(&mut message).unwrap();
+        assert_eq!(&message.payload[..], b"datam");
+
+        let mut frame = message.into_frame();
+        registry.encode_frame(&mut frame).unwrap();
+        assert_eq!(frame.payload(), b"datamf");
+
+        registry.decode_frame(&mut frame).unwrap();
+        assert_eq!(frame.payload(), b"datam");
+
+        let mut message = ExtensionMessage::from_frame(frame);
+        registry.decode_message(&mut message).unwrap();
+        assert_eq!(&message.payload[..], b"data");
+    }
+
+    /// XORs each byte with a counter that runs across a message's frames,
+    /// so fragments only decode in order, and logs the hooks it sees.
+    struct StreamXorExtension {
+        counter: u8,
+        active: bool,
+        log: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    impl Extension for StreamXorExtension {
+        fn name(&self) -> &str {
+            "x-stream"
+        }
+
+        fn rsv_bits(&self) -> RsvBits {
+            RsvBits::from_mask(0x20)
+        }
+
+        fn scope(&self) -> ExtensionScope {
+            ExtensionScope::PerFragment
+        }
+
+        fn negotiate(&mut self, _params: &[ExtensionParam]) -> Result<Vec<ExtensionParam>> {
+            Ok(vec![])
+        }
+
+        fn encode(&mut self, _frame: &mut Frame) -> Result<()> {
+            unreachable!("per-fragment extensions use the streaming hooks")
+        }
+
+        fn decode(&mut self, _frame: &mut Frame) -> Result<()> {
+            unreachable!("per-fragment extensions use the streaming hooks")
+        }
+
+        fn begin_message(
+            &mut self,
+            direction: Direction,
+            _opcode: OpCode,
+            rsv: &mut RsvBits,
+        ) -> Result<()> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("begin {:?}", direction));
+            self.counter = 0;
+            self.active = direction == Direction::Encode || rsv.rsv2;
+            rsv.rsv2 = direction == Direction::Encode;
+            Ok(())
+        }
+
+        fn fragment(
+            &mut self,
+            _direction: Direction,
+            payload: &mut Bytes,
+            fin: bool,
+        ) -> Result<()> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("fragment fin={}", fin));
+            if self.active {
+                let mut data = payload.to_vec();
+                for byte in &mut data {
+                    *byte ^= self.counter;
+                    self.counter = self.counter.wrapping_add(1);
+                }
+                *payload = data.into();
+            }
+            Ok(())
+        }
+
+        fn end_message(&mut self, direction: Direction) -> Result<()> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("end {:?}", direction));
+            self.active = false;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_registry_streams_fragments() {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut registry = ExtensionRegistry::new();
+        registry
+            .add(Box::new(StreamXorExtension {
+                counter: 0,
+                active: false,
+                log: Arc::clone(&log),
+            }))
+            .unwrap();
+        registry.negotiate(&[ExtensionOffer::new("x-stream")]);
+        assert!(registry.has_negotiated(ExtensionScope::PerFragment));
+
+        let mut frames = vec![
+            Frame::new(false, OpCode::Binary, b"abc".to_vec()),
+            Frame::new(false, OpCode::Continuation, b"de".to_vec()),
+            Frame::new(true, OpCode::Continuation, b"f".to_vec()),
+        ];
+        for frame in &mut frames {
+            registry.encode_fragment(frame).unwrap();
+        }
+        assert!(frames[0].rsv2);
+        assert!(!frames[1].rsv2 && !frames[2].rsv2);
+        assert_eq!(frames[1].payload(), &[b'd' ^ 3, b'e' ^ 4]);
+
+        for frame in &mut frames {
+            registry.decode_fragment(frame).unwrap();
+        }
+        assert!(!frames[0].rsv2);
+        let payload: Vec<u8> = frames.iter().flat_map(|f| f.payload().to_vec()).collect();
+        assert_eq!(payload, b"abcdef");
+        assert_eq!(
+            *log.lock().unwrap(),
+            [
+                "begin Encode",
+                "fragment fin=false",
+                "fragment fin=false",
+                "fragment fin=true",
+                "end Encode",
+                "begin Decode",
+                "fragment fin=false",
+                "fragment fin=false",
+                "fragment fin=true",
+                "end Decode",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_per_fragment_extension_skips_control_frames() {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut registry = ExtensionRegistry::new();
+        registry
+            .add(Box::new(StreamXorExtension {
+                counter: 0,
+                active: false,
+                log: Arc::clone(&log),
+            }))
+            .unwrap();
+        registry.negotiate(&[ExtensionOffer::new("x-stream")]);
+
+        let mut ping = Frame::ping(b"hi".to_vec());
+        registry.encode_fragment(&mut ping).unwrap();
+        assert_eq!(ping.payload(), b"hi");
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_decode_message_reverse_order() {
+        let mut registry = ExtensionRegistry::new();
+        for (name, tag) in [("x-a", b'a'), ("x-b", b'b')] {
+            registry
+                .add(Box::new(TagExtension {
+                    name,
+                    tag,
+                    scope: ExtensionScope::PerMessage,
+                }))
+                .unwrap();
+        }
+        registry.negotiate(&[ExtensionOffer::new("x-a"), ExtensionOffer::new("x-b")]);
+
+        let mut message = ExtensionMessage::new(OpCode::Text, "hi");
+        registry.encode_message(&mut message).unwrap();
+        assert_eq!(&message.payload[..], b"hiab");
+
+        registry.decode_message(&mut message).unwrap();
+        assert_eq!(&message.payload[..], b"hi");
+    }
+
+    #[test]
+    fn test_extension_message_frame_conversion_keeps_rsv() {
+        let mut message = ExtensionMessage::new(OpCode::Text, "x");
+        message.rsv = RsvBits::RSV1;
+
+        let frame = message.clone().into_frame();
+        assert!(frame.fin);
+        assert!(frame.rsv1);
+        assert!(!frame.rsv2);
+
+        assert_eq!(ExtensionMessage::from_frame(frame), message);
+    }
 }
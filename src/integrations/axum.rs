@@ -0,0 +1,106 @@
+//! WebSocket routes in axum (feature = "axum").
+//!
+//! [`WebSocketUpgrade`] is an extractor: a handler that takes it receives
+//! requests that carry a WebSocket upgrade, while other requests are
+//! answered with [`rejection_response`].
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use axum::{Router, response::Response, routing::get};
+//! use rsws::integrations::axum::WebSocketUpgrade;
+//! use rsws::{Config, Message};
+//!
+//! async fn ws(ws: WebSocketUpgrade) -> Response {
+//!     ws.with_config(Config::server().with_subprotocols(["chat"]))
+//!         .on_upgrade(|mut conn| async move {
+//!             while let Ok(Some(msg)) = conn.recv().await {
+//!                 if msg.is_text() {
+//!                     let _ = conn.send(msg).await;
+//!                 }
+//!             }
+//!         })
+//! }
+//!
+//! let app: Router = Router::new().route("/ws", get(ws));
+//! ```
+
+use axum_core::extract::FromRequestParts;
+use axum_core::response::Response;
+
+use super::hyper::rejection_response;
+pub use super::hyper::{UpgradedConnection, WebSocketUpgrade};
+
+impl<S: Send + Sync> FromRequestParts<S> for WebSocketUpgrade {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut ::http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        WebSocketUpgrade::from_parts(parts).map_err(|e| rejection_response(&e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientBuilder;
+    use crate::config::Config;
+    use crate::message::Message;
+    use axum::Router;
+    use axum::routing::get;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    async fn echo(ws: WebSocketUpgrade) -> Response {
+        ws.with_config(Config::server().with_subprotocols(["chat"]))
+            .on_upgrade(|mut conn| async move {
+                while let Ok(Some(msg)) = conn.recv().await {
+                    if msg.is_text() {
+                        let _ = conn.send(msg).await;
+                    }
+                }
+            })
+    }
+
+    async fn start() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new().route("/ws", get(echo));
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_extractor_upgrades() {
+        let addr = start().await;
+
+        let (mut conn, _response) = ClientBuilder::new(&format!("ws://{}/ws", addr))
+            .unwrap()
+            .with_subprotocols(["chat"])
+            .connect()
+            .await
+            .unwrap();
+        assert_eq!(conn.subprotocol(), Some("chat"));
+
+        conn.send(Message::text("hello")).await.unwrap();
+        assert_eq!(conn.recv().await.unwrap(), Some(Message::text("hello")));
+    }
+
+    #[tokio::test]
+    async fn test_extractor_rejects_plain_request() {
+        let addr = start().await;
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /ws HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = vec![0u8; 1024];
+        let n = stream.read(&mut buf).await.unwrap();
+        assert!(buf[..n].starts_with(b"HTTP/1.1 400 Bad Request\r\n"));
+    }
+}
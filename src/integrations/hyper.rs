@@ -0,0 +1,362 @@
+//! WebSocket upgrades on hyper 1.x servers (feature = "hyper").
+//!
+//! [`WebSocketUpgrade`] takes an incoming request, validates it with the same
+//! rules as [`server::accept`](crate::server::accept), selects the
+//! subprotocol from the [`Config`], negotiates extensions, and answers with
+//! the `101 Switching Protocols` response. Once hyper hands over the
+//! connection, the handler runs on a new task with a ready [`Connection`].
+//! Invalid requests get the same `400`/`403`/`426` responses as
+//! [`server::accept`](crate::server::accept).
+//!
+//! The hyper connection must be served with upgrades enabled
+//! (`http1::Builder::serve_connection(..).with_upgrades()`).
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use rsws::integrations::hyper::upgrade;
+//! use rsws::{Config, Message};
+//!
+//! async fn handle(mut req: Request<Incoming>) -> Result<Response<String>, Infallible> {
+//!     Ok(upgrade(&mut req, Config::server(), |mut conn| async move {
+//!         while let Ok(Some(msg)) = conn.recv().await {
+//!             if msg.is_text() || msg.is_binary() {
+//!                 let _ = conn.send(msg).await;
+//!             }
+//!         }
+//!     }))
+//! }
+//! ```
+
+use std::future::Future;
+
+use ::http::{Request, Response, header};
+use bytes::Bytes;
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use hyper_util::rt::TokioIo;
+
+use crate::config::Config;
+use crate::connection::{Connection, Role};
+use crate::error::{Error, Result};
+use crate::extensions::ExtensionRegistry;
+use crate::protocol::{HandshakeRequest, HandshakeResponse};
+use crate::server::Rejection;
+
+/// A server connection over a hyper upgrade.
+pub type UpgradedConnection = Connection<TokioIo<Upgraded>>;
+
+/// A pending WebSocket upgrade taken from an HTTP request.
+///
+/// Created with [`Self::new`] (or as an axum extractor with the `axum`
+/// feature), configured with [`Self::with_config`] and
+/// [`Self::with_extensions`], and completed with [`Self::on_upgrade`].
+#[derive(Debug)]
+pub struct WebSocketUpgrade {
+    request: HandshakeRequest,
+    on_upgrade: OnUpgrade,
+    config: Config,
+    extensions: ExtensionRegistry,
+}
+
+impl WebSocketUpgrade {
+    /// Take the upgrade from `req`.
+    ///
+    /// The request is converted as by [`HandshakeRequest::try_from`]; the
+    /// checks against the configuration run in [`Self::on_upgrade`].
+    ///
+    /// # Errors
+    ///
+    /// - `Error::InvalidHandshake` if the request is not a WebSocket upgrade
+    ///   or was not received by a hyper server with upgrades enabled
+    pub fn new<B>(req: &mut Request<B>) -> Result<Self> {
+        let request = HandshakeRequest::try_from(&*req)?;
+        Self::with_request(request, req.extensions_mut())
+    }
+
+    /// Take the upgrade from request parts, as [`Self::new`] does.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::new`].
+    pub fn from_parts(parts: &mut ::http::request::Parts) -> Result<Self> {
+        let request = HandshakeRequest::try_from(&*parts)?;
+        Self::with_request(request, &mut parts.extensions)
+    }
+
+    fn with_request(
+        request: HandshakeRequest,
+        extensions: &mut ::http::Extensions,
+    ) -> Result<Self> {
+        let on_upgrade = extensions.remove::<OnUpgrade>().ok_or_else(|| {
+            Error::InvalidHandshake("Request was not received by a hyper server".into())
+        })?;
+        Ok(Self {
+            request,
+            on_upgrade,
+            config: Config::server(),
+            extensions: ExtensionRegistry::new(),
+        })
+    }
+
+    /// Set the connection configuration (default: [`Config::server()`]).
+    ///
+    /// Origin checks and subprotocol selection use this configuration.
+    #[must_use]
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Negotiate the client's extension offers against `extensions`.
+    #[must_use]
+    pub fn with_extensions(mut self, extensions: ExtensionRegistry) -> Self {
+        self.extensions = extensions;
+        self
+    }
+
+    /// The upgrade request, for routing or authentication before
+    /// [`Self::on_upgrade`].
+    #[must_use]
+    pub fn request(&self) -> &HandshakeRequest {
+        &self.request
+    }
+
+    /// Complete the handshake and run `handler` once the connection is
+    /// upgraded.
+    ///
+    /// Returns the response to send: `101 Switching Protocols` on success,
+    /// or the rejection for an invalid request (see [`rejection_response`]),
+    /// in which case `handler` never runs. The handler is spawned on the
+    /// current Tokio runtime; if hyper fails to upgrade the connection (for
+    /// example because the client went away), it is dropped without running.
+    pub fn on_upgrade<R, F, Fut>(self, handler: F) -> Response<R>
+    where
+        R: Default,
+        F: FnOnce(UpgradedConnection) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let Self {
+            request,
+            on_upgrade,
+            config,
+            extensions,
+        } = self;
+
+        let accepted = request.validate_with_config(&config).and_then(|()| {
+            let protocol = request.select_subprotocol(&config)?;
+            let (mut response, extensions) =
+                HandshakeResponse::from_request_with_extensions(&request, extensions)?;
+            response.protocol = protocol;
+            Ok((response, extensions))
+        });
+        let (response, extensions) = match accepted {
+            Ok(v) => v,
+            Err(e) => return rejection_response(&e),
+        };
+
+        let protocol = response.protocol.clone();
+        let response = match response.into_http_response() {
+            Ok(response) => response,
+            Err(_) => return into_response(Rejection::new(500, "Internal Server Error")),
+        };
+
+        tokio::spawn(async move {
+            let Ok(upgraded) = on_upgrade.await else {
+                return;
+            };
+            let conn = Connection::from_parts(
+                TokioIo::new(upgraded),
+                Bytes::new(),
+                Role::Server,
+                config,
+                extensions,
+            )
+            .with_subprotocol(protocol)
+            .with_request(request);
+            handler(conn).await;
+        });
+
+        response.map(|()| R::default())
+    }
+}
+
+/// Upgrade `req` with `config` and run `handler` on the connection.
+///
+/// Shorthand for [`WebSocketUpgrade::new`] followed by
+/// [`WebSocketUpgrade::on_upgrade`]; a request that cannot be upgraded gets
+/// its [`rejection_response`].
+pub fn upgrade<B, R, F, Fut>(req: &mut Request<B>, config: Config, handler: F) -> Response<R>
+where
+    R: Default,
+    F: FnOnce(UpgradedConnection) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    match WebSocketUpgrade::new(req) {
+        Ok(ws) => ws.with_config(config).on_upgrade(handler),
+        Err(e) => rejection_response(&e),
+    }
+}
+
+/// Check if `req` asks for a WebSocket upgrade (`Upgrade: websocket`).
+///
+/// Useful for routes that serve both plain HTTP and WebSocket clients.
+pub fn is_upgrade_request<B>(req: &Request<B>) -> bool {
+    req.headers()
+        .get_all(header::UPGRADE)
+        .iter()
+        .any(|v| v.as_bytes().eq_ignore_ascii_case(b"websocket"))
+}
+
+/// The response [`server::accept`](crate::server::accept) would send for a
+/// handshake that failed with `err`: `403` for disallowed origins, `426`
+/// with `Sec-WebSocket-Version: 13` for unsupported versions, `400`
+/// otherwise.
+pub fn rejection_response<R: Default>(err: &Error) -> Response<R> {
+    into_response(Rejection::for_error(err))
+}
+
+fn into_response<R: Default>(rejection: Rejection) -> Response<R> {
+    let status = rejection.status;
+    match rejection.into_http_response() {
+        Ok(response) => response.map(|()| R::default()),
+        Err(_) => {
+            let mut response = Response::new(R::default());
+            *response.status_mut() =
+                ::http::StatusCode::from_u16(status).unwrap_or(::http::StatusCode::BAD_REQUEST);
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientBuilder;
+    use crate::message::Message;
+    use hyper::body::Incoming;
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use std::convert::Infallible;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+
+    /// Serve one hyper HTTP/1 connection over an in-memory stream.
+    fn serve<F>(config: Config, handler: F) -> tokio::io::DuplexStream
+    where
+        F: Fn(UpgradedConnection) -> std::pin::Pin<Box<dyn Future<Output = ()> + Send>>
+            + Clone
+            + Send
+            + Sync
+            + 'static,
+    {
+        let (client, server) = duplex(4096);
+        tokio::spawn(async move {
+            let service = service_fn(move |mut req: Request<Incoming>| {
+                let config = config.clone();
+                let handler = handler.clone();
+                async move { Ok::<Response<String>, Infallible>(upgrade(&mut req, config, handler)) }
+            });
+            let _ = http1::Builder::new()
+                .serve_connection(TokioIo::new(server), service)
+                .with_upgrades()
+                .await;
+        });
+        client
+    }
+
+    fn echo(mut conn: UpgradedConnection) -> std::pin::Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async move {
+            while let Ok(Some(msg)) = conn.recv().await {
+                if msg.is_text() || msg.is_binary() {
+                    let _ = conn.send(msg).await;
+                }
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_echo() {
+        let config = Config::server().with_subprotocols(["chat"]);
+        let stream = serve(config, echo);
+
+        let (mut conn, _response) = ClientBuilder::new("ws://localhost/ws")
+            .unwrap()
+            .with_subprotocols(["chat"])
+            .handshake(stream)
+            .await
+            .unwrap();
+        assert_eq!(conn.subprotocol(), Some("chat"));
+
+        conn.send(Message::text("hello")).await.unwrap();
+        assert_eq!(conn.recv().await.unwrap(), Some(Message::text("hello")));
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_handler_sees_request() {
+        let stream = serve(Config::server(), |mut conn| {
+            Box::pin(async move {
+                let token = conn.request().and_then(|r| r.query_param("token"));
+                let _ = conn.send(Message::text(token.unwrap_or_default())).await;
+            })
+        });
+
+        let (mut conn, _response) = ClientBuilder::new("ws://localhost/ws?token=abc")
+            .unwrap()
+            .handshake(stream)
+            .await
+            .unwrap();
+        assert_eq!(conn.recv().await.unwrap(), Some(Message::text("abc")));
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_rejects_unsupported_version() {
+        let mut stream = serve(Config::server(), echo);
+        stream
+            .write_all(
+                b"GET /ws HTTP/1.1\r\n\
+                Host: localhost\r\n\
+                Upgrade: websocket\r\n\
+                Connection: Upgrade\r\n\
+                Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                Sec-WebSocket-Version: 8\r\n\
+                \r\n",
+            )
+            .await
+            .unwrap();
+
+        let mut buf = vec![0u8; 1024];
+        let n = stream.read(&mut buf).await.unwrap();
+        let response = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+        assert!(response.starts_with("http/1.1 426 upgrade required\r\n"));
+        assert!(response.contains("sec-websocket-version: 13\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_rejects_plain_request() {
+        let mut stream = serve(Config::server(), echo);
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+
+        let mut buf = vec![0u8; 1024];
+        let n = stream.read(&mut buf).await.unwrap();
+        assert!(buf[..n].starts_with(b"HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[test]
+    fn test_new_requires_hyper_request() {
+        let mut req = Request::get("/ws")
+            .header("Host", "localhost")
+            .header("Upgrade", "websocket")
+            .header("Connection", "Upgrade")
+            .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .header("Sec-WebSocket-Version", "13")
+            .body(())
+            .unwrap();
+        assert!(is_upgrade_request(&req));
+        assert!(matches!(
+            WebSocketUpgrade::new(&mut req),
+            Err(Error::InvalidHandshake(_))
+        ));
+    }
+}
@@ -0,0 +1,10 @@
+//! Integrations with third-party HTTP servers.
+//!
+//! - [`hyper`] (feature = "hyper"): upgrade requests on hyper 1.x servers.
+//! - [`axum`] (feature = "axum"): a [`WebSocketUpgrade`](hyper::WebSocketUpgrade)
+//!   extractor for axum handlers.
+
+#[cfg(feature = "axum")]
+pub mod axum;
+#[cfg(feature = "hyper")]
+pub mod hyper;
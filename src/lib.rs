@@ -14,36 +14,48 @@
 //! ## Quick Start
 //!
 //! ```rust,ignore
-//! use rsws::{Connection, Config, Role};
+//! use rsws::{Connection, Config, Message, Role};
 //!
-//! // Client connection
+//! // Client connection with the built-in handshake
+//! let (mut conn, _response) = rsws::client::connect("ws://example.com/chat").await?;
+//! conn.send(Message::text("Hello")).await?;
+//!
+//! // Or wrap a stream whose handshake was done elsewhere
 //! let config = Config::client();
-//! let conn = Connection::new(stream, Role::Client, config).await?;
+//! let conn = Connection::new(stream, Role::Client, config);
 //! ```
 
 pub mod config;
 pub mod connection;
 pub mod error;
 pub mod extensions;
+#[cfg(feature = "hyper")]
+pub mod integrations;
 pub mod message;
 pub mod protocol;
 
+#[cfg(feature = "async-tokio")]
+pub mod client;
 #[cfg(feature = "async-tokio")]
 pub mod codec;
+#[cfg(feature = "async-tokio")]
+pub mod server;
 
 pub use bytes::Bytes;
 pub use config::{Config, Limits};
 #[cfg(feature = "async-tokio")]
-pub use connection::Connection;
+pub use connection::{Connection, WsReader, WsWriter};
 pub use connection::{ConnectionState, Role};
 pub use error::{Error, Result};
-pub use message::{CloseCode, CloseFrame, Message};
+pub use message::{CloseCode, CloseFrame, CloseOutcome, Message};
 pub use protocol::{HandshakeRequest, HandshakeResponse, OpCode, WS_GUID, compute_accept_key};
 
 #[cfg(feature = "async-tokio")]
 pub use codec::WebSocketCodec;
+#[cfg(feature = "async-tokio")]
+pub use server::Server;
 
-#[cfg(feature = "tls-rustls")]
+#[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
 pub mod tls;
 
 #[cfg(test)]
@@ -63,6 +75,8 @@ mod tests {
         assert_send::<CloseFrame>();
         assert_send::<ConnectionState>();
         assert_send::<Role>();
+        #[cfg(feature = "async-tokio")]
+        assert_send::<Server>();
     }
 
     #[test]
@@ -75,5 +89,7 @@ mod tests {
         assert_sync::<CloseFrame>();
         assert_sync::<ConnectionState>();
         assert_sync::<Role>();
+        #[cfg(feature = "async-tokio")]
+        assert_sync::<Server>();
     }
 }
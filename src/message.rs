@@ -114,6 +114,41 @@ impl CloseFrame {
     }
 }
 
+/// How a closing handshake ended.
+///
+/// Returned by `Connection::close_and_wait`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CloseOutcome {
+    /// The peer answered with a close frame (carrying its payload, if any).
+    Clean(Option<CloseFrame>),
+    /// The stream ended or the timeout passed before the peer's close frame
+    /// arrived (reported as 1006).
+    Abnormal,
+}
+
+impl CloseOutcome {
+    /// Check if both close frames were exchanged.
+    #[must_use]
+    pub const fn is_clean(&self) -> bool {
+        matches!(self, CloseOutcome::Clean(_))
+    }
+
+    /// The close code to report for this outcome.
+    ///
+    /// This is the peer's code for a clean close, 1005 (No Status Received)
+    /// if the peer's close frame was empty, and 1006 (Abnormal Closure)
+    /// otherwise. The last two must never be sent on the wire.
+    #[must_use]
+    pub const fn code(&self) -> CloseCode {
+        match self {
+            CloseOutcome::Clean(Some(frame)) => frame.code,
+            CloseOutcome::Clean(None) => CloseCode::Other(1005),
+            CloseOutcome::Abnormal => CloseCode::Other(1006),
+        }
+    }
+}
+
 /// WebSocket message types.
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[non_exhaustive]
@@ -457,4 +492,14 @@ mod tests {
         assert!(!Message::text("hello").is_binary());
         assert!(!Message::pong(vec![]).is_binary());
     }
+
+    #[test]
+    fn test_close_outcome_code() {
+        let clean = CloseOutcome::Clean(Some(CloseFrame::new(CloseCode::GoingAway, "")));
+        assert!(clean.is_clean());
+        assert_eq!(clean.code(), CloseCode::GoingAway);
+        assert_eq!(CloseOutcome::Clean(None).code().as_u16(), 1005);
+        assert!(!CloseOutcome::Abnormal.is_clean());
+        assert_eq!(CloseOutcome::Abnormal.code().as_u16(), 1006);
+    }
 }
@@ -67,7 +67,10 @@ impl MessageAssembler {
             self.opcode = Some(frame.opcode);
             self.first_frame_rsv1 = frame.rsv1;
 
-            if frame.opcode == OpCode::Text {
+            // Extension-encoded payloads (RSV bits set) are only valid UTF-8
+            // after decoding, so they are validated when converted to text.
+            let encoded = frame.rsv1 || frame.rsv2 || frame.rsv3;
+            if frame.opcode == OpCode::Text && !encoded {
                 self.utf8_validator = Some(Utf8Validator::new());
             }
         }
@@ -326,6 +329,20 @@ mod tests {
         assert!(matches!(result, Err(Error::InvalidUtf8)));
     }
 
+    #[test]
+    fn test_compressed_text_skips_utf8_validation() {
+        let mut assembler = MessageAssembler::new(test_config());
+
+        let mut first = Frame::new(false, OpCode::Text, vec![0xf2, 0x48]);
+        first.rsv1 = true;
+        assert!(assembler.push(first).unwrap().is_none());
+        let last = Frame::new(true, OpCode::Continuation, vec![0xcd, 0xc9]);
+
+        let msg = assembler.push(last).unwrap().unwrap();
+        assert!(msg.rsv1);
+        assert_eq!(&msg.payload[..], &[0xf2, 0x48, 0xcd, 0xc9]);
+    }
+
     #[test]
     fn test_binary_message_no_utf8_validation() {
         let mut assembler = MessageAssembler::new(test_config());
@@ -3,64 +3,127 @@
 //! This module handles the HTTP Upgrade mechanism for establishing WebSocket connections.
 
 use crate::error::{Error, Result};
+use crate::extensions::{ExtensionOffer, ExtensionRegistry, split_quoted};
+use crate::protocol::HeaderMap;
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use sha1::{Digest, Sha1};
-use std::collections::HashMap;
 
 /// The WebSocket GUID used in the Sec-WebSocket-Accept calculation (RFC 6455).
 pub const WS_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
 
-/// Parse HTTP headers from an iterator of lines into a case-insensitive HashMap.
+/// Find the end of an HTTP head in `data`.
+///
+/// Returns the length of the head including the terminating `\r\n\r\n`,
+/// or `None` if the terminator has not been received yet. Anything past the
+/// returned offset belongs to the WebSocket stream.
+#[must_use]
+pub fn find_head_end(data: &[u8]) -> Option<usize> {
+    data.windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|pos| pos + 4)
+}
+
+/// Parse HTTP headers from an iterator of lines into a [`HeaderMap`].
 ///
 /// Optionally checks for duplicate security-critical headers when `security_headers` is provided.
 ///
 /// # Arguments
 /// * `lines` - Iterator over header lines (after the request/status line)
-/// * `security_headers` - Optional slice of header names that should not be duplicated
+/// * `security_headers` - Optional slice of lowercase header names that should not be duplicated
 ///
 /// # Returns
-/// A HashMap with lowercase header names as keys and trimmed values.
+/// Every header line in order, with names as sent and trimmed values.
 ///
 /// # Errors
 /// Returns `Error::InvalidHandshake` if a security-critical header is duplicated.
-fn parse_headers<'a, I>(
-    lines: I,
-    security_headers: Option<&[&str]>,
-) -> Result<HashMap<String, String>>
+fn parse_headers<'a, I>(lines: I, security_headers: Option<&[&str]>) -> Result<HeaderMap>
 where
     I: Iterator<Item = &'a str>,
 {
-    let mut headers: HashMap<String, String> = HashMap::new();
+    let mut headers = HeaderMap::new();
 
     for line in lines {
         if line.is_empty() {
             break;
         }
         if let Some((name, value)) = line.split_once(':') {
-            let name_lower = name.trim().to_lowercase();
+            let name = name.trim();
 
             #[allow(clippy::collapsible_if)]
             if let Some(sec_headers) = security_headers {
-                if sec_headers.contains(&name_lower.as_str()) && headers.contains_key(&name_lower) {
+                if sec_headers.iter().any(|h| h.eq_ignore_ascii_case(name))
+                    && headers.contains(name)
+                {
                     return Err(Error::InvalidHandshake(format!(
                         "Duplicate header: {}",
-                        name.trim()
+                        name
                     )));
                 }
             }
 
-            headers.insert(name_lower, value.trim().to_string());
+            headers.append(name, value.trim());
         }
     }
 
     Ok(headers)
 }
 
+/// Decode one `application/x-www-form-urlencoded` name or value: `+` is a
+/// space and `%XX` a byte. Malformed escapes are kept as is.
+fn decode_query_component(s: &str) -> String {
+    percent_decode(s, true)
+}
+
+/// Decode one path segment: `%XX` is a byte and `+` stays a `+`.
+/// Malformed escapes are kept as is.
+#[cfg(feature = "async-tokio")]
+pub(crate) fn decode_path_component(s: &str) -> String {
+    percent_decode(s, false)
+}
+
+fn percent_decode(s: &str, plus_as_space: bool) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' if plus_as_space => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                match std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(byte) => {
+                        out.push(byte);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Collect the entries of every `Sec-WebSocket-Extensions` line, one per
+/// extension, keeping commas inside quoted parameter values.
+fn split_extensions(headers: &HeaderMap) -> Vec<String> {
+    headers
+        .get_all("sec-websocket-extensions")
+        .flat_map(|value| split_quoted(value, ','))
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
 /// Validate that a header value does not contain CR or LF characters.
 ///
 /// # Errors
 /// Returns `Error::InvalidHeaderValue` if the value contains `\r` or `\n`.
-fn validate_header_value(header_name: &str, value: &str) -> Result<()> {
+pub(crate) fn validate_header_value(header_name: &str, value: &str) -> Result<()> {
     if value.contains('\r') || value.contains('\n') {
         return Err(Error::InvalidHeaderValue {
             header: header_name.to_string(),
@@ -70,6 +133,38 @@ fn validate_header_value(header_name: &str, value: &str) -> Result<()> {
     Ok(())
 }
 
+/// Check if `s` is an HTTP token (RFC 7230 Section 3.2.6).
+pub(crate) fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|b| b.is_ascii_graphic() && !b"\"(),/:;<=>?@[\\]{}".contains(&b))
+}
+
+/// Validate that a header name is an HTTP token.
+///
+/// # Errors
+/// Returns `Error::InvalidHeaderValue` if the name is empty or contains
+/// separators, whitespace or control characters.
+pub(crate) fn validate_header_name(name: &str) -> Result<()> {
+    if !is_token(name) {
+        return Err(Error::InvalidHeaderValue {
+            header: name.to_string(),
+            reason: "invalid header name".to_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Headers that [`HandshakeResponse::write`] emits itself and that may not
+/// appear in [`HandshakeResponse::headers`].
+const RESPONSE_MANAGED_HEADERS: &[&str] = &[
+    "upgrade",
+    "connection",
+    "sec-websocket-accept",
+    "sec-websocket-protocol",
+    "sec-websocket-extensions",
+];
+
 /// Computes the Sec-WebSocket-Accept value from the client's Sec-WebSocket-Key.
 ///
 /// The accept key is calculated as: Base64(SHA-1(key + GUID))
@@ -91,6 +186,20 @@ pub fn compute_accept_key(key: &str) -> String {
     BASE64.encode(hash)
 }
 
+/// Generates a random `Sec-WebSocket-Key` for a client handshake.
+///
+/// The key is 16 bytes from the operating system CSPRNG, Base64 encoded
+/// (RFC 6455 Section 4.1).
+///
+/// # Errors
+/// Returns `Error::Io` if the system random number generator fails.
+pub fn generate_key() -> Result<String> {
+    let mut nonce = [0u8; 16];
+    getrandom::getrandom(&mut nonce)
+        .map_err(|e| Error::Io(format!("Failed to generate Sec-WebSocket-Key: {e}")))?;
+    Ok(BASE64.encode(nonce))
+}
+
 /// Validate the Origin header against a list of allowed origins.
 ///
 /// # Arguments
@@ -136,6 +245,8 @@ pub struct HandshakeRequest {
     pub protocols: Vec<String>,
     /// The Sec-WebSocket-Extensions values (optional).
     pub extensions: Vec<String>,
+    /// Every request header, in order, including repeated ones.
+    pub headers: HeaderMap,
 }
 
 impl HandshakeRequest {
@@ -168,21 +279,35 @@ impl HandshakeRequest {
             return Err(Error::InvalidHandshake("Invalid request line".into()));
         }
 
-        if parts[0] != "GET" {
+        Self::from_parts(parts[0], parts[1], parts[2], lines)
+    }
+
+    /// Build a request from an already split request line and its header
+    /// lines, applying the same checks as [`Self::parse`].
+    pub(crate) fn from_parts<'a, I>(
+        method: &str,
+        path: &str,
+        http_version: &str,
+        lines: I,
+    ) -> Result<Self>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        if method != "GET" {
             return Err(Error::InvalidHandshake(format!(
                 "Expected GET method, got {}",
-                parts[0]
+                method
             )));
         }
 
-        if parts[2] != "HTTP/1.1" {
+        if http_version != "HTTP/1.1" {
             return Err(Error::InvalidHandshake(format!(
                 "Expected HTTP/1.1, got {}",
-                parts[2]
+                http_version
             )));
         }
 
-        let path = parts[1].to_string();
+        let path = path.to_string();
 
         // Parse headers with duplicate detection for security-critical headers
         let security_headers = [
@@ -220,13 +345,13 @@ impl HandshakeRequest {
         let host = headers
             .get("host")
             .ok_or_else(|| Error::InvalidHandshake("Missing Host header".into()))?
-            .clone();
+            .to_string();
 
         // Extract Sec-WebSocket-Key
         let key = headers
             .get("sec-websocket-key")
             .ok_or_else(|| Error::InvalidHandshake("Missing Sec-WebSocket-Key header".into()))?
-            .clone();
+            .to_string();
 
         // Extract Sec-WebSocket-Version
         let version_str = headers.get("sec-websocket-version").ok_or_else(|| {
@@ -237,19 +362,19 @@ impl HandshakeRequest {
             .map_err(|_| Error::InvalidHandshake(format!("Invalid version: {}", version_str)))?;
 
         // Extract optional Origin
-        let origin = headers.get("origin").cloned();
+        let origin = headers.get("origin").map(str::to_string);
 
-        // Extract optional Sec-WebSocket-Protocol (comma-separated)
+        // Extract optional Sec-WebSocket-Protocol (comma-separated, may repeat)
         let protocols = headers
-            .get("sec-websocket-protocol")
-            .map(|p| p.split(',').map(|s| s.trim().to_string()).collect())
-            .unwrap_or_default();
+            .get_all("sec-websocket-protocol")
+            .flat_map(|p| p.split(','))
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect();
 
         // Extract optional Sec-WebSocket-Extensions (comma-separated)
-        let extensions = headers
-            .get("sec-websocket-extensions")
-            .map(|e| e.split(',').map(|s| s.trim().to_string()).collect())
-            .unwrap_or_default();
+        let extensions = split_extensions(&headers);
 
         Ok(Self {
             path,
@@ -259,25 +384,86 @@ impl HandshakeRequest {
             origin,
             protocols,
             extensions,
+            headers,
         })
     }
 
+    /// The first value of the request header `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    /// The raw query string of the request path, without the `?`.
+    pub fn query(&self) -> Option<&str> {
+        self.path.split_once('?').map(|(_, query)| query)
+    }
+
+    /// The query parameters in order, with `+` and percent-escapes decoded.
+    ///
+    /// A parameter without `=` has an empty value. Repeated names are kept.
+    pub fn query_params(&self) -> Vec<(String, String)> {
+        self.query()
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| {
+                let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+                (decode_query_component(name), decode_query_component(value))
+            })
+            .collect()
+    }
+
+    /// The decoded value of the first query parameter called `name`.
+    pub fn query_param(&self, name: &str) -> Option<String> {
+        self.query_params()
+            .into_iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value)
+    }
+
+    /// The cookies from every `Cookie` header, as `(name, value)` pairs.
+    ///
+    /// Values are returned as sent, except that surrounding double quotes
+    /// are removed (RFC 6265 Section 4.2.1).
+    pub fn cookies(&self) -> Vec<(&str, &str)> {
+        self.headers
+            .get_all("cookie")
+            .flat_map(|header| header.split(';'))
+            .filter_map(|pair| {
+                let (name, value) = pair.split_once('=')?;
+                let value = value.trim();
+                let value = value
+                    .strip_prefix('"')
+                    .and_then(|v| v.strip_suffix('"'))
+                    .unwrap_or(value);
+                Some((name.trim(), value))
+            })
+            .collect()
+    }
+
+    /// The value of the first cookie called `name`.
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies()
+            .into_iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| value)
+    }
+
     /// Validate the handshake request according to RFC 6455.
     ///
     /// # Errors
     ///
-    /// Returns [`Error::InvalidHandshake`] if:
-    /// - The WebSocket version is not 13.
+    /// Returns [`Error::UnsupportedVersion`] if the WebSocket version is not
+    /// 13, and [`Error::InvalidHandshake`] if:
     /// - The `Sec-WebSocket-Key` is not valid Base64.
     /// - The decoded `Sec-WebSocket-Key` is not exactly 16 bytes.
     /// - The `Host` header is empty.
     pub fn validate(&self) -> Result<()> {
         // Version must be 13
         if self.version != 13 {
-            return Err(Error::InvalidHandshake(format!(
-                "Unsupported WebSocket version: {} (expected 13)",
-                self.version
-            )));
+            return Err(Error::UnsupportedVersion {
+                version: self.version,
+            });
         }
 
         // Key must be 16 bytes when decoded (24 chars base64 with padding)
@@ -326,6 +512,36 @@ impl HandshakeRequest {
         Ok(())
     }
 
+    /// Choose the subprotocol to answer this request with.
+    ///
+    /// Uses `config.subprotocol_selector` if set, otherwise the first entry
+    /// of `config.subprotocols` (the server's preference order) that the
+    /// client offered. A protocol the client did not offer is never returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedSubprotocol`] if nothing matches and
+    /// `config.require_subprotocol` is set.
+    pub fn select_subprotocol(&self, config: &crate::config::Config) -> Result<Option<String>> {
+        let selected = match config.subprotocol_selector.as_ref() {
+            Some(selector) => selector
+                .select(&self.protocols)
+                .filter(|p| self.protocols.contains(p)),
+            None => config
+                .subprotocols
+                .iter()
+                .find(|p| self.protocols.contains(p))
+                .cloned(),
+        };
+
+        if selected.is_none() && config.require_subprotocol {
+            return Err(Error::UnsupportedSubprotocol {
+                offered: self.protocols.clone(),
+            });
+        }
+        Ok(selected)
+    }
+
     /// Parse a handshake request with size limit.
     ///
     /// # Errors
@@ -341,6 +557,23 @@ impl HandshakeRequest {
         }
         Self::parse(data)
     }
+
+    /// Parse a request from the start of a buffer that may hold more data.
+    ///
+    /// Returns `Ok(None)` while the head is still incomplete, or the parsed
+    /// request together with the number of bytes it consumed. Bytes after
+    /// that offset (for example frames pipelined behind the request) are not
+    /// part of the handshake and must be handed to the connection.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::parse`], once the head is complete.
+    pub fn parse_partial(data: &[u8]) -> Result<Option<(Self, usize)>> {
+        match find_head_end(data) {
+            Some(end) => Ok(Some((Self::parse(&data[..end])?, end))),
+            None => Ok(None),
+        }
+    }
 }
 
 /// WebSocket handshake response from server.
@@ -352,43 +585,162 @@ pub struct HandshakeResponse {
     pub protocol: Option<String>,
     /// The negotiated Sec-WebSocket-Extensions (optional).
     pub extensions: Vec<String>,
+    /// Additional headers such as `Set-Cookie` or `Server`, written after
+    /// the handshake headers. When parsing, every header other than
+    /// `Upgrade`, `Connection` and the `Sec-WebSocket-*` ones above.
+    pub headers: HeaderMap,
 }
 
 impl HandshakeResponse {
     /// Create a handshake response from a validated request.
+    ///
+    /// No subprotocol is selected (see [`HandshakeRequest::select_subprotocol`])
+    /// and no extensions are accepted (see [`Self::from_request_with_extensions`]).
     pub fn from_request(req: &HandshakeRequest) -> Self {
         Self {
             accept: compute_accept_key(&req.key),
-            protocol: req.protocols.first().cloned(),
-            extensions: Vec::new(), // No extensions supported yet
+            protocol: None,
+            extensions: Vec::new(),
+            headers: HeaderMap::new(),
         }
     }
 
+    /// Add a header to the response, keeping any existing values.
+    ///
+    /// The header is validated when the response is written.
+    #[must_use]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Create a handshake response, negotiating the request's extension
+    /// offers against `registry`.
+    ///
+    /// Offers are handled as described in [`ExtensionRegistry::negotiate`],
+    /// including fallback offers of the same extension. The accepted
+    /// extensions go into [`Self::extensions`], and the returned registry is
+    /// configured for `Connection::with_extensions`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidExtension`] if the request's
+    /// `Sec-WebSocket-Extensions` header is malformed.
+    pub fn from_request_with_extensions(
+        req: &HandshakeRequest,
+        mut registry: ExtensionRegistry,
+    ) -> Result<(Self, ExtensionRegistry)> {
+        let mut response = Self::from_request(req);
+        response.negotiate_extensions(req, &mut registry)?;
+        Ok((response, registry))
+    }
+
+    /// Negotiate the request's extension offers against `registry` and
+    /// replace [`Self::extensions`] with the accepted ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidExtension`] if the request's
+    /// `Sec-WebSocket-Extensions` header is malformed.
+    pub fn negotiate_extensions(
+        &mut self,
+        req: &HandshakeRequest,
+        registry: &mut ExtensionRegistry,
+    ) -> Result<()> {
+        let offers = req
+            .extensions
+            .iter()
+            .map(|e| ExtensionOffer::parse(e))
+            .collect::<Result<Vec<_>>>()?;
+        self.extensions = registry
+            .negotiate(&offers)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        Ok(())
+    }
+
     /// Write the HTTP response to a buffer.
     ///
     /// # Errors
-    /// Returns `Error::InvalidHeaderValue` if protocol or extensions contain CR/LF.
+    /// Returns `Error::InvalidHeaderValue` if any value contains CR/LF, if an
+    /// additional header has an invalid name, or if it would override one of
+    /// the handshake headers (`Upgrade`, `Connection`, `Sec-WebSocket-*`).
     pub fn write(&self, buf: &mut Vec<u8>) -> Result<()> {
-        validate_header_value("Sec-WebSocket-Accept", &self.accept)?;
+        self.validate_headers()?;
         buf.extend_from_slice(b"HTTP/1.1 101 Switching Protocols\r\n");
         buf.extend_from_slice(b"Upgrade: websocket\r\n");
         buf.extend_from_slice(b"Connection: Upgrade\r\n");
         buf.extend_from_slice(format!("Sec-WebSocket-Accept: {}\r\n", self.accept).as_bytes());
 
         if let Some(ref proto) = self.protocol {
-            validate_header_value("Sec-WebSocket-Protocol", proto)?;
             buf.extend_from_slice(format!("Sec-WebSocket-Protocol: {}\r\n", proto).as_bytes());
         }
 
         for ext in &self.extensions {
-            validate_header_value("Sec-WebSocket-Extensions", ext)?;
             buf.extend_from_slice(format!("Sec-WebSocket-Extensions: {}\r\n", ext).as_bytes());
         }
 
+        for (name, value) in self.headers.iter() {
+            buf.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+
         buf.extend_from_slice(b"\r\n");
         Ok(())
     }
 
+    /// Check every header value before the response is serialized.
+    pub(crate) fn validate_headers(&self) -> Result<()> {
+        validate_header_value("Sec-WebSocket-Accept", &self.accept)?;
+        if let Some(ref proto) = self.protocol {
+            validate_header_value("Sec-WebSocket-Protocol", proto)?;
+        }
+        for ext in &self.extensions {
+            validate_header_value("Sec-WebSocket-Extensions", ext)?;
+        }
+        for (name, value) in self.headers.iter() {
+            validate_header_name(name)?;
+            if RESPONSE_MANAGED_HEADERS
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case(name))
+            {
+                return Err(Error::InvalidHeaderValue {
+                    header: name.to_string(),
+                    reason: "set by the handshake".to_string(),
+                });
+            }
+            validate_header_value(name, value)?;
+        }
+        Ok(())
+    }
+
+    /// Check the response against the client's request (RFC 6455 Section 4.1).
+    ///
+    /// `key` is the `Sec-WebSocket-Key` that was sent and `protocols` the
+    /// offered subprotocols.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidHandshake`] if `Sec-WebSocket-Accept` does not
+    /// match `key`, or the server selected a subprotocol that was not offered.
+    pub fn verify(&self, key: &str, protocols: &[String]) -> Result<()> {
+        if self.accept != compute_accept_key(key) {
+            return Err(Error::InvalidHandshake(
+                "Sec-WebSocket-Accept does not match Sec-WebSocket-Key".into(),
+            ));
+        }
+
+        if let Some(ref protocol) = self.protocol
+            && !protocols.contains(protocol)
+        {
+            return Err(Error::InvalidHandshake(format!(
+                "Server selected subprotocol '{}' that was not offered",
+                protocol
+            )));
+        }
+        Ok(())
+    }
+
     /// Parse a WebSocket handshake response from raw HTTP data.
     ///
     /// # Errors
@@ -422,6 +774,15 @@ impl HandshakeResponse {
             )));
         }
 
+        Self::from_header_lines(lines)
+    }
+
+    /// Build a `101` response from its header lines, applying the same
+    /// checks as [`Self::parse`] after the status line.
+    pub(crate) fn from_header_lines<'a, I>(lines: I) -> Result<Self>
+    where
+        I: Iterator<Item = &'a str>,
+    {
         let headers = parse_headers(lines, None)?;
 
         // Validate Upgrade header
@@ -450,23 +811,46 @@ impl HandshakeResponse {
         let accept = headers
             .get("sec-websocket-accept")
             .ok_or_else(|| Error::InvalidHandshake("Missing Sec-WebSocket-Accept header".into()))?
-            .clone();
+            .to_string();
 
         // Extract optional protocol
-        let protocol = headers.get("sec-websocket-protocol").cloned();
+        let protocol = headers.get("sec-websocket-protocol").map(str::to_string);
 
         // Extract optional extensions
-        let extensions = headers
-            .get("sec-websocket-extensions")
-            .map(|e| e.split(',').map(|s| s.trim().to_string()).collect())
-            .unwrap_or_default();
+        let extensions = split_extensions(&headers);
+
+        let headers = headers
+            .iter()
+            .filter(|(name, _)| {
+                !RESPONSE_MANAGED_HEADERS
+                    .iter()
+                    .any(|h| h.eq_ignore_ascii_case(name))
+            })
+            .collect();
 
         Ok(Self {
             accept,
             protocol,
             extensions,
+            headers,
         })
     }
+
+    /// Parse a response from the start of a buffer that may hold more data.
+    ///
+    /// Returns `Ok(None)` while the head is still incomplete, or the parsed
+    /// response together with the number of bytes it consumed. A server may
+    /// send its first frames in the same segment as the `101` response.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::parse`], once the head is complete.
+    pub fn parse_partial(data: &[u8]) -> Result<Option<(Self, usize)>> {
+        match find_head_end(data) {
+            Some(end) => Ok(Some((Self::parse(&data[..end])?, end))),
+            None => Ok(None),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -483,6 +867,13 @@ mod tests {
         assert_eq!(compute_accept_key(key), expected);
     }
 
+    #[test]
+    fn test_generate_key() {
+        let key = generate_key().unwrap();
+        assert_eq!(BASE64.decode(&key).unwrap().len(), 16);
+        assert_ne!(key, generate_key().unwrap());
+    }
+
     // Test 2: Full client request parsing
     #[test]
     fn test_parse_valid_request() {
@@ -552,7 +943,8 @@ mod tests {
         let result = req.validate();
         assert!(result.is_err());
         let err = result.unwrap_err();
-        assert!(matches!(err, Error::InvalidHandshake(msg) if msg.contains("version")));
+        assert!(matches!(err, Error::UnsupportedVersion { version: 8 }));
+        assert!(err.to_string().contains("version"));
     }
 
     // Test 6: Validation rules
@@ -567,6 +959,7 @@ mod tests {
             origin: None,
             protocols: vec![],
             extensions: vec![],
+            headers: HeaderMap::new(),
         };
         assert!(valid_req.validate().is_ok());
 
@@ -582,7 +975,10 @@ mod tests {
             version: 12,
             ..valid_req.clone()
         };
-        assert!(invalid_version_req.validate().is_err());
+        assert!(matches!(
+            invalid_version_req.validate(),
+            Err(Error::UnsupportedVersion { version: 12 })
+        ));
     }
 
     // Test 7: Generate response from request
@@ -596,11 +992,75 @@ mod tests {
             origin: None,
             protocols: vec!["chat".to_string(), "superchat".to_string()],
             extensions: vec![],
+            headers: HeaderMap::new(),
         };
 
         let resp = HandshakeResponse::from_request(&req);
         assert_eq!(resp.accept, "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
-        assert_eq!(resp.protocol, Some("chat".to_string()));
+        assert_eq!(resp.protocol, None);
+    }
+
+    fn request_with_protocols(protocols: &[&str]) -> HandshakeRequest {
+        HandshakeRequest {
+            path: "/chat".to_string(),
+            host: "example.com".to_string(),
+            key: "dGhlIHNhbXBsZSBub25jZQ==".to_string(),
+            version: 13,
+            origin: None,
+            protocols: protocols.iter().map(|p| p.to_string()).collect(),
+            extensions: vec![],
+            headers: HeaderMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_select_subprotocol_uses_server_preference() {
+        let req = request_with_protocols(&["chat.v1", "chat.v2"]);
+        let config = Config::server().with_subprotocols(["chat.v2", "chat.v1"]);
+        assert_eq!(
+            req.select_subprotocol(&config).unwrap(),
+            Some("chat.v2".to_string())
+        );
+
+        let config = Config::server().with_subprotocols(["mqtt"]);
+        assert_eq!(req.select_subprotocol(&config).unwrap(), None);
+
+        // Nothing is selected unless the server lists it.
+        assert_eq!(req.select_subprotocol(&Config::server()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_select_subprotocol_with_selector() {
+        let req = request_with_protocols(&["chat.v1", "chat.v3"]);
+        let config = Config::server()
+            .with_subprotocols(["chat.v1"])
+            .with_subprotocol_selector(|offered| offered.iter().max().cloned());
+        assert_eq!(
+            req.select_subprotocol(&config).unwrap(),
+            Some("chat.v3".to_string())
+        );
+
+        // A selector cannot pick something the client did not offer.
+        let config =
+            Config::server().with_subprotocol_selector(|_| Some("not-offered".to_string()));
+        assert_eq!(req.select_subprotocol(&config).unwrap(), None);
+    }
+
+    #[test]
+    fn test_select_subprotocol_required() {
+        let req = request_with_protocols(&["chat.v1"]);
+        let config = Config::server()
+            .with_subprotocols(["mqtt"])
+            .with_require_subprotocol(true);
+        assert_eq!(
+            req.select_subprotocol(&config),
+            Err(Error::UnsupportedSubprotocol {
+                offered: vec!["chat.v1".to_string()]
+            })
+        );
+
+        let req = request_with_protocols(&[]);
+        assert!(req.select_subprotocol(&config).is_err());
     }
 
     // Test 8: Serialize response to bytes
@@ -610,6 +1070,7 @@ mod tests {
             accept: "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string(),
             protocol: Some("chat".to_string()),
             extensions: vec![],
+            headers: HeaderMap::new(),
         };
 
         let mut buf = Vec::new();
@@ -711,6 +1172,7 @@ mod tests {
             origin: Some("https://evil.com".to_string()),
             protocols: vec![],
             extensions: vec![],
+            headers: HeaderMap::new(),
         };
         let config = Config::server().with_allowed_origins(vec!["https://example.com".to_string()]);
 
@@ -863,6 +1325,7 @@ Sec-WebSocket-Version: 13\r\n\r\n";
             accept: "ok\r\nX-Injected: evil".to_string(),
             protocol: None,
             extensions: vec![],
+            headers: HeaderMap::new(),
         };
         let mut buf = Vec::new();
         let result = response.write(&mut buf);
@@ -875,6 +1338,7 @@ Sec-WebSocket-Version: 13\r\n\r\n";
             accept: "dGhlIHNhbXBsZSBub25jZQ==".to_string(),
             protocol: Some("chat\r\nX-Injected: evil".to_string()),
             extensions: vec![],
+            headers: HeaderMap::new(),
         };
         let mut buf = Vec::new();
         let result = response.write(&mut buf);
@@ -887,6 +1351,7 @@ Sec-WebSocket-Version: 13\r\n\r\n";
             accept: "dGhlIHNhbXBsZSBub25jZQ==".to_string(),
             protocol: None,
             extensions: vec!["permessage-deflate\nX-Evil: bad".to_string()],
+            headers: HeaderMap::new(),
         };
         let mut buf = Vec::new();
         let result = response.write(&mut buf);
@@ -899,10 +1364,305 @@ Sec-WebSocket-Version: 13\r\n\r\n";
             accept: "dGhlIHNhbXBsZSBub25jZQ==".to_string(),
             protocol: Some("chat".to_string()),
             extensions: vec!["permessage-deflate".to_string()],
+            headers: HeaderMap::new(),
         };
         let mut buf = Vec::new();
         let result = response.write(&mut buf);
         assert!(result.is_ok());
         assert!(!buf.is_empty());
     }
+
+    #[test]
+    fn test_response_write_additional_headers() {
+        let response = HandshakeResponse {
+            accept: "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string(),
+            protocol: None,
+            extensions: vec![],
+            headers: HeaderMap::new(),
+        }
+        .with_header("Set-Cookie", "a=1")
+        .with_header("Set-Cookie", "b=2");
+        let mut buf = Vec::new();
+        response.write(&mut buf).unwrap();
+
+        let parsed = HandshakeResponse::parse(&buf).unwrap();
+        assert_eq!(parsed, response);
+        assert_eq!(
+            parsed.headers.get_all("set-cookie").collect::<Vec<_>>(),
+            ["a=1", "b=2"]
+        );
+    }
+
+    #[test]
+    fn test_response_rejects_invalid_additional_headers() {
+        let base = HandshakeResponse {
+            accept: "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_string(),
+            protocol: None,
+            extensions: vec![],
+            headers: HeaderMap::new(),
+        };
+        for (name, value) in [
+            ("Set-Cookie", "a=1\r\nX-Injected: evil"),
+            ("Bad Name", "x"),
+            ("sec-websocket-accept", "forged"),
+            ("Connection", "close"),
+        ] {
+            let mut buf = Vec::new();
+            let result = base.clone().with_header(name, value).write(&mut buf);
+            assert!(
+                matches!(result, Err(Error::InvalidHeaderValue { .. })),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_head_end() {
+        assert_eq!(
+            find_head_end(b"GET / HTTP/1.1\r\nHost: x\r\n\r\n"),
+            Some(27)
+        );
+        assert_eq!(find_head_end(b"GET / HTTP/1.1\r\nHost: x\r\n\r"), None);
+        assert_eq!(find_head_end(b""), None);
+    }
+
+    #[test]
+    fn test_request_parse_partial() {
+        let request = b"GET /chat HTTP/1.1\r\n\
+            Host: server.example.com\r\n\
+            Upgrade: websocket\r\n\
+            Connection: Upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\r\n";
+
+        for split in [0, 10, request.len() - 1] {
+            assert!(
+                HandshakeRequest::parse_partial(&request[..split])
+                    .unwrap()
+                    .is_none()
+            );
+        }
+
+        let mut data = request.to_vec();
+        data.extend_from_slice(&[0x81, 0x80, 1, 2, 3, 4]);
+        let (req, consumed) = HandshakeRequest::parse_partial(&data).unwrap().unwrap();
+        assert_eq!(req.path, "/chat");
+        assert_eq!(consumed, request.len());
+        assert_eq!(&data[consumed..], &[0x81, 0x80, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_response_parse_partial() {
+        let response = b"HTTP/1.1 101 Switching Protocols\r\n\
+            Upgrade: websocket\r\n\
+            Connection: Upgrade\r\n\
+            Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n";
+
+        assert!(
+            HandshakeResponse::parse_partial(&response[..20])
+                .unwrap()
+                .is_none()
+        );
+
+        let mut data = response.to_vec();
+        data.extend_from_slice(b"\x81\x02hi");
+        let (resp, consumed) = HandshakeResponse::parse_partial(&data).unwrap().unwrap();
+        assert_eq!(resp.accept, "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert_eq!(&data[consumed..], b"\x81\x02hi");
+    }
+
+    #[test]
+    fn test_parse_partial_reports_invalid_head() {
+        let result = HandshakeRequest::parse_partial(b"POST / HTTP/1.1\r\nHost: x\r\n\r\n");
+        assert!(matches!(result, Err(Error::InvalidHandshake(_))));
+    }
+
+    /// Accepts only a `mode` parameter, echoing it back.
+    struct ModeExtension;
+
+    impl crate::extensions::Extension for ModeExtension {
+        fn name(&self) -> &str {
+            "x-mode"
+        }
+
+        fn negotiate(
+            &mut self,
+            params: &[crate::extensions::ExtensionParam],
+        ) -> Result<Vec<crate::extensions::ExtensionParam>> {
+            if params.iter().all(|p| p.name == "mode") {
+                Ok(params.to_vec())
+            } else {
+                Err(Error::InvalidExtension("unsupported parameter".into()))
+            }
+        }
+
+        fn encode(&mut self, _frame: &mut crate::protocol::Frame) -> Result<()> {
+            Ok(())
+        }
+
+        fn decode(&mut self, _frame: &mut crate::protocol::Frame) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    fn request_with_extensions(header: &str) -> HandshakeRequest {
+        let request = format!(
+            "GET /chat HTTP/1.1\r\n\
+             Host: server.example.com\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Version: 13\r\n\
+             Sec-WebSocket-Extensions: {}\r\n\r\n",
+            header
+        );
+        HandshakeRequest::parse(request.as_bytes()).unwrap()
+    }
+
+    fn mode_registry() -> ExtensionRegistry {
+        let mut registry = ExtensionRegistry::new();
+        registry.add(Box::new(ModeExtension)).unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_request_extensions_keep_quoted_commas() {
+        let req = request_with_extensions(r#"x-mode; mode="a,b", other"#);
+        assert_eq!(
+            req.extensions,
+            vec![r#"x-mode; mode="a,b""#.to_string(), "other".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_response_negotiates_extensions() {
+        let req = request_with_extensions("x-unknown, x-mode; mode=fast");
+
+        let (resp, registry) =
+            HandshakeResponse::from_request_with_extensions(&req, mode_registry()).unwrap();
+        assert_eq!(resp.extensions, vec!["x-mode; mode=fast".to_string()]);
+        assert_eq!(registry.negotiated_count(), 1);
+    }
+
+    #[test]
+    fn test_response_uses_fallback_offer() {
+        let req = request_with_extensions(r#"x-mode; level=9, x-mode; mode="a b", x-mode"#);
+
+        let (resp, registry) =
+            HandshakeResponse::from_request_with_extensions(&req, mode_registry()).unwrap();
+        assert_eq!(resp.extensions, vec![r#"x-mode; mode="a b""#.to_string()]);
+        assert_eq!(registry.negotiated_count(), 1);
+    }
+
+    #[test]
+    fn test_response_without_acceptable_offer() {
+        let req = request_with_extensions("x-mode; level=9");
+
+        let (resp, registry) =
+            HandshakeResponse::from_request_with_extensions(&req, mode_registry()).unwrap();
+        assert!(resp.extensions.is_empty());
+        assert_eq!(registry.negotiated_count(), 0);
+
+        let mut buf = Vec::new();
+        resp.write(&mut buf).unwrap();
+        assert!(
+            !String::from_utf8(buf)
+                .unwrap()
+                .contains("Sec-WebSocket-Extensions")
+        );
+    }
+
+    #[test]
+    fn test_response_rejects_malformed_extension_header() {
+        let req = request_with_extensions(r#"x-mode; mode="unterminated"#);
+        assert!(matches!(
+            HandshakeResponse::from_request_with_extensions(&req, mode_registry()),
+            Err(Error::InvalidExtension(_))
+        ));
+    }
+
+    fn parse_request_with(path: &str, extra_headers: &str) -> HandshakeRequest {
+        let request = format!(
+            "GET {} HTTP/1.1\r\n\
+             Host: server.example.com\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Version: 13\r\n\
+             {}\r\n",
+            path, extra_headers
+        );
+        HandshakeRequest::parse(request.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_request_keeps_all_headers() {
+        let req = parse_request_with(
+            "/chat",
+            "Authorization: Bearer t\r\n\
+             X-Forwarded-For: 203.0.113.7\r\n\
+             x-forwarded-for: 10.0.0.1\r\n",
+        );
+
+        assert_eq!(req.header("authorization"), Some("Bearer t"));
+        assert_eq!(req.header("Host"), Some("server.example.com"));
+        assert_eq!(
+            req.headers.get_all("X-Forwarded-For").collect::<Vec<_>>(),
+            ["203.0.113.7", "10.0.0.1"]
+        );
+        assert_eq!(req.headers.len(), 8);
+    }
+
+    #[test]
+    fn test_request_combines_repeated_protocol_and_extension_headers() {
+        let req = parse_request_with(
+            "/chat",
+            "Sec-WebSocket-Protocol: chat\r\n\
+             Sec-WebSocket-Protocol: superchat, v2\r\n\
+             Sec-WebSocket-Extensions: x-a\r\n\
+             Sec-WebSocket-Extensions: x-b; p=1\r\n",
+        );
+
+        assert_eq!(req.protocols, ["chat", "superchat", "v2"]);
+        assert_eq!(req.extensions, ["x-a", "x-b; p=1"]);
+    }
+
+    #[test]
+    fn test_request_query_params() {
+        let req = parse_request_with(
+            "/ws?token=a%2Bb%3D&name=J+Doe&flag&token=second&bad=%zz",
+            "",
+        );
+
+        assert_eq!(
+            req.query(),
+            Some("token=a%2Bb%3D&name=J+Doe&flag&token=second&bad=%zz")
+        );
+        assert_eq!(req.query_param("token").as_deref(), Some("a+b="));
+        assert_eq!(req.query_param("name").as_deref(), Some("J Doe"));
+        assert_eq!(req.query_param("flag").as_deref(), Some(""));
+        assert_eq!(req.query_param("bad").as_deref(), Some("%zz"));
+        assert_eq!(req.query_param("missing"), None);
+        assert_eq!(req.query_params().len(), 5);
+
+        let req = parse_request_with("/ws", "");
+        assert_eq!(req.query(), None);
+        assert!(req.query_params().is_empty());
+    }
+
+    #[test]
+    fn test_request_cookies() {
+        let req = parse_request_with(
+            "/",
+            "Cookie: session=abc123; theme=\"dark\"\r\n\
+             Cookie: lang=en; invalid\r\n",
+        );
+
+        assert_eq!(
+            req.cookies(),
+            [("session", "abc123"), ("theme", "dark"), ("lang", "en")]
+        );
+        assert_eq!(req.cookie("theme"), Some("dark"));
+        assert_eq!(req.cookie("missing"), None);
+    }
 }
@@ -0,0 +1,123 @@
+//! Ordered, case-insensitive HTTP header multimap.
+
+use std::fmt;
+
+/// HTTP headers in the order they were received.
+///
+/// Names are compared case-insensitively but kept as sent. Repeated headers
+/// keep every value, so `Cookie` or `X-Forwarded-For` sent on several lines
+/// are all available through [`HeaderMap::get_all`].
+///
+/// ## Example
+///
+/// ```rust
+/// use rsws::protocol::HeaderMap;
+///
+/// let mut headers = HeaderMap::new();
+/// headers.append("X-Forwarded-For", "203.0.113.7");
+/// headers.append("x-forwarded-for", "10.0.0.1");
+///
+/// assert_eq!(headers.get("X-FORWARDED-FOR"), Some("203.0.113.7"));
+/// assert_eq!(headers.get_all("x-forwarded-for").count(), 2);
+/// ```
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    entries: Vec<(String, String)>,
+}
+
+impl HeaderMap {
+    /// Create an empty header map.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a header, keeping any existing values with the same name.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    /// The first value of the header `name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// All values of the header `name`, in the order they were received.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.entries
+            .iter()
+            .filter(move |(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Check if the header `name` is present.
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// All headers as `(name, value)` pairs, in the order they were received.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// Number of header lines.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if there are no headers.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl fmt::Debug for HeaderMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<N: Into<String>, V: Into<String>> FromIterator<(N, V)> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = (N, V)>>(iter: I) -> Self {
+        Self {
+            entries: iter
+                .into_iter()
+                .map(|(n, v)| (n.into(), v.into()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_is_case_insensitive() {
+        let headers: HeaderMap = [("User-Agent", "rsws-test")].into_iter().collect();
+        assert_eq!(headers.get("user-agent"), Some("rsws-test"));
+        assert!(headers.contains("USER-AGENT"));
+        assert!(!headers.contains("cookie"));
+    }
+
+    #[test]
+    fn test_repeated_headers_are_kept_in_order() {
+        let mut headers = HeaderMap::new();
+        headers.append("Cookie", "a=1");
+        headers.append("Host", "example.com");
+        headers.append("cookie", "b=2");
+
+        assert_eq!(headers.len(), 3);
+        assert_eq!(headers.get("Cookie"), Some("a=1"));
+        assert_eq!(
+            headers.get_all("COOKIE").collect::<Vec<_>>(),
+            ["a=1", "b=2"]
+        );
+        assert_eq!(
+            headers.iter().map(|(n, _)| n).collect::<Vec<_>>(),
+            ["Cookie", "Host", "cookie"]
+        );
+    }
+}
@@ -0,0 +1,431 @@
+//! Conversions between the handshake types and the [`http`] crate
+//! (feature = "http").
+//!
+//! These let rsws validate and answer upgrade requests that were already
+//! parsed by another HTTP stack (hyper, axum, actix), and build client
+//! requests for one. The same rules as [`HandshakeRequest::parse`] and
+//! [`HandshakeResponse::parse`] apply; nothing is re-checked by hand.
+//!
+//! ## Example
+//!
+//! ```rust
+//! use rsws::{Config, HandshakeRequest, HandshakeResponse};
+//!
+//! let req = http::Request::get("/chat")
+//!     .header("Host", "example.com")
+//!     .header("Upgrade", "websocket")
+//!     .header("Connection", "Upgrade")
+//!     .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+//!     .header("Sec-WebSocket-Version", "13")
+//!     .body(())
+//!     .unwrap();
+//!
+//! let request = HandshakeRequest::try_from(&req).unwrap();
+//! request.validate_with_config(&Config::server()).unwrap();
+//!
+//! let response = HandshakeResponse::from_request(&request)
+//!     .into_http_response()
+//!     .unwrap();
+//! assert_eq!(response.status(), http::StatusCode::SWITCHING_PROTOCOLS);
+//! ```
+
+use ::http::header::{self, HeaderName, HeaderValue};
+use ::http::{Method, Request, Response, StatusCode, Uri, Version};
+
+use crate::error::{Error, Result};
+use crate::protocol::handshake::{is_token, validate_header_name, validate_header_value};
+use crate::protocol::{HandshakeRequest, HandshakeResponse, HeaderMap, generate_key};
+
+/// Headers that [`HandshakeRequest::to_http_request`] builds from the
+/// request's fields rather than copying from [`HandshakeRequest::headers`].
+const REQUEST_MANAGED_HEADERS: &[&str] = &[
+    "host",
+    "upgrade",
+    "connection",
+    "sec-websocket-key",
+    "sec-websocket-version",
+    "origin",
+    "sec-websocket-protocol",
+    "sec-websocket-extensions",
+];
+
+impl<B> TryFrom<&Request<B>> for HandshakeRequest {
+    type Error = Error;
+
+    /// Convert an upgrade request received by another HTTP stack.
+    ///
+    /// Applies the checks of [`HandshakeRequest::parse`]; call
+    /// [`HandshakeRequest::validate`] or
+    /// [`HandshakeRequest::validate_with_config`] afterwards, as with a
+    /// parsed request.
+    fn try_from(req: &Request<B>) -> Result<Self> {
+        request_from(req.method(), req.uri(), req.version(), req.headers())
+    }
+}
+
+impl TryFrom<&::http::request::Parts> for HandshakeRequest {
+    type Error = Error;
+
+    /// Same as the conversion from a whole [`Request`].
+    fn try_from(parts: &::http::request::Parts) -> Result<Self> {
+        request_from(&parts.method, &parts.uri, parts.version, &parts.headers)
+    }
+}
+
+fn request_from(
+    method: &Method,
+    uri: &Uri,
+    version: Version,
+    headers: &::http::HeaderMap,
+) -> Result<HandshakeRequest> {
+    let lines = header_lines(headers)?;
+    let path = uri.path_and_query().map_or("/", |pq| pq.as_str());
+    HandshakeRequest::from_parts(
+        method.as_str(),
+        path,
+        &format!("{:?}", version),
+        lines.iter().map(String::as_str),
+    )
+}
+
+impl<B> TryFrom<&Response<B>> for HandshakeResponse {
+    type Error = Error;
+
+    /// Convert the server's answer to a client upgrade request.
+    ///
+    /// Applies the checks of [`HandshakeResponse::parse`]; call
+    /// [`HandshakeResponse::verify`] afterwards with the key that was sent.
+    fn try_from(resp: &Response<B>) -> Result<Self> {
+        if resp.version() != Version::HTTP_11 || resp.status() != StatusCode::SWITCHING_PROTOCOLS {
+            return Err(Error::InvalidHandshake(format!(
+                "Expected 101 status, got: {:?} {}",
+                resp.version(),
+                resp.status()
+            )));
+        }
+        let lines = header_lines(resp.headers())?;
+        HandshakeResponse::from_header_lines(lines.iter().map(String::as_str))
+    }
+}
+
+impl HandshakeRequest {
+    /// Create a client request for `uri` with a fresh `Sec-WebSocket-Key`.
+    ///
+    /// The URI's authority becomes the `Host` header and its path and query
+    /// the request target; the scheme is not inspected. Set
+    /// [`Self::origin`], [`Self::protocols`], [`Self::extensions`] and
+    /// [`Self::headers`] as needed, then call [`Self::to_http_request`].
+    ///
+    /// # Errors
+    ///
+    /// - `Error::InvalidUrl` if `uri` has no authority
+    /// - `Error::Io` if the system random number generator fails
+    pub fn client(uri: &Uri) -> Result<Self> {
+        let authority = uri
+            .authority()
+            .ok_or_else(|| Error::InvalidUrl(format!("missing host in {}", uri)))?;
+        let host = match authority.port_u16() {
+            Some(port) => format!("{}:{}", authority.host(), port),
+            None => authority.host().to_string(),
+        };
+
+        Ok(Self {
+            path: uri
+                .path_and_query()
+                .map_or("/", |pq| pq.as_str())
+                .to_string(),
+            host,
+            key: generate_key()?,
+            version: 13,
+            origin: None,
+            protocols: Vec::new(),
+            extensions: Vec::new(),
+            headers: HeaderMap::new(),
+        })
+    }
+
+    /// Build the `GET` upgrade request for another HTTP stack's client.
+    ///
+    /// The handshake headers come from the request's fields. Entries of
+    /// [`Self::headers`] that would repeat them are skipped, so a request
+    /// returned by [`Self::parse`] converts cleanly.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidHeaderValue` if a value contains CR/LF or other
+    /// control characters, a header name is not a token, or a subprotocol
+    /// is not a valid token.
+    pub fn to_http_request(&self) -> Result<Request<()>> {
+        for protocol in &self.protocols {
+            if !is_token(protocol) {
+                return Err(Error::InvalidHeaderValue {
+                    header: "Sec-WebSocket-Protocol".to_string(),
+                    reason: format!("invalid subprotocol name: {:?}", protocol),
+                });
+            }
+        }
+
+        let uri: Uri = self.path.parse().map_err(|_| Error::InvalidHeaderValue {
+            header: "path".to_string(),
+            reason: format!("invalid request target: {:?}", self.path),
+        })?;
+        let mut req = Request::new(());
+        *req.method_mut() = Method::GET;
+        *req.uri_mut() = uri;
+        *req.version_mut() = Version::HTTP_11;
+
+        let headers = req.headers_mut();
+        headers.append(header::HOST, header_value("Host", &self.host)?);
+        headers.append(header::UPGRADE, HeaderValue::from_static("websocket"));
+        headers.append(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+        headers.append(
+            header::SEC_WEBSOCKET_KEY,
+            header_value("Sec-WebSocket-Key", &self.key)?,
+        );
+        headers.append(
+            header::SEC_WEBSOCKET_VERSION,
+            HeaderValue::from(u16::from(self.version)),
+        );
+        if let Some(ref origin) = self.origin {
+            headers.append(header::ORIGIN, header_value("Origin", origin)?);
+        }
+        if !self.protocols.is_empty() {
+            headers.append(
+                header::SEC_WEBSOCKET_PROTOCOL,
+                header_value("Sec-WebSocket-Protocol", &self.protocols.join(", "))?,
+            );
+        }
+        if !self.extensions.is_empty() {
+            headers.append(
+                header::SEC_WEBSOCKET_EXTENSIONS,
+                header_value("Sec-WebSocket-Extensions", &self.extensions.join(", "))?,
+            );
+        }
+        for (name, value) in self.headers.iter() {
+            if REQUEST_MANAGED_HEADERS
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case(name))
+            {
+                continue;
+            }
+            headers.append(header_name(name)?, header_value(name, value)?);
+        }
+
+        Ok(req)
+    }
+}
+
+impl HandshakeResponse {
+    /// Build the `101 Switching Protocols` response for another HTTP stack.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::write`]; values with other control characters are
+    /// rejected with `Error::InvalidHeaderValue` as well.
+    pub fn into_http_response(self) -> Result<Response<()>> {
+        self.validate_headers()?;
+
+        let mut resp = Response::new(());
+        *resp.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
+        *resp.version_mut() = Version::HTTP_11;
+
+        let headers = resp.headers_mut();
+        headers.append(header::UPGRADE, HeaderValue::from_static("websocket"));
+        headers.append(header::CONNECTION, HeaderValue::from_static("Upgrade"));
+        headers.append(
+            header::SEC_WEBSOCKET_ACCEPT,
+            header_value("Sec-WebSocket-Accept", &self.accept)?,
+        );
+        if let Some(ref protocol) = self.protocol {
+            headers.append(
+                header::SEC_WEBSOCKET_PROTOCOL,
+                header_value("Sec-WebSocket-Protocol", protocol)?,
+            );
+        }
+        for ext in &self.extensions {
+            headers.append(
+                header::SEC_WEBSOCKET_EXTENSIONS,
+                header_value("Sec-WebSocket-Extensions", ext)?,
+            );
+        }
+        for (name, value) in self.headers.iter() {
+            headers.append(header_name(name)?, header_value(name, value)?);
+        }
+
+        Ok(resp)
+    }
+}
+
+/// Render `headers` as `name: value` lines for the shared header parser.
+fn header_lines(headers: &::http::HeaderMap) -> Result<Vec<String>> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = std::str::from_utf8(value.as_bytes())
+                .map_err(|_| Error::InvalidHandshake("Invalid UTF-8".into()))?;
+            Ok(format!("{}: {}", name, value))
+        })
+        .collect()
+}
+
+pub(crate) fn header_name(name: &str) -> Result<HeaderName> {
+    validate_header_name(name)?;
+    HeaderName::from_bytes(name.as_bytes()).map_err(|_| Error::InvalidHeaderValue {
+        header: name.to_string(),
+        reason: "invalid header name".to_string(),
+    })
+}
+
+pub(crate) fn header_value(name: &str, value: &str) -> Result<HeaderValue> {
+    validate_header_value(name, value)?;
+    HeaderValue::from_str(value).map_err(|_| Error::InvalidHeaderValue {
+        header: name.to_string(),
+        reason: "contains control characters".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::compute_accept_key;
+
+    fn upgrade_request() -> ::http::request::Builder {
+        Request::get("/chat?room=1")
+            .header("Host", "server.example.com")
+            .header("Upgrade", "websocket")
+            .header("Connection", "Upgrade")
+            .header("Sec-WebSocket-Key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .header("Sec-WebSocket-Version", "13")
+    }
+
+    #[test]
+    fn test_request_try_from_matches_parse() {
+        let req = upgrade_request()
+            .header("Origin", "http://example.com")
+            .header("Sec-WebSocket-Protocol", "chat, superchat")
+            .body(())
+            .unwrap();
+        let converted = HandshakeRequest::try_from(&req).unwrap();
+
+        let parsed = HandshakeRequest::parse(
+            b"GET /chat?room=1 HTTP/1.1\r\n\
+            host: server.example.com\r\n\
+            upgrade: websocket\r\n\
+            connection: Upgrade\r\n\
+            sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            sec-websocket-version: 13\r\n\
+            origin: http://example.com\r\n\
+            sec-websocket-protocol: chat, superchat\r\n\
+            \r\n",
+        )
+        .unwrap();
+        assert_eq!(converted, parsed);
+        assert!(converted.validate().is_ok());
+    }
+
+    #[test]
+    fn test_request_try_from_applies_parse_rules() {
+        let post = upgrade_request().method(Method::POST).body(()).unwrap();
+        assert!(matches!(
+            HandshakeRequest::try_from(&post),
+            Err(Error::InvalidHandshake(_))
+        ));
+
+        let duplicate_host = upgrade_request()
+            .header("Host", "evil.com")
+            .body(())
+            .unwrap();
+        assert!(matches!(
+            HandshakeRequest::try_from(&duplicate_host),
+            Err(Error::InvalidHandshake(msg)) if msg.contains("Duplicate")
+        ));
+
+        let http2 = upgrade_request().version(Version::HTTP_2).body(()).unwrap();
+        assert!(HandshakeRequest::try_from(&http2).is_err());
+
+        let old = upgrade_request()
+            .header("Sec-WebSocket-Version", "8")
+            .body(())
+            .unwrap();
+        assert!(HandshakeRequest::try_from(&old).is_err());
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        let req = HandshakeRequest::try_from(&upgrade_request().body(()).unwrap()).unwrap();
+        let mut response = HandshakeResponse::from_request(&req).with_header("Set-Cookie", "a=1");
+        response.protocol = Some("chat".to_string());
+
+        let http_response = response.clone().into_http_response().unwrap();
+        assert_eq!(
+            http_response.headers()["sec-websocket-accept"],
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+
+        // The http crate lowercases header names.
+        let converted = HandshakeResponse::try_from(&http_response).unwrap();
+        assert_eq!(converted.accept, response.accept);
+        assert_eq!(converted.protocol, response.protocol);
+        assert_eq!(converted.headers.get("Set-Cookie"), Some("a=1"));
+    }
+
+    #[test]
+    fn test_response_rejects_invalid_headers() {
+        let req = HandshakeRequest::try_from(&upgrade_request().body(()).unwrap()).unwrap();
+        let response = HandshakeResponse::from_request(&req);
+
+        assert!(matches!(
+            response
+                .clone()
+                .with_header("X-Bad", "a\0b")
+                .into_http_response(),
+            Err(Error::InvalidHeaderValue { .. })
+        ));
+        assert!(matches!(
+            response.with_header("Upgrade", "h2c").into_http_response(),
+            Err(Error::InvalidHeaderValue { .. })
+        ));
+
+        let not_upgraded = Response::builder().status(200).body(()).unwrap();
+        assert!(HandshakeResponse::try_from(&not_upgraded).is_err());
+    }
+
+    #[test]
+    fn test_client_request() {
+        let uri: Uri = "ws://example.com:9001/chat?token=abc".parse().unwrap();
+        let mut request = HandshakeRequest::client(&uri).unwrap();
+        request.protocols = vec!["chat".to_string()];
+        request.headers.append("Authorization", "Bearer abc");
+
+        let http_request = request.to_http_request().unwrap();
+        assert_eq!(http_request.uri(), "/chat?token=abc");
+        assert_eq!(http_request.headers()["host"], "example.com:9001");
+        assert_eq!(http_request.headers()["authorization"], "Bearer abc");
+
+        // The server side accepts what the client side builds.
+        let received = HandshakeRequest::try_from(&http_request).unwrap();
+        assert!(received.validate().is_ok());
+        assert_eq!(received.key, request.key);
+        assert_eq!(received.to_http_request().unwrap().headers().len(), 7);
+
+        let mut response = HandshakeResponse::from_request(&received);
+        response.protocol = Some("chat".to_string());
+        assert_eq!(response.accept, compute_accept_key(&request.key));
+        assert!(response.verify(&request.key, &request.protocols).is_ok());
+        assert!(response.verify(&request.key, &[]).is_err());
+    }
+
+    #[test]
+    fn test_client_request_rejects_invalid_values() {
+        let uri: Uri = "/relative".parse().unwrap();
+        assert!(matches!(
+            HandshakeRequest::client(&uri),
+            Err(Error::InvalidUrl(_))
+        ));
+
+        let uri: Uri = "ws://example.com/".parse().unwrap();
+        let mut request = HandshakeRequest::client(&uri).unwrap();
+        request.protocols = vec!["chat v2".to_string()];
+        assert!(request.to_http_request().is_err());
+    }
+}
@@ -3,6 +3,9 @@
 pub mod assembler;
 pub mod frame;
 pub mod handshake;
+pub mod headers;
+#[cfg(feature = "http")]
+pub mod http;
 pub mod mask;
 pub mod opcode;
 pub mod utf8;
@@ -11,7 +14,10 @@ pub mod validation;
 
 pub use assembler::{AssembledMessage, MessageAssembler};
 pub use frame::Frame;
-pub use handshake::{HandshakeRequest, HandshakeResponse, WS_GUID, compute_accept_key};
+pub use handshake::{
+    HandshakeRequest, HandshakeResponse, WS_GUID, compute_accept_key, find_head_end, generate_key,
+};
+pub use headers::HeaderMap;
 pub use mask::{apply_mask, apply_mask_fast};
 pub use opcode::OpCode;
 pub use utf8::{Utf8Validator, validate_utf8};
@@ -0,0 +1,842 @@
+//! Server side of the opening handshake over an async stream.
+
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+use crate::codec::handshake::read_http_head;
+use crate::config::Config;
+use crate::connection::{Connection, Role};
+use crate::error::{Error, Result};
+use crate::extensions::ExtensionRegistry;
+use crate::protocol::handshake::{validate_header_name, validate_header_value};
+use crate::protocol::{HandshakeRequest, HandshakeResponse, HeaderMap};
+
+/// Reason for refusing an upgrade request in [`accept_with`].
+///
+/// The peer receives a plain HTTP response with this status code and any
+/// additional [`headers`](Self::headers) instead of
+/// `101 Switching Protocols`.
+///
+/// ## Example
+///
+/// ```rust
+/// use rsws::server::Rejection;
+///
+/// let rejection = Rejection::too_many_requests().with_header("Retry-After", "30");
+/// let mut buf = Vec::new();
+/// rejection.write(&mut buf).unwrap();
+/// assert!(buf.starts_with(b"HTTP/1.1 429 Too Many Requests\r\n"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rejection {
+    /// HTTP status code (e.g. 403).
+    pub status: u16,
+    /// Human-readable reason, sent as the reason phrase.
+    pub reason: String,
+    /// Additional response headers, such as `WWW-Authenticate` or
+    /// `Retry-After`.
+    pub headers: HeaderMap,
+}
+
+impl Rejection {
+    /// Create a rejection with a custom status code and reason.
+    #[must_use]
+    pub fn new(status: u16, reason: impl Into<String>) -> Self {
+        Self {
+            status,
+            reason: reason.into(),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// `400 Bad Request`.
+    #[must_use]
+    pub fn bad_request() -> Self {
+        Self::new(400, "Bad Request")
+    }
+
+    /// `401 Unauthorized` with a `WWW-Authenticate` challenge
+    /// (e.g. `Bearer realm="chat"`).
+    #[must_use]
+    pub fn unauthorized(challenge: impl Into<String>) -> Self {
+        Self::new(401, "Unauthorized").with_header("WWW-Authenticate", challenge)
+    }
+
+    /// `403 Forbidden`.
+    #[must_use]
+    pub fn forbidden() -> Self {
+        Self::new(403, "Forbidden")
+    }
+
+    /// `426 Upgrade Required`, advertising `Sec-WebSocket-Version: 13`
+    /// (RFC 6455 Section 4.4).
+    #[must_use]
+    pub fn upgrade_required() -> Self {
+        Self::new(426, "Upgrade Required")
+            .with_header("Upgrade", "websocket")
+            .with_header("Sec-WebSocket-Version", "13")
+    }
+
+    /// `429 Too Many Requests`. Add a `Retry-After` header with
+    /// [`Self::with_header`] to tell the client when to come back.
+    #[must_use]
+    pub fn too_many_requests() -> Self {
+        Self::new(429, "Too Many Requests")
+    }
+
+    /// Add a header to the response, keeping any existing values.
+    #[must_use]
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Write the HTTP response to a buffer.
+    ///
+    /// CR and LF are stripped from the reason phrase. `Connection: close` and
+    /// `Content-Length: 0` are always sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidHeaderValue` if a header name is not a token, a
+    /// value contains CR/LF, or a header would override `Connection` or
+    /// `Content-Length`. Nothing is written in that case.
+    pub fn write(&self, buf: &mut Vec<u8>) -> Result<()> {
+        self.validate_headers()?;
+
+        // The reason phrase is caller-controlled; never let it break the status line.
+        let reason: String = self
+            .reason
+            .chars()
+            .filter(|c| *c != '\r' && *c != '\n')
+            .collect();
+        buf.extend_from_slice(format!("HTTP/1.1 {} {}\r\n", self.status, reason).as_bytes());
+        for (name, value) in self.headers.iter() {
+            buf.extend_from_slice(format!("{}: {}\r\n", name, value).as_bytes());
+        }
+        buf.extend_from_slice(b"Connection: close\r\n");
+        buf.extend_from_slice(b"Content-Length: 0\r\n");
+        buf.extend_from_slice(b"\r\n");
+        Ok(())
+    }
+
+    /// Build the response for another HTTP stack (feature = "http").
+    ///
+    /// The reason phrase is not carried over: `http` responses use the
+    /// canonical reason for the status code.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::write`]; an invalid status code or values with other
+    /// control characters are rejected with `Error::InvalidHeaderValue` as
+    /// well.
+    #[cfg(feature = "http")]
+    pub fn into_http_response(self) -> Result<::http::Response<()>> {
+        use crate::protocol::http::{header_name, header_value};
+        use ::http::header::{CONNECTION, CONTENT_LENGTH};
+        use ::http::{HeaderValue, StatusCode};
+
+        self.validate_headers()?;
+        let status = StatusCode::from_u16(self.status).map_err(|_| Error::InvalidHeaderValue {
+            header: "status".to_string(),
+            reason: format!("invalid status code: {}", self.status),
+        })?;
+
+        let mut resp = ::http::Response::new(());
+        *resp.status_mut() = status;
+        let headers = resp.headers_mut();
+        for (name, value) in self.headers.iter() {
+            headers.append(header_name(name)?, header_value(name, value)?);
+        }
+        headers.insert(CONNECTION, HeaderValue::from_static("close"));
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("0"));
+        Ok(resp)
+    }
+
+    /// The response sent when the handshake fails with `err`.
+    pub(crate) fn for_error(err: &Error) -> Self {
+        match err {
+            Error::OriginNotAllowed { .. } => Self::forbidden(),
+            Error::UnsupportedVersion { .. } => Self::upgrade_required(),
+            _ => Self::bad_request(),
+        }
+    }
+
+    fn validate_headers(&self) -> Result<()> {
+        for (name, value) in self.headers.iter() {
+            validate_header_name(name)?;
+            if name.eq_ignore_ascii_case("connection")
+                || name.eq_ignore_ascii_case("content-length")
+            {
+                return Err(Error::InvalidHeaderValue {
+                    header: name.to_string(),
+                    reason: "set by the rejection response".to_string(),
+                });
+            }
+            validate_header_value(name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Accept a WebSocket upgrade on a freshly accepted stream.
+///
+/// Reads the HTTP request head (bounded by `limits.max_handshake_size`),
+/// validates it with [`HandshakeRequest::validate_with_config`], writes the
+/// `101 Switching Protocols` response and returns the connection together
+/// with the parsed request.
+///
+/// The subprotocol is chosen with [`HandshakeRequest::select_subprotocol`]
+/// and is available from [`Connection::subprotocol`]. The request (headers,
+/// query parameters, cookies) stays available from [`Connection::request`].
+///
+/// Malformed requests and requests without a required subprotocol are
+/// answered with `400 Bad Request`, unsupported versions with
+/// `426 Upgrade Required` and disallowed origins with `403 Forbidden`
+/// before the error is returned.
+///
+/// # Errors
+///
+/// - `Error::HandshakeTooLarge` if the request head exceeds the limit
+/// - `Error::HandshakeTimeout` if the exchange exceeds `timeouts.handshake`
+/// - `Error::InvalidHandshake` if the request is malformed
+/// - `Error::UnsupportedVersion` if the client does not speak version 13
+/// - `Error::OriginNotAllowed` if `config.allowed_origins` rejects the origin
+/// - `Error::UnsupportedSubprotocol` if `config.require_subprotocol` is set
+///   and no subprotocol matches
+/// - `Error::InvalidHeaderValue` if a header added to the response is invalid
+/// - `Error::Io` on stream failure
+pub async fn accept<S>(stream: S, config: Config) -> Result<(Connection<S>, HandshakeRequest)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    accept_with(stream, config, |_, _| Ok(())).await
+}
+
+/// Accept a WebSocket upgrade, letting `callback` inspect the request first.
+///
+/// The callback receives the validated request and the response that will
+/// be sent, with the subprotocol already selected from the configuration.
+/// It may modify the response (for example to choose a different
+/// subprotocol or add a `Set-Cookie` header) and return `Ok(())` to complete
+/// the upgrade, or return a [`Rejection`] to refuse it.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// use rsws::server::{Rejection, accept_with};
+///
+/// let (conn, request) = accept_with(stream, Config::server(), |req, resp| {
+///     if req.path != "/chat" {
+///         return Err(Rejection::new(404, "Not Found"));
+///     }
+///     if req.header("Authorization").is_none() {
+///         return Err(Rejection::unauthorized(r#"Bearer realm="chat""#));
+///     }
+///     resp.protocol = req.protocols.iter().find(|p| *p == "chat.v2").cloned();
+///     resp.headers.append("Set-Cookie", "seen=1; HttpOnly");
+///     Ok(())
+/// })
+/// .await?;
+/// ```
+///
+/// # Errors
+///
+/// - `Error::HandshakeRejected` if the callback returned a [`Rejection`]
+/// - `Error::InvalidHeaderValue` if the callback's [`Rejection`] has an
+///   invalid header; the client gets `500 Internal Server Error` instead
+/// - Any error from [`accept`]
+pub async fn accept_with<S, F>(
+    stream: S,
+    config: Config,
+    callback: F,
+) -> Result<(Connection<S>, HandshakeRequest)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: FnOnce(&HandshakeRequest, &mut HandshakeResponse) -> std::result::Result<(), Rejection>,
+{
+    accept_with_extensions(stream, config, ExtensionRegistry::new(), callback).await
+}
+
+/// Accept a WebSocket upgrade, negotiating extensions from `extensions`.
+///
+/// The client's `Sec-WebSocket-Extensions` offers are negotiated with
+/// [`HandshakeResponse::from_request_with_extensions`] before `callback`
+/// runs, so the callback sees the accepted extensions in
+/// `response.extensions`. The returned connection uses the negotiated
+/// registry. A malformed extension header is answered with
+/// `400 Bad Request`.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// use rsws::extensions::ExtensionRegistry;
+/// use rsws::extensions::deflate::{DeflateConfig, DeflateExtension};
+/// use rsws::server::accept_with_extensions;
+///
+/// let mut registry = ExtensionRegistry::new();
+/// registry.add(Box::new(DeflateExtension::server(DeflateConfig::default())))?;
+///
+/// let (conn, request) =
+///     accept_with_extensions(stream, Config::server(), registry, |_, _| Ok(())).await?;
+/// ```
+///
+/// # Errors
+///
+/// - `Error::InvalidExtension` if the extension header is malformed
+/// - Any error from [`accept_with`]
+pub async fn accept_with_extensions<S, F>(
+    stream: S,
+    config: Config,
+    extensions: ExtensionRegistry,
+    callback: F,
+) -> Result<(Connection<S>, HandshakeRequest)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    F: FnOnce(&HandshakeRequest, &mut HandshakeResponse) -> std::result::Result<(), Rejection>,
+{
+    let timeout = config.timeouts.as_ref().map(|t| t.handshake);
+    accept_routed(
+        stream,
+        config,
+        extensions,
+        timeout,
+        |_, config| Ok(config),
+        callback,
+    )
+    .await
+}
+
+/// Like [`accept_with_extensions`], but `route` picks the configuration for
+/// the parsed request before it is validated.
+///
+/// The request head is read with the limits of `config`; the configuration
+/// returned by `route` applies to everything after that, including the
+/// returned connection. The whole exchange is bounded by `timeout`.
+pub(crate) async fn accept_routed<S, R, F>(
+    stream: S,
+    config: Config,
+    extensions: ExtensionRegistry,
+    timeout: Option<Duration>,
+    route: R,
+    callback: F,
+) -> Result<(Connection<S>, HandshakeRequest)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    R: FnOnce(&HandshakeRequest, Config) -> std::result::Result<Config, Rejection>,
+    F: FnOnce(&HandshakeRequest, &mut HandshakeResponse) -> std::result::Result<(), Rejection>,
+{
+    match timeout {
+        Some(limit) => {
+            tokio::time::timeout(limit, exchange(stream, config, extensions, route, callback))
+                .await
+                .map_err(|_| Error::HandshakeTimeout)?
+        }
+        None => exchange(stream, config, extensions, route, callback).await,
+    }
+}
+
+async fn exchange<S, R, F>(
+    mut stream: S,
+    config: Config,
+    extensions: ExtensionRegistry,
+    route: R,
+    callback: F,
+) -> Result<(Connection<S>, HandshakeRequest)>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    R: FnOnce(&HandshakeRequest, Config) -> std::result::Result<Config, Rejection>,
+    F: FnOnce(&HandshakeRequest, &mut HandshakeResponse) -> std::result::Result<(), Rejection>,
+{
+    let (head, leftover) = read_http_head(&mut stream, config.limits.max_handshake_size).await?;
+
+    let request = match HandshakeRequest::parse(&head) {
+        Ok(req) => req,
+        Err(e) => {
+            let _ = send_rejection(&mut stream, &Rejection::for_error(&e)).await;
+            return Err(e);
+        }
+    };
+    let config = match route(&request, config) {
+        Ok(config) => config,
+        Err(rejection) => return Err(reject(&mut stream, rejection).await),
+    };
+    if let Err(e) = request.validate_with_config(&config) {
+        let _ = send_rejection(&mut stream, &Rejection::for_error(&e)).await;
+        return Err(e);
+    }
+
+    let negotiated = request.select_subprotocol(&config).and_then(|protocol| {
+        let (mut response, extensions) =
+            HandshakeResponse::from_request_with_extensions(&request, extensions)?;
+        response.protocol = protocol;
+        Ok((response, extensions))
+    });
+    let (mut response, extensions) = match negotiated {
+        Ok(v) => v,
+        Err(e) => {
+            let _ = send_rejection(&mut stream, &Rejection::for_error(&e)).await;
+            return Err(e);
+        }
+    };
+    if let Err(rejection) = callback(&request, &mut response) {
+        return Err(reject(&mut stream, rejection).await);
+    }
+
+    let mut buf = Vec::with_capacity(256);
+    if let Err(e) = response.write(&mut buf) {
+        // The callback added a header that cannot be written.
+        let _ = send_rejection(&mut stream, &Rejection::new(500, "Internal Server Error")).await;
+        return Err(e);
+    }
+    stream.write_all(&buf).await?;
+    stream.flush().await?;
+
+    // A client may send its first frames without waiting for the 101; keep
+    // whatever was read along with the request head.
+    let conn = Connection::from_parts(stream, leftover, Role::Server, config, extensions)
+        .with_subprotocol(response.protocol)
+        .with_request(request.clone());
+    Ok((conn, request))
+}
+
+/// Send a rejection chosen by the caller and return the error to report.
+///
+/// A rejection that cannot be written is replaced with `500 Internal Server
+/// Error`, so the client still gets a response.
+async fn reject<S>(stream: &mut S, rejection: Rejection) -> Error
+where
+    S: AsyncWrite + Unpin,
+{
+    if let Err(e @ Error::InvalidHeaderValue { .. }) = send_rejection(stream, &rejection).await {
+        let _ = send_rejection(stream, &Rejection::new(500, "Internal Server Error")).await;
+        return e;
+    }
+    Error::HandshakeRejected {
+        status: rejection.status,
+        reason: rejection.reason,
+    }
+}
+
+async fn send_rejection<S>(stream: &mut S, rejection: &Rejection) -> Result<()>
+where
+    S: AsyncWrite + Unpin,
+{
+    let mut buf = Vec::with_capacity(128);
+    rejection.write(&mut buf)?;
+    stream.write_all(&buf).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Message;
+    use tokio::io::{AsyncReadExt, duplex};
+
+    const REQUEST: &[u8] = b"GET /chat HTTP/1.1\r\n\
+        Host: server.example.com\r\n\
+        Upgrade: websocket\r\n\
+        Connection: Upgrade\r\n\
+        Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+        Sec-WebSocket-Version: 13\r\n\
+        Origin: http://example.com\r\n\
+        Sec-WebSocket-Protocol: chat, superchat\r\n\
+        \r\n";
+
+    async fn read_response(client: &mut tokio::io::DuplexStream) -> String {
+        let (head, _) = read_http_head(client, 8192).await.unwrap();
+        String::from_utf8(head).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_accept() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        let (mut conn, request) = accept(server, Config::server()).await.unwrap();
+        assert_eq!(request.path, "/chat");
+
+        let response = read_response(&mut client).await;
+        let parsed = HandshakeResponse::parse(response.as_bytes()).unwrap();
+        assert_eq!(parsed.accept, "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+
+        conn.send(Message::text("hi")).await.unwrap();
+        let mut frame = [0u8; 4];
+        client.read_exact(&mut frame).await.unwrap();
+        assert_eq!(frame, [0x81, 0x02, b'h', b'i']);
+    }
+
+    #[tokio::test]
+    async fn test_accept_keeps_pipelined_frames() {
+        let (mut client, server) = duplex(4096);
+        let mut data = REQUEST.to_vec();
+        // Masked text frames "hi" and "yo", sent without waiting for the 101.
+        data.extend_from_slice(&[0x81, 0x82, 0, 0, 0, 0, b'h', b'i']);
+        data.extend_from_slice(&[0x81, 0x82, 0, 0, 0, 0, b'y', b'o']);
+        client.write_all(&data).await.unwrap();
+
+        let (mut conn, _request) = accept(server, Config::server()).await.unwrap();
+        assert_eq!(conn.recv().await.unwrap(), Some(Message::text("hi")));
+        assert_eq!(conn.recv().await.unwrap(), Some(Message::text("yo")));
+    }
+
+    #[tokio::test]
+    async fn test_accept_with_selects_protocol() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        let (conn, _request) = accept_with(server, Config::server(), |req, resp| {
+            resp.protocol = req.protocols.iter().find(|p| *p == "superchat").cloned();
+            Ok(())
+        })
+        .await
+        .unwrap();
+        assert_eq!(conn.subprotocol(), Some("superchat"));
+
+        let response = read_response(&mut client).await;
+        assert!(response.contains("Sec-WebSocket-Protocol: superchat\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_with_rejection() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        let result = accept_with(server, Config::server(), |_, _| {
+            Err(Rejection::new(401, "Unauthorized"))
+        })
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::HandshakeRejected { status: 401, .. })
+        ));
+
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_with_response_headers() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        accept_with(server, Config::server(), |_, resp| {
+            resp.headers.append("Set-Cookie", "session=abc; HttpOnly");
+            resp.headers.append("Server", "rsws");
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        let response = read_response(&mut client).await;
+        assert!(response.contains("Set-Cookie: session=abc; HttpOnly\r\n"));
+        let parsed = HandshakeResponse::parse(response.as_bytes()).unwrap();
+        assert_eq!(parsed.headers.get("server"), Some("rsws"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_with_unauthorized_challenge() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        let result = accept_with(server, Config::server(), |_, _| {
+            Err(Rejection::unauthorized(r#"Bearer realm="chat""#))
+        })
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::HandshakeRejected { status: 401, .. })
+        ));
+
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 401 Unauthorized\r\n"));
+        assert!(response.contains("WWW-Authenticate: Bearer realm=\"chat\"\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_with_rate_limit() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        let result = accept_with(server, Config::server(), |_, _| {
+            Err(Rejection::too_many_requests().with_header("Retry-After", "30"))
+        })
+        .await;
+        assert!(matches!(
+            result,
+            Err(Error::HandshakeRejected { status: 429, .. })
+        ));
+
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 429 Too Many Requests\r\n"));
+        assert!(response.contains("Retry-After: 30\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_with_invalid_rejection_header() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        let result = accept_with(server, Config::server(), |_, _| {
+            Err(Rejection::forbidden().with_header("X-Reason", "no\r\nX-Injected: 1"))
+        })
+        .await;
+        assert!(matches!(result, Err(Error::InvalidHeaderValue { .. })));
+
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert!(!response.contains("X-Injected"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_with_invalid_response_header() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        let result = accept_with(server, Config::server(), |_, resp| {
+            resp.headers.append("X-Session", "a\r\nX-Injected: 1");
+            Ok(())
+        })
+        .await;
+        assert!(matches!(result, Err(Error::InvalidHeaderValue { .. })));
+
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert!(!response.contains("X-Injected"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_rejects_unsupported_version() {
+        let (mut client, server) = duplex(4096);
+        let request = std::str::from_utf8(REQUEST)
+            .unwrap()
+            .replace("Sec-WebSocket-Version: 13", "Sec-WebSocket-Version: 8");
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        let result = accept(server, Config::server()).await;
+        assert!(matches!(
+            result,
+            Err(Error::UnsupportedVersion { version: 8 })
+        ));
+
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 426 Upgrade Required\r\n"));
+        assert!(response.contains("Sec-WebSocket-Version: 13\r\n"));
+    }
+
+    #[test]
+    fn test_rejection_rejects_reserved_header() {
+        let rejection = Rejection::bad_request().with_header("Content-Length", "5");
+        let mut buf = Vec::new();
+        assert!(matches!(
+            rejection.write(&mut buf),
+            Err(Error::InvalidHeaderValue { .. })
+        ));
+        assert!(buf.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_accept_rejects_malformed_request() {
+        let (mut client, server) = duplex(4096);
+        client
+            .write_all(b"POST / HTTP/1.1\r\nHost: x\r\n\r\n")
+            .await
+            .unwrap();
+
+        let result = accept(server, Config::server()).await;
+        assert!(matches!(result, Err(Error::InvalidHandshake(_))));
+
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_rejects_disallowed_origin() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        let config = Config::server().with_allowed_origins(vec!["https://example.com".into()]);
+        let result = accept(server, config).await;
+        assert!(matches!(result, Err(Error::OriginNotAllowed { .. })));
+
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 403 Forbidden\r\n"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_accept_handshake_timeout() {
+        let (mut client, server) = duplex(4096);
+        // Slowloris: a partial request head that never completes.
+        client.write_all(b"GET /chat HTTP/1.1\r\n").await.unwrap();
+
+        let config = Config::server().with_timeouts(crate::config::Timeouts::default());
+        let result = accept(server, config).await;
+        assert!(matches!(result, Err(Error::HandshakeTimeout)));
+    }
+
+    #[tokio::test]
+    async fn test_accept_enforces_handshake_limit() {
+        let (mut client, server) = duplex(16 * 1024);
+        client.write_all(&[b'A'; 9000]).await.unwrap();
+
+        let result = accept(server, Config::server()).await;
+        assert!(matches!(result, Err(Error::HandshakeTooLarge { .. })));
+    }
+
+    #[cfg(feature = "compression")]
+    fn deflate_registry() -> ExtensionRegistry {
+        use crate::extensions::deflate::{DeflateConfig, DeflateExtension};
+
+        let mut registry = ExtensionRegistry::new();
+        registry
+            .add(Box::new(DeflateExtension::server(DeflateConfig::default())))
+            .unwrap();
+        registry
+    }
+
+    fn request_with_extensions(header: &str) -> Vec<u8> {
+        let request = std::str::from_utf8(REQUEST).unwrap();
+        let head = request.strip_suffix("\r\n").unwrap();
+        format!("{}Sec-WebSocket-Extensions: {}\r\n\r\n", head, header).into_bytes()
+    }
+
+    #[cfg(feature = "compression")]
+    #[tokio::test]
+    async fn test_accept_with_extensions_negotiates_fallback() {
+        let (mut client, server) = duplex(4096);
+        client
+            .write_all(&request_with_extensions(
+                "permessage-deflate; x-unknown, permessage-deflate; server_no_context_takeover",
+            ))
+            .await
+            .unwrap();
+
+        let (mut conn, _request) =
+            accept_with_extensions(server, Config::server(), deflate_registry(), |_, resp| {
+                assert_eq!(resp.extensions.len(), 1);
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+        let response = read_response(&mut client).await;
+        assert!(response.contains(
+            "Sec-WebSocket-Extensions: permessage-deflate; server_no_context_takeover\r\n"
+        ));
+        assert_eq!(conn.extensions_mut().negotiated_count(), 1);
+
+        conn.send(Message::text("compress me please"))
+            .await
+            .unwrap();
+        let mut header = [0u8; 1];
+        client.read_exact(&mut header).await.unwrap();
+        assert_eq!(header[0], 0xC1);
+    }
+
+    #[tokio::test]
+    async fn test_accept_with_extensions_ignores_unknown() {
+        let (mut client, server) = duplex(4096);
+        client
+            .write_all(&request_with_extensions("x-unknown"))
+            .await
+            .unwrap();
+
+        let (mut conn, request) = accept_with_extensions(
+            server,
+            Config::server(),
+            ExtensionRegistry::new(),
+            |_, _| Ok(()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(request.extensions, vec!["x-unknown".to_string()]);
+        assert_eq!(conn.extensions_mut().negotiated_count(), 0);
+
+        let response = read_response(&mut client).await;
+        assert!(!response.contains("Sec-WebSocket-Extensions"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_rejects_malformed_extension_header() {
+        let (mut client, server) = duplex(4096);
+        client
+            .write_all(&request_with_extensions(r#"x-ext; p="open"#))
+            .await
+            .unwrap();
+
+        let result = accept(server, Config::server()).await;
+        assert!(matches!(result, Err(Error::InvalidExtension(_))));
+
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_selects_configured_subprotocol() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        let config = Config::server().with_subprotocols(["superchat", "chat"]);
+        let (conn, _request) = accept(server, config).await.unwrap();
+        assert_eq!(conn.subprotocol(), Some("superchat"));
+
+        let response = read_response(&mut client).await;
+        assert!(response.contains("Sec-WebSocket-Protocol: superchat\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_without_supported_subprotocol() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        let (conn, _request) = accept(server, Config::server()).await.unwrap();
+        assert_eq!(conn.subprotocol(), None);
+
+        let response = read_response(&mut client).await;
+        assert!(!response.contains("Sec-WebSocket-Protocol"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_rejects_missing_required_subprotocol() {
+        let (mut client, server) = duplex(4096);
+        client.write_all(REQUEST).await.unwrap();
+
+        let config = Config::server()
+            .with_subprotocols(["mqtt"])
+            .with_require_subprotocol(true);
+        let result = accept(server, config).await;
+        assert!(matches!(
+            result,
+            Err(Error::UnsupportedSubprotocol { offered }) if offered == ["chat", "superchat"]
+        ));
+
+        let response = read_response(&mut client).await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_accept_keeps_request_headers() {
+        let (mut client, server) = duplex(4096);
+        let request = std::str::from_utf8(REQUEST).unwrap();
+        let head = request.strip_suffix("\r\n").unwrap();
+        let request = format!(
+            "{}Cookie: session=abc\r\nX-Forwarded-For: 203.0.113.7\r\nCookie: theme=dark\r\n\r\n",
+            head.replace("GET /chat ", "GET /chat?token=t%2F1&room=a+b ")
+        );
+        client.write_all(request.as_bytes()).await.unwrap();
+
+        let (conn, request) = accept(server, Config::server()).await.unwrap();
+        assert_eq!(conn.request(), Some(&request));
+        assert_eq!(request.header("x-forwarded-for"), Some("203.0.113.7"));
+        assert_eq!(request.query_param("token").as_deref(), Some("t/1"));
+        assert_eq!(request.query_param("room").as_deref(), Some("a b"));
+        assert_eq!(request.cookie("theme"), Some("dark"));
+
+        let (reader, writer) = conn.split();
+        assert_eq!(reader.request().unwrap().cookie("session"), Some("abc"));
+        assert!(writer.request().is_some());
+    }
+}
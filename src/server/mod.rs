@@ -0,0 +1,32 @@
+//! Server-side WebSocket handshake.
+//!
+//! [`accept`] drives the opening handshake (RFC 6455 Section 4.2) on a raw
+//! stream: it reads and validates the HTTP Upgrade request, writes the
+//! `101 Switching Protocols` response, and returns a ready [`Connection`].
+//! [`accept_with_extensions`] also negotiates the client's extension offers.
+//!
+//! [`Server`] wraps the whole accept loop: it routes upgrade requests by path
+//! to handlers, limits concurrent connections and shuts down gracefully.
+//! Its [`Router`] matches path templates such as `/chat/:room` and can also
+//! serve connections from an accept loop of your own.
+//!
+//! ## Example
+//!
+//! ```rust,ignore
+//! use rsws::{Config, Message};
+//!
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:9001").await?;
+//! let (stream, _) = listener.accept().await?;
+//! let (mut conn, request) = rsws::server::accept(stream, Config::server()).await?;
+//! println!("upgrade for {}", request.path);
+//! ```
+//!
+//! [`Connection`]: crate::Connection
+
+mod handshake;
+mod router;
+mod serve;
+
+pub use handshake::{Rejection, accept, accept_with, accept_with_extensions};
+pub use router::{Params, RoutePolicy, Router};
+pub use serve::{Server, ServerStream};
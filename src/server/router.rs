@@ -0,0 +1,735 @@
+//! Routing upgrade requests by path template.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use super::handshake::{Rejection, accept_routed};
+use super::serve::ServerStream;
+use crate::config::{Config, Limits};
+use crate::connection::{Connection, ShutdownSignal};
+use crate::error::Result;
+use crate::extensions::ExtensionRegistry;
+use crate::protocol::HandshakeRequest;
+use crate::protocol::handshake::decode_path_component;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type Handler<S> = Arc<dyn Fn(Connection<S>, Params) -> BoxFuture + Send + Sync>;
+
+/// `(name, value)` pairs in the order they appear in the request.
+type Pairs = Vec<(String, String)>;
+
+/// What a route captured from the request: path parameters and the decoded
+/// query string.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params {
+    path: Pairs,
+    query: Pairs,
+}
+
+impl Params {
+    /// The percent-decoded value captured by `:name` or `*name`.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.path
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The captured path parameters in template order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.path.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    /// The decoded value of the first query parameter called `name`.
+    pub fn query_param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// All query parameters in order; see [`HandshakeRequest::query_params`].
+    pub fn query_params(&self) -> &[(String, String)] {
+        &self.query
+    }
+}
+
+/// Handshake settings for one route, applied on top of the server's
+/// [`Config`].
+///
+/// Settings left unset keep the server's values.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// use rsws::server::RoutePolicy;
+/// use rsws::Limits;
+///
+/// let policy = RoutePolicy::new()
+///     .with_allowed_origins(vec!["https://app.example.com".to_string()])
+///     .with_subprotocols(["files.v1"])
+///     .with_limits(Limits::new(1 << 20, 16 << 20, 256, 8192));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RoutePolicy {
+    allowed_origins: Option<Vec<String>>,
+    subprotocols: Option<Vec<String>>,
+    require_subprotocol: Option<bool>,
+    limits: Option<Limits>,
+}
+
+impl RoutePolicy {
+    /// A policy that keeps every server setting.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only accept these origins on this route.
+    #[must_use]
+    pub fn with_allowed_origins(mut self, origins: Vec<String>) -> Self {
+        self.allowed_origins = Some(origins);
+        self
+    }
+
+    /// Offer these subprotocols on this route, in order of preference.
+    ///
+    /// This replaces the server's subprotocols and its selector.
+    #[must_use]
+    pub fn with_subprotocols<I, P>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.subprotocols = Some(protocols.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Reject clients that do not offer one of the route's subprotocols.
+    #[must_use]
+    pub fn with_require_subprotocol(mut self, require: bool) -> Self {
+        self.require_subprotocol = Some(require);
+        self
+    }
+
+    /// Use these limits for connections on this route.
+    ///
+    /// `max_handshake_size` is the exception: the request head is read
+    /// before the route is known, so the server's limit applies to it.
+    #[must_use]
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+
+    /// The server's configuration with this policy's settings applied.
+    pub fn apply(&self, mut config: Config) -> Config {
+        if let Some(origins) = &self.allowed_origins {
+            config.allowed_origins = Some(origins.clone());
+        }
+        if let Some(protocols) = &self.subprotocols {
+            config.subprotocols = protocols.clone();
+            config.subprotocol_selector = None;
+        }
+        if let Some(require) = self.require_subprotocol {
+            config.require_subprotocol = require;
+        }
+        if let Some(limits) = &self.limits {
+            config.limits = limits.clone();
+        }
+        config
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Capture(String),
+    Wildcard(String),
+}
+
+impl Segment {
+    /// Literals are more specific than captures, captures than wildcards.
+    fn rank(&self) -> u8 {
+        match self {
+            Segment::Literal(_) => 3,
+            Segment::Capture(_) => 2,
+            Segment::Wildcard(_) => 0,
+        }
+    }
+}
+
+/// A parsed path template such as `/chat/:room` or `/files/*path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pattern {
+    template: String,
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    fn parse(template: &str) -> std::result::Result<Self, String> {
+        let rest = template
+            .strip_prefix('/')
+            .ok_or_else(|| "must start with '/'".to_string())?;
+
+        let mut segments = Vec::new();
+        if !rest.is_empty() {
+            for part in rest.split('/') {
+                if matches!(segments.last(), Some(Segment::Wildcard(_))) {
+                    return Err("'*' must be the last segment".to_string());
+                }
+                let segment = if let Some(name) = part.strip_prefix(':') {
+                    Segment::Capture(name.to_string())
+                } else if let Some(name) = part.strip_prefix('*') {
+                    Segment::Wildcard(name.to_string())
+                } else {
+                    Segment::Literal(part.to_string())
+                };
+                if let Segment::Capture(name) | Segment::Wildcard(name) = &segment {
+                    if name.is_empty() {
+                        return Err("parameters need a name".to_string());
+                    }
+                    let taken = segments.iter().any(
+                        |s| matches!(s, Segment::Capture(n) | Segment::Wildcard(n) if n == name),
+                    );
+                    if taken {
+                        return Err(format!("parameter '{}' appears twice", name));
+                    }
+                }
+                segments.push(segment);
+            }
+        }
+
+        Ok(Self {
+            template: template.to_string(),
+            segments,
+        })
+    }
+
+    /// Match a request path without its query string.
+    fn matches(&self, path: &str) -> Option<Pairs> {
+        let rest = path.strip_prefix('/')?;
+        let parts: Vec<&str> = if rest.is_empty() {
+            Vec::new()
+        } else {
+            rest.split('/').collect()
+        };
+
+        let mut captures = Vec::new();
+        for (i, segment) in self.segments.iter().enumerate() {
+            match segment {
+                Segment::Literal(literal) => {
+                    if parts.get(i) != Some(&literal.as_str()) {
+                        return None;
+                    }
+                }
+                Segment::Capture(name) => match parts.get(i) {
+                    Some(part) if !part.is_empty() => {
+                        captures.push((name.clone(), decode_path_component(part)));
+                    }
+                    _ => return None,
+                },
+                Segment::Wildcard(name) => {
+                    let tail = parts.get(i..).unwrap_or_default().join("/");
+                    captures.push((name.clone(), decode_path_component(&tail)));
+                    return Some(captures);
+                }
+            }
+        }
+        (parts.len() == self.segments.len()).then_some(captures)
+    }
+
+    /// Segment ranks followed by an end-of-pattern marker, compared
+    /// lexicographically between patterns matching the same path. The end
+    /// marker only ever meets a wildcard with an empty tail, and outranks
+    /// it, so `/files` beats `/files/*path` for the path `/files`.
+    fn ranks(&self) -> impl Iterator<Item = u8> + '_ {
+        const END: u8 = 1;
+        self.segments
+            .iter()
+            .map(Segment::rank)
+            .chain(std::iter::once(END))
+    }
+}
+
+struct Route<S> {
+    pattern: Pattern,
+    policy: RoutePolicy,
+    handler: Handler<S>,
+}
+
+/// Dispatches WebSocket upgrades to handlers by path template.
+///
+/// Templates are made of `/`-separated segments: literals match themselves,
+/// `:name` captures one non-empty segment and a final `*name` captures the
+/// rest of the path (possibly empty). The query string is ignored when
+/// matching. When several templates match, the most specific one wins,
+/// comparing segment by segment: literal before `:name` before `*name`.
+///
+/// Requests that match no route are answered with `404 Not Found`. A route
+/// can carry a [`RoutePolicy`] that adjusts the server's [`Config`] for the
+/// handshake and the connection.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// use rsws::server::{Router, RoutePolicy};
+///
+/// let router = Router::new()
+///     .route("/chat/:room", |mut conn, params| async move {
+///         let room = params.get("room").unwrap_or_default().to_string();
+///         while let Ok(Some(msg)) = conn.recv().await { /* ... */ }
+///     })
+///     .route_with(
+///         "/files/*path",
+///         RoutePolicy::new().with_subprotocols(["files.v1"]),
+///         handle_files,
+///     );
+///
+/// // With your own accept loop (or pass the router to `Server::with_router`)
+/// let (stream, _) = listener.accept().await?;
+/// router.serve_connection(stream, Config::server()).await?;
+/// ```
+pub struct Router<S = ServerStream> {
+    routes: Vec<Route<S>>,
+}
+
+impl<S> fmt::Debug for Router<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.routes.iter().map(|r| &r.pattern.template))
+            .finish()
+    }
+}
+
+impl<S> Default for Router<S> {
+    fn default() -> Self {
+        Self { routes: Vec::new() }
+    }
+}
+
+impl<S> Router<S> {
+    /// Create a router with no routes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run `handler` for upgrade requests whose path matches `template`.
+    ///
+    /// A later route with the same template replaces the earlier one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` does not start with `/`, has a `*name` segment
+    /// that is not last, or has an unnamed or repeated parameter.
+    #[must_use]
+    pub fn route<F, Fut>(self, template: &str, handler: F) -> Self
+    where
+        F: Fn(Connection<S>, Params) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.route_with(template, RoutePolicy::new(), handler)
+    }
+
+    /// Like [`Self::route`], with a policy for the route's handshakes.
+    ///
+    /// # Panics
+    ///
+    /// Same as [`Self::route`].
+    #[must_use]
+    pub fn route_with<F, Fut>(mut self, template: &str, policy: RoutePolicy, handler: F) -> Self
+    where
+        F: Fn(Connection<S>, Params) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let pattern = Pattern::parse(template)
+            .unwrap_or_else(|reason| panic!("invalid route template {:?}: {}", template, reason));
+        let route = Route {
+            pattern,
+            policy,
+            handler: Arc::new(move |conn, params| Box::pin(handler(conn, params))),
+        };
+        match self
+            .routes
+            .iter_mut()
+            .find(|r| r.pattern.template == route.pattern.template)
+        {
+            Some(existing) => *existing = route,
+            None => self.routes.push(route),
+        }
+        self
+    }
+
+    /// The most specific route matching the request, with what it captured.
+    fn find(&self, request: &HandshakeRequest) -> Option<(&Route<S>, Params)> {
+        let path = request.path.split('?').next().unwrap_or_default();
+        let mut best: Option<(&Route<S>, Pairs)> = None;
+        for route in &self.routes {
+            let Some(captures) = route.pattern.matches(path) else {
+                continue;
+            };
+            if best
+                .as_ref()
+                .is_none_or(|(b, _)| route.pattern.ranks().gt(b.pattern.ranks()))
+            {
+                best = Some((route, captures));
+            }
+        }
+        best.map(|(route, path)| {
+            let query = request.query_params();
+            (route, Params { path, query })
+        })
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Router<S> {
+    /// Perform the handshake on `stream`, then run the matching route's
+    /// handler until it returns.
+    ///
+    /// # Errors
+    ///
+    /// - `Error::HandshakeRejected` with status 404 if no route matches
+    /// - Any error from [`accept`](super::accept), checked against the
+    ///   route's policy
+    pub async fn serve_connection(&self, stream: S, config: Config) -> Result<()> {
+        self.dispatch(stream, config, ExtensionRegistry::new(), None, None)
+            .await
+    }
+
+    /// Like [`Self::serve_connection`], negotiating extensions from
+    /// `extensions`.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::serve_connection`], plus `Error::InvalidExtension` if
+    /// the extension header is malformed.
+    pub async fn serve_connection_with_extensions(
+        &self,
+        stream: S,
+        config: Config,
+        extensions: ExtensionRegistry,
+    ) -> Result<()> {
+        self.dispatch(stream, config, extensions, None, None).await
+    }
+
+    /// `handshake_timeout` overrides `config.timeouts.handshake`.
+    pub(crate) async fn dispatch(
+        &self,
+        stream: S,
+        config: Config,
+        extensions: ExtensionRegistry,
+        handshake_timeout: Option<Duration>,
+        shutdown: Option<ShutdownSignal>,
+    ) -> Result<()> {
+        let timeout = handshake_timeout.or_else(|| config.timeouts.as_ref().map(|t| t.handshake));
+        let mut matched = None;
+        let (conn, _) = accept_routed(
+            stream,
+            config,
+            extensions,
+            timeout,
+            |request, config| {
+                let (route, params) = self
+                    .find(request)
+                    .ok_or_else(|| Rejection::new(404, "Not Found"))?;
+                matched = Some((Arc::clone(&route.handler), params));
+                Ok(route.policy.apply(config))
+            },
+            |_, _| Ok(()),
+        )
+        .await?;
+
+        let (handler, params) = matched.expect("a route matched before the upgrade");
+        let conn = match shutdown {
+            Some(signal) => conn.with_shutdown(signal),
+            None => conn,
+        };
+        handler(conn, params).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientBuilder;
+    use crate::error::Error;
+    use crate::message::Message;
+    use tokio::io::{DuplexStream, duplex};
+    use tokio::sync::mpsc;
+
+    fn request(path: &str) -> HandshakeRequest {
+        let head = format!(
+            "GET {} HTTP/1.1\r\n\
+             Host: localhost\r\n\
+             Upgrade: websocket\r\n\
+             Connection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Version: 13\r\n\r\n",
+            path
+        );
+        HandshakeRequest::parse(head.as_bytes()).unwrap()
+    }
+
+    fn captures(template: &str, path: &str) -> Option<Vec<(String, String)>> {
+        Pattern::parse(template).unwrap().matches(path)
+    }
+
+    fn pair(name: &str, value: &str) -> (String, String) {
+        (name.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn test_pattern_literals() {
+        assert_eq!(captures("/", "/"), Some(vec![]));
+        assert_eq!(captures("/files", "/files"), Some(vec![]));
+        assert_eq!(captures("/files", "/files/"), None);
+        assert_eq!(captures("/files/", "/files/"), Some(vec![]));
+        assert_eq!(captures("/files", "/other"), None);
+        assert_eq!(captures("/", "/files"), None);
+    }
+
+    #[test]
+    fn test_pattern_captures() {
+        assert_eq!(
+            captures("/chat/:room", "/chat/lobby"),
+            Some(vec![pair("room", "lobby")])
+        );
+        assert_eq!(
+            captures("/chat/:room/:user", "/chat/a%20b/c+d"),
+            Some(vec![pair("room", "a b"), pair("user", "c+d")])
+        );
+        assert_eq!(captures("/chat/:room", "/chat/"), None);
+        assert_eq!(captures("/chat/:room", "/chat"), None);
+        assert_eq!(captures("/chat/:room", "/chat/a/b"), None);
+    }
+
+    #[test]
+    fn test_pattern_wildcard() {
+        assert_eq!(
+            captures("/files/*path", "/files/a/b%2Ec"),
+            Some(vec![pair("path", "a/b.c")])
+        );
+        assert_eq!(
+            captures("/files/*path", "/files"),
+            Some(vec![pair("path", "")])
+        );
+        assert_eq!(captures("/files/*path", "/other/a"), None);
+    }
+
+    #[test]
+    fn test_invalid_templates() {
+        assert!(Pattern::parse("chat").is_err());
+        assert!(Pattern::parse("/files/*path/more").is_err());
+        assert!(Pattern::parse("/chat/:").is_err());
+        assert!(Pattern::parse("/a/:id/b/:id").is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid route template")]
+    fn test_route_panics_on_invalid_template() {
+        let _ = Router::<DuplexStream>::new().route("no-slash", |_, _| async {});
+    }
+
+    #[test]
+    fn test_most_specific_route_wins() {
+        let router = Router::<DuplexStream>::new()
+            .route("/files/*path", |_, _| async {})
+            .route("/files/:name", |_, _| async {})
+            .route("/files/index", |_, _| async {})
+            .route("/files", |_, _| async {});
+
+        let template = |path: &str| {
+            router
+                .find(&request(path))
+                .map(|(route, _)| route.pattern.template.clone())
+        };
+        assert_eq!(template("/files/index").as_deref(), Some("/files/index"));
+        assert_eq!(template("/files/a").as_deref(), Some("/files/:name"));
+        assert_eq!(template("/files/a/b").as_deref(), Some("/files/*path"));
+        assert_eq!(template("/files").as_deref(), Some("/files"));
+        assert_eq!(template("/files/").as_deref(), Some("/files/*path"));
+        assert_eq!(template("/chat"), None);
+    }
+
+    #[test]
+    fn test_params_include_query() {
+        let router = Router::<DuplexStream>::new().route("/chat/:room", |_, _| async {});
+        let (_, params) = router
+            .find(&request("/chat/lobby?token=a%2Bb&lang=en"))
+            .unwrap();
+        assert_eq!(params.get("room"), Some("lobby"));
+        assert_eq!(params.get("token"), None);
+        assert_eq!(params.query_param("token"), Some("a+b"));
+        assert_eq!(params.query_params().len(), 2);
+        assert_eq!(params.iter().collect::<Vec<_>>(), vec![("room", "lobby")]);
+    }
+
+    #[test]
+    fn test_policy_overrides_config() {
+        let config = Config::server()
+            .with_subprotocol_selector(|offered| offered.first().cloned())
+            .with_allowed_origins(vec!["https://a.example".to_string()]);
+        let limits = Limits::new(1024, 4096, 8, 8192);
+        let applied = RoutePolicy::new()
+            .with_subprotocols(["files.v1"])
+            .with_require_subprotocol(true)
+            .with_limits(limits.clone())
+            .apply(config);
+
+        assert_eq!(applied.subprotocols, vec!["files.v1".to_string()]);
+        assert!(applied.subprotocol_selector.is_none());
+        assert!(applied.require_subprotocol);
+        assert_eq!(applied.limits, limits);
+        // Unset settings keep the server's values.
+        assert_eq!(
+            applied.allowed_origins,
+            Some(vec!["https://a.example".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_passes_captures() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let router = Router::new().route("/chat/:room", move |mut conn, params| {
+            let tx = tx.clone();
+            async move {
+                let room = params.get("room").unwrap_or_default().to_string();
+                let _ = conn.send(Message::text(format!("joined {}", room))).await;
+                let _ = tx.send(params);
+            }
+        });
+
+        let (client_io, server_io) = duplex(4096);
+        let server =
+            tokio::spawn(async move { router.serve_connection(server_io, Config::server()).await });
+        let (mut client, _) = ClientBuilder::new("ws://localhost/chat/lobby?nick=ann")
+            .unwrap()
+            .handshake(client_io)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.recv().await.unwrap(),
+            Some(Message::text("joined lobby"))
+        );
+        server.await.unwrap().unwrap();
+        let params = rx.recv().await.unwrap();
+        assert_eq!(params.query_param("nick"), Some("ann"));
+    }
+
+    #[tokio::test]
+    async fn test_serve_connection_unknown_path() {
+        let router = Router::new().route("/chat/:room", |_, _| async {});
+
+        let (client_io, server_io) = duplex(4096);
+        let server =
+            tokio::spawn(async move { router.serve_connection(server_io, Config::server()).await });
+        let result = ClientBuilder::new("ws://localhost/files")
+            .unwrap()
+            .handshake(client_io)
+            .await;
+
+        assert!(result.is_err());
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(Error::HandshakeRejected { status: 404, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_route_policy_checks_origin_and_subprotocol() {
+        let policy = RoutePolicy::new()
+            .with_allowed_origins(vec!["https://app.example".to_string()])
+            .with_subprotocols(["files.v1"]);
+        let router = Arc::new(
+            Router::new()
+                .route_with("/files", policy, |mut conn, _| async move {
+                    let protocol = conn.subprotocol().unwrap_or_default().to_string();
+                    let _ = conn.send(Message::text(protocol)).await;
+                })
+                .route("/open", |_, _| async {}),
+        );
+
+        let serve = |router: Arc<Router<DuplexStream>>, io| {
+            tokio::spawn(async move { router.serve_connection(io, Config::server()).await })
+        };
+
+        // Wrong origin for the route.
+        let (client_io, server_io) = duplex(4096);
+        let server = serve(Arc::clone(&router), server_io);
+        let _ = ClientBuilder::new("ws://localhost/files")
+            .unwrap()
+            .with_origin("https://evil.example")
+            .handshake(client_io)
+            .await;
+        assert!(matches!(
+            server.await.unwrap(),
+            Err(Error::OriginNotAllowed { .. })
+        ));
+
+        // The other route keeps the server's open policy.
+        let (client_io, server_io) = duplex(4096);
+        let server = serve(Arc::clone(&router), server_io);
+        ClientBuilder::new("ws://localhost/open")
+            .unwrap()
+            .with_origin("https://evil.example")
+            .handshake(client_io)
+            .await
+            .unwrap();
+        server.await.unwrap().unwrap();
+
+        // Allowed origin, the route's subprotocol is selected.
+        let (client_io, server_io) = duplex(4096);
+        let server = serve(Arc::clone(&router), server_io);
+        let (mut client, _) = ClientBuilder::new("ws://localhost/files")
+            .unwrap()
+            .with_origin("https://app.example")
+            .with_subprotocols(["chat", "files.v1"])
+            .handshake(client_io)
+            .await
+            .unwrap();
+        assert_eq!(client.subprotocol(), Some("files.v1"));
+        assert_eq!(
+            client.recv().await.unwrap(),
+            Some(Message::text("files.v1"))
+        );
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_route_policy_limits_apply_to_connection() {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let limits = Limits::new(16, 16, 4, 8192);
+        let router = Router::new().route_with(
+            "/small",
+            RoutePolicy::new().with_limits(limits),
+            move |mut conn, _| {
+                let tx = tx.clone();
+                async move {
+                    let _ = tx.send(conn.recv().await);
+                }
+            },
+        );
+
+        let (client_io, server_io) = duplex(4096);
+        let server =
+            tokio::spawn(async move { router.serve_connection(server_io, Config::server()).await });
+        let (mut client, _) = ClientBuilder::new("ws://localhost/small")
+            .unwrap()
+            .handshake(client_io)
+            .await
+            .unwrap();
+
+        client.send(Message::text("x".repeat(64))).await.unwrap();
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            Err(Error::FrameTooLarge { size: 64, max: 16 })
+        );
+        server.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,635 @@
+//! Standalone server: accept loop, routing and graceful shutdown.
+
+use std::fmt;
+use std::future::{Future, pending, poll_fn};
+use std::io;
+use std::pin::{Pin, pin};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+use super::router::{Params, RoutePolicy, Router};
+use crate::config::Config;
+use crate::connection::{Connection, PendingCloses, ShutdownSignal};
+use crate::error::Result;
+use crate::extensions::ExtensionRegistry;
+
+#[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+use crate::tls::{Acceptor, MaybeTlsStream};
+
+/// How long [`Server::serve`] waits for connections to close by default.
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a client may take to finish the TLS and WebSocket handshakes
+/// when `config.timeouts` is unset.
+const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Pause after a failed `accept`, so running out of file descriptors does
+/// not turn the accept loop into a busy loop.
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// How long shutdown waits, once the shutdown timeout has passed, for close
+/// frames that connections already queued to be written.
+const GOING_AWAY_FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+type ExtensionFactory = Arc<dyn Fn() -> ExtensionRegistry + Send + Sync>;
+
+/// The stream under a [`Connection`] accepted by [`Server`]: plain TCP, or
+/// TLS when the server was given an acceptor.
+///
+/// With a TLS feature enabled this wraps a
+/// `MaybeTlsStream<TcpStream>`, reachable with `as_maybe_tls`.
+pub struct ServerStream(Inner);
+
+#[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+type Inner = MaybeTlsStream<TcpStream>;
+#[cfg(not(any(feature = "tls-rustls", feature = "tls-native")))]
+type Inner = TcpStream;
+
+#[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+impl From<MaybeTlsStream<TcpStream>> for ServerStream {
+    fn from(stream: MaybeTlsStream<TcpStream>) -> Self {
+        Self(stream)
+    }
+}
+
+impl From<TcpStream> for ServerStream {
+    fn from(stream: TcpStream) -> Self {
+        #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+        let stream = MaybeTlsStream::Plain(stream);
+        Self(stream)
+    }
+}
+
+impl ServerStream {
+    /// The remote address of the TCP connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O error from the socket, e.g. if it is disconnected.
+    pub fn peer_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.0.peer_addr()
+    }
+
+    /// Returns `true` if the connection was accepted with TLS.
+    pub fn is_tls(&self) -> bool {
+        #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+        {
+            self.0.is_tls()
+        }
+        #[cfg(not(any(feature = "tls-rustls", feature = "tls-native")))]
+        {
+            false
+        }
+    }
+
+    /// The TLS stream, or the plain one for connections without TLS.
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+    pub fn as_maybe_tls(&self) -> &MaybeTlsStream<TcpStream> {
+        &self.0
+    }
+
+    /// The client's verified certificate chain, when the server requires or
+    /// accepts client certificates.
+    #[cfg(feature = "tls-rustls")]
+    pub fn peer_certificates(&self) -> Option<&[rustls::pki_types::CertificateDer<'static>]> {
+        self.0.peer_certificates()
+    }
+
+    /// The server name the client asked for via SNI.
+    #[cfg(feature = "tls-rustls")]
+    pub fn sni_hostname(&self) -> Option<&str> {
+        self.0.sni_hostname()
+    }
+}
+
+impl AsyncRead for ServerStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ServerStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0).poll_shutdown(cx)
+    }
+}
+
+/// A WebSocket server that owns the accept loop.
+///
+/// Each accepted TCP connection gets its own task, which performs the TLS
+/// handshake (if configured) and the WebSocket handshake, then runs the
+/// handler whose route matches the request path. Routes are path templates
+/// as described on [`Router`]; requests for unknown paths are answered with
+/// `404 Not Found`.
+///
+/// On shutdown the server stops accepting, every live connection sends
+/// `CloseCode::GoingAway` the next time it sends or waits in `recv`, and the
+/// server waits up to the shutdown timeout for the closing handshakes to
+/// finish. Before aborting the remaining tasks it lets the `GoingAway` frames
+/// already queued be written.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// use rsws::{Config, Server};
+///
+/// let listener = tokio::net::TcpListener::bind("127.0.0.1:9001").await?;
+/// Server::new(Config::server())
+///     .route("/echo", |mut conn, _params| async move {
+///         while let Ok(Some(msg)) = conn.recv().await {
+///             if msg.is_text() || msg.is_binary() {
+///                 let _ = conn.send(msg).await;
+///             }
+///         }
+///     })
+///     .with_max_connections(1024)
+///     .serve_with_shutdown(listener, async {
+///         let _ = tokio::signal::ctrl_c().await;
+///     })
+///     .await;
+/// ```
+pub struct Server {
+    config: Config,
+    router: Router<ServerStream>,
+    extensions: Option<ExtensionFactory>,
+    max_connections: Option<usize>,
+    handshake_timeout: Duration,
+    shutdown_timeout: Duration,
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+    tls: Option<Acceptor>,
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("config", &self.config)
+            .field("router", &self.router)
+            .field("max_connections", &self.max_connections)
+            .field("handshake_timeout", &self.handshake_timeout())
+            .field("shutdown_timeout", &self.shutdown_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Server {
+    /// Create a server with no routes.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            router: Router::new(),
+            extensions: None,
+            max_connections: None,
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+            tls: None,
+        }
+    }
+
+    /// Run `handler` for upgrade requests whose path matches `template`.
+    ///
+    /// See [`Router::route`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` is not a valid route template.
+    #[must_use]
+    pub fn route<F, Fut>(mut self, template: &str, handler: F) -> Self
+    where
+        F: Fn(Connection<ServerStream>, Params) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.router = self.router.route(template, handler);
+        self
+    }
+
+    /// Like [`Self::route`], with a policy for the route's handshakes.
+    ///
+    /// See [`Router::route_with`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `template` is not a valid route template.
+    #[must_use]
+    pub fn route_with<F, Fut>(mut self, template: &str, policy: RoutePolicy, handler: F) -> Self
+    where
+        F: Fn(Connection<ServerStream>, Params) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.router = self.router.route_with(template, policy, handler);
+        self
+    }
+
+    /// Replace the server's routes with `router`.
+    #[must_use]
+    pub fn with_router(mut self, router: Router<ServerStream>) -> Self {
+        self.router = router;
+        self
+    }
+
+    /// Negotiate extensions from a registry built by `factory` for each
+    /// connection.
+    #[must_use]
+    pub fn with_extensions<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> ExtensionRegistry + Send + Sync + 'static,
+    {
+        self.extensions = Some(Arc::new(factory));
+        self
+    }
+
+    /// Serve at most `max` connections at a time.
+    ///
+    /// Once the limit is reached the server stops accepting; further clients
+    /// wait in the listen backlog until a connection ends.
+    #[must_use]
+    pub fn with_max_connections(mut self, max: usize) -> Self {
+        self.max_connections = Some(max);
+        self
+    }
+
+    /// How long a client may take to finish the TLS and WebSocket
+    /// handshakes before it is disconnected, when `config.timeouts` is unset
+    /// (default 10 seconds). Otherwise `timeouts.handshake` applies.
+    ///
+    /// Without a deadline, clients that connect and send nothing would hold
+    /// their slots under [`Self::with_max_connections`] forever.
+    #[must_use]
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// How long shutdown waits for closing handshakes before aborting the
+    /// remaining connections (default 10 seconds).
+    #[must_use]
+    pub fn with_shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = timeout;
+        self
+    }
+
+    /// Accept `wss://` connections, running the TLS handshake with `acceptor`
+    /// before the WebSocket handshake. Takes a rustls `TlsAcceptor`, a
+    /// `NativeTlsAcceptor` or an [`Acceptor`].
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+    #[must_use]
+    pub fn with_tls(mut self, acceptor: impl Into<Acceptor>) -> Self {
+        self.tls = Some(acceptor.into());
+        self
+    }
+
+    /// Bind a listener to `addr` and serve connections forever.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Io` if the address cannot be bound.
+    pub async fn listen(self, addr: impl ToSocketAddrs) -> Result<()> {
+        let listener = TcpListener::bind(addr).await?;
+        self.serve(listener).await;
+        Ok(())
+    }
+
+    /// Serve connections from `listener` forever.
+    pub async fn serve(self, listener: TcpListener) {
+        self.serve_with_shutdown(listener, pending()).await;
+    }
+
+    /// Serve connections from `listener` until `signal` completes, then shut
+    /// down gracefully.
+    ///
+    /// Returns once every connection has closed or the shutdown timeout has
+    /// passed, whichever comes first.
+    pub async fn serve_with_shutdown<F>(self, listener: TcpListener, signal: F)
+    where
+        F: Future<Output = ()> + Send,
+    {
+        let server = Arc::new(self);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let pending_closes = Arc::new(PendingCloses::default());
+        let mut tasks = JoinSet::new();
+        let mut signal = pin!(signal);
+
+        loop {
+            let accepted = poll_fn(|cx| {
+                // Reap finished connections; this also wakes the loop when
+                // a slot frees up under `max_connections`.
+                while let Poll::Ready(Some(_)) = tasks.poll_join_next(cx) {}
+
+                if signal.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(None);
+                }
+                if server.max_connections.is_some_and(|max| tasks.len() >= max) {
+                    return Poll::Pending;
+                }
+                listener.poll_accept(cx).map(Some)
+            })
+            .await;
+
+            match accepted {
+                Some(Ok((stream, _))) => {
+                    let shutdown =
+                        ShutdownSignal::new(shutdown_rx.clone(), Arc::clone(&pending_closes));
+                    tasks.spawn(Arc::clone(&server).handle(stream, shutdown));
+                }
+                Some(Err(_)) => tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await,
+                None => break,
+            }
+        }
+
+        drop(listener);
+        let _ = shutdown_tx.send(true);
+        let drained = tokio::time::timeout(server.shutdown_timeout, async {
+            while tasks.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            // Aborting a task mid-write would cut its close frame short.
+            let _ = tokio::time::timeout(GOING_AWAY_FLUSH_TIMEOUT, pending_closes.wait()).await;
+            tasks.shutdown().await;
+        }
+    }
+
+    /// Run the handshakes for one accepted connection, then its handler.
+    async fn handle(self: Arc<Self>, stream: TcpStream, shutdown: ShutdownSignal) {
+        let Some(stream) = self.secure(stream).await else {
+            return;
+        };
+
+        let extensions = self
+            .extensions
+            .as_ref()
+            .map_or_else(ExtensionRegistry::new, |factory| factory());
+        let _ = self
+            .router
+            .dispatch(
+                stream,
+                self.config.clone(),
+                extensions,
+                Some(self.handshake_timeout()),
+                Some(shutdown),
+            )
+            .await;
+    }
+
+    /// The deadline for the handshakes that precede the upgrade.
+    fn handshake_timeout(&self) -> Duration {
+        self.config
+            .timeouts
+            .as_ref()
+            .map_or(self.handshake_timeout, |t| t.handshake)
+    }
+
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native"))]
+    async fn secure(&self, stream: TcpStream) -> Option<ServerStream> {
+        let Some(acceptor) = self.tls.as_ref() else {
+            return Some(stream.into());
+        };
+        let tls = tokio::time::timeout(self.handshake_timeout(), acceptor.accept(stream))
+            .await
+            .ok()?;
+        tls.ok().map(ServerStream::from)
+    }
+
+    #[cfg(not(any(feature = "tls-rustls", feature = "tls-native")))]
+    async fn secure(&self, stream: TcpStream) -> Option<ServerStream> {
+        Some(stream.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientBuilder;
+    use crate::error::Error;
+    use crate::message::{CloseCode, Message};
+    use std::net::SocketAddr;
+    use tokio::io::AsyncReadExt;
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+    use tokio::time::Instant;
+
+    async fn echo(mut conn: Connection<ServerStream>, _params: Params) {
+        while let Ok(Some(msg)) = conn.recv().await {
+            if msg.is_text() {
+                let _ = conn.send(msg).await;
+            }
+        }
+    }
+
+    async fn start(server: Server) -> (SocketAddr, oneshot::Sender<()>, JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop_tx, stop_rx) = oneshot::channel::<()>();
+        let task = tokio::spawn(server.serve_with_shutdown(listener, async {
+            let _ = stop_rx.await;
+        }));
+        (addr, stop_tx, task)
+    }
+
+    fn url(addr: SocketAddr, path: &str) -> String {
+        format!("ws://{}{}", addr, path)
+    }
+
+    #[tokio::test]
+    async fn test_routes_by_path() {
+        let server = Server::new(Config::server()).route("/echo", echo).route(
+            "/hello",
+            |mut conn, _| async move {
+                let _ = conn.send(Message::text("hello")).await;
+            },
+        );
+        let (addr, _stop, _task) = start(server).await;
+
+        let (mut conn, _) = ClientBuilder::new(&url(addr, "/echo?room=1"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        conn.send(Message::text("ping")).await.unwrap();
+        assert_eq!(conn.recv().await.unwrap(), Some(Message::text("ping")));
+
+        let (mut conn, _) = ClientBuilder::new(&url(addr, "/hello"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        assert_eq!(conn.recv().await.unwrap(), Some(Message::text("hello")));
+    }
+
+    #[tokio::test]
+    async fn test_unknown_path_is_not_found() {
+        let (addr, _stop, _task) = start(Server::new(Config::server()).route("/echo", echo)).await;
+
+        let result = ClientBuilder::new(&url(addr, "/missing"))
+            .unwrap()
+            .connect()
+            .await;
+        let Err(Error::InvalidHandshake(msg)) = result else {
+            panic!("expected the handshake to be refused");
+        };
+        assert!(msg.contains("404 Not Found"));
+    }
+
+    #[tokio::test]
+    async fn test_max_connections_holds_back_clients() {
+        let server = Server::new(Config::server())
+            .route("/echo", echo)
+            .with_max_connections(1);
+        let (addr, _stop, _task) = start(server).await;
+
+        let (mut first, _) = ClientBuilder::new(&url(addr, "/echo"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        let second = tokio::spawn(ClientBuilder::new(&url(addr, "/echo")).unwrap().connect());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!second.is_finished());
+
+        first
+            .close_and_wait(CloseCode::Normal, "", Duration::from_secs(1))
+            .await
+            .unwrap();
+        let (mut second, _) = second.await.unwrap().unwrap();
+        second.send(Message::text("next")).await.unwrap();
+        assert_eq!(second.recv().await.unwrap(), Some(Message::text("next")));
+    }
+
+    #[tokio::test]
+    async fn test_silent_client_times_out() {
+        let server = Server::new(Config::server())
+            .route("/echo", echo)
+            .with_max_connections(1)
+            .with_handshake_timeout(Duration::from_millis(100));
+        let (addr, _stop, _task) = start(server).await;
+
+        // Takes the only slot and never sends a request.
+        let mut silent = tokio::net::TcpStream::connect(addr).await.unwrap();
+
+        let (mut conn, _) = tokio::time::timeout(
+            Duration::from_secs(5),
+            ClientBuilder::new(&url(addr, "/echo")).unwrap().connect(),
+        )
+        .await
+        .expect("the silent client must lose its slot")
+        .unwrap();
+        conn.send(Message::text("ping")).await.unwrap();
+        assert_eq!(conn.recv().await.unwrap(), Some(Message::text("ping")));
+
+        // The server hung up on the silent client.
+        let mut buf = [0u8; 1];
+        assert_eq!(silent.read(&mut buf).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_closes_connections_with_going_away() {
+        let server = Server::new(Config::server())
+            .route("/echo", echo)
+            .with_shutdown_timeout(Duration::from_secs(5));
+        let (addr, stop, task) = start(server).await;
+
+        let mut clients = Vec::new();
+        for _ in 0..3 {
+            let (conn, _) = ClientBuilder::new(&url(addr, "/echo"))
+                .unwrap()
+                .connect()
+                .await
+                .unwrap();
+            clients.push(conn);
+        }
+
+        let start = Instant::now();
+        stop.send(()).unwrap();
+        for conn in &mut clients {
+            let Some(Message::Close(Some(frame))) = conn.recv().await.unwrap() else {
+                panic!("expected a close frame");
+            };
+            assert_eq!(frame.code, CloseCode::GoingAway);
+        }
+
+        // Every handshake completed, so the server does not wait for the deadline.
+        task.await.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_aborts_after_timeout() {
+        let server = Server::new(Config::server())
+            .route("/stuck", |_conn, _| pending::<()>())
+            .with_shutdown_timeout(Duration::from_millis(100));
+        let (addr, stop, task) = start(server).await;
+
+        let (_conn, _) = ClientBuilder::new(&url(addr, "/stuck"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+
+        let start = Instant::now();
+        stop.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("shutdown must finish at the deadline")
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_sends_going_away_from_send_only_handler() {
+        // Pushes updates without ever calling `recv`, then hangs once closed.
+        let server = Server::new(Config::server())
+            .route("/feed", |mut conn, _| async move {
+                while conn.send(Message::text("tick")).await.is_ok() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+                pending::<()>().await;
+            })
+            .with_shutdown_timeout(Duration::from_millis(100));
+        let (addr, stop, task) = start(server).await;
+
+        let (mut conn, _) = ClientBuilder::new(&url(addr, "/feed"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap();
+        assert_eq!(conn.recv().await.unwrap(), Some(Message::text("tick")));
+
+        stop.send(()).unwrap();
+        let frame = loop {
+            match conn.recv().await.unwrap() {
+                Some(Message::Text(_)) => {}
+                Some(Message::Close(Some(frame))) => break frame,
+                other => panic!("unexpected message: {:?}", other),
+            }
+        };
+        assert_eq!(frame.code, CloseCode::GoingAway);
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("shutdown must finish at the deadline")
+            .unwrap();
+    }
+}
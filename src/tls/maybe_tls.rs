@@ -0,0 +1,366 @@
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+
+use crate::error::{Error, Result};
+
+#[cfg(feature = "tls-native")]
+use super::native::{NativeTlsAcceptor, NativeTlsConnector, NativeTlsError, NativeTlsStream};
+#[cfg(feature = "tls-rustls")]
+use super::rustls_impl::{TlsAcceptor, TlsConnector, TlsError, TlsStream};
+
+/// A stream that is either plain or wrapped in TLS by one of the enabled
+/// backends.
+///
+/// Code that only reads and writes does not need to know which backend
+/// (if any) secured the connection.
+// Unboxed like `TlsStream`: there is one per connection, and callers match
+// on the variants to reach backend-specific accessors.
+#[allow(clippy::large_enum_variant)]
+pub enum MaybeTlsStream<S> {
+    /// No TLS (`ws://`).
+    Plain(S),
+    /// TLS via rustls.
+    #[cfg(feature = "tls-rustls")]
+    Rustls(TlsStream<S>),
+    /// TLS via native-tls.
+    #[cfg(feature = "tls-native")]
+    NativeTls(NativeTlsStream<S>),
+}
+
+impl<S> MaybeTlsStream<S> {
+    /// Returns `true` if the stream is wrapped in TLS.
+    pub fn is_tls(&self) -> bool {
+        !matches!(self, MaybeTlsStream::Plain(_))
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        match self {
+            MaybeTlsStream::Plain(s) => s,
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Rustls(s) => s.get_ref(),
+            #[cfg(feature = "tls-native")]
+            MaybeTlsStream::NativeTls(s) => s.get_ref(),
+        }
+    }
+
+    /// The client's verified certificate chain (rustls only).
+    #[cfg(feature = "tls-rustls")]
+    pub fn peer_certificates(&self) -> Option<&[rustls::pki_types::CertificateDer<'static>]> {
+        match self {
+            MaybeTlsStream::Rustls(s) => s.peer_certificates(),
+            _ => None,
+        }
+    }
+
+    /// The server name the client asked for via SNI (rustls only).
+    #[cfg(feature = "tls-rustls")]
+    pub fn sni_hostname(&self) -> Option<&str> {
+        match self {
+            MaybeTlsStream::Rustls(s) => s.sni_hostname(),
+            _ => None,
+        }
+    }
+
+    /// The protocol agreed on via ALPN (rustls only).
+    #[cfg(feature = "tls-rustls")]
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        match self {
+            MaybeTlsStream::Rustls(s) => s.alpn_protocol(),
+            _ => None,
+        }
+    }
+
+    /// The negotiated TLS version (rustls only).
+    #[cfg(feature = "tls-rustls")]
+    pub fn protocol_version(&self) -> Option<rustls::ProtocolVersion> {
+        match self {
+            MaybeTlsStream::Rustls(s) => s.protocol_version(),
+            _ => None,
+        }
+    }
+
+    /// The negotiated cipher suite (rustls only).
+    #[cfg(feature = "tls-rustls")]
+    pub fn cipher_suite(&self) -> Option<rustls::SupportedCipherSuite> {
+        match self {
+            MaybeTlsStream::Rustls(s) => s.cipher_suite(),
+            _ => None,
+        }
+    }
+}
+
+impl MaybeTlsStream<TcpStream> {
+    /// The remote address of the TCP connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O error from the socket, e.g. if it is disconnected.
+    pub fn peer_addr(&self) -> io::Result<std::net::SocketAddr> {
+        self.get_ref().peer_addr()
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for MaybeTlsStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTlsStream::Plain(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Rustls(s) => Pin::new(s).poll_read(cx, buf),
+            #[cfg(feature = "tls-native")]
+            MaybeTlsStream::NativeTls(s) => Pin::new(s).poll_read(cx, buf),
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for MaybeTlsStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            MaybeTlsStream::Plain(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Rustls(s) => Pin::new(s).poll_write(cx, buf),
+            #[cfg(feature = "tls-native")]
+            MaybeTlsStream::NativeTls(s) => Pin::new(s).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTlsStream::Plain(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Rustls(s) => Pin::new(s).poll_flush(cx),
+            #[cfg(feature = "tls-native")]
+            MaybeTlsStream::NativeTls(s) => Pin::new(s).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            MaybeTlsStream::Plain(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(feature = "tls-rustls")]
+            MaybeTlsStream::Rustls(s) => Pin::new(s).poll_shutdown(cx),
+            #[cfg(feature = "tls-native")]
+            MaybeTlsStream::NativeTls(s) => Pin::new(s).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Client-side TLS for whichever backend is enabled.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// use rsws::tls::Connector;
+///
+/// let connector = Connector::new()?;
+/// let stream = connector.connect("example.com", tcp_stream).await?;
+/// let (conn, _) = ClientBuilder::new("wss://example.com/chat")?
+///     .handshake(stream)
+///     .await?;
+/// ```
+#[derive(Clone)]
+pub enum Connector {
+    /// Leave the stream as it is.
+    Plain,
+    /// Connect with rustls.
+    #[cfg(feature = "tls-rustls")]
+    Rustls(TlsConnector),
+    /// Connect with native-tls.
+    #[cfg(feature = "tls-native")]
+    NativeTls(NativeTlsConnector),
+}
+
+impl fmt::Debug for Connector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Connector::Plain => "Connector::Plain",
+            #[cfg(feature = "tls-rustls")]
+            Connector::Rustls(_) => "Connector::Rustls",
+            #[cfg(feature = "tls-native")]
+            Connector::NativeTls(_) => "Connector::NativeTls",
+        })
+    }
+}
+
+impl Connector {
+    /// A connector for the default backend: rustls trusting the bundled
+    /// Mozilla roots when `tls-rustls` is enabled, otherwise native-tls
+    /// trusting the platform store.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Tls` if the backend cannot be initialized.
+    pub fn new() -> Result<Self> {
+        #[cfg(feature = "tls-rustls")]
+        {
+            let config = super::TlsClientConfigBuilder::new()
+                .with_webpki_roots()
+                .build()?;
+            Ok(Connector::Rustls(TlsConnector::new(config)))
+        }
+        #[cfg(not(feature = "tls-rustls"))]
+        {
+            let connector = native_tls::TlsConnector::new().map_err(NativeTlsError::Tls)?;
+            Ok(Connector::NativeTls(NativeTlsConnector::new(connector)))
+        }
+    }
+
+    /// Run the client side of the TLS handshake over `stream`, verifying
+    /// the server's certificate for `domain`. [`Connector::Plain`] returns
+    /// the stream unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Tls` if the handshake fails.
+    pub async fn connect<S>(&self, domain: &str, stream: S) -> Result<MaybeTlsStream<S>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        match self {
+            Connector::Plain => Ok(MaybeTlsStream::Plain(stream)),
+            #[cfg(feature = "tls-rustls")]
+            Connector::Rustls(connector) => Ok(MaybeTlsStream::Rustls(
+                connector.connect(domain, stream).await?,
+            )),
+            #[cfg(feature = "tls-native")]
+            Connector::NativeTls(connector) => Ok(MaybeTlsStream::NativeTls(
+                connector.connect(domain, stream).await?,
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "tls-rustls")]
+impl From<TlsConnector> for Connector {
+    fn from(connector: TlsConnector) -> Self {
+        Connector::Rustls(connector)
+    }
+}
+
+#[cfg(feature = "tls-native")]
+impl From<NativeTlsConnector> for Connector {
+    fn from(connector: NativeTlsConnector) -> Self {
+        Connector::NativeTls(connector)
+    }
+}
+
+/// Server-side TLS for whichever backend is enabled.
+///
+/// [`Server::with_tls`](crate::Server::with_tls) takes anything that
+/// converts into one, so a rustls `TlsAcceptor` and a `NativeTlsAcceptor`
+/// plug in the same way.
+#[derive(Clone)]
+pub enum Acceptor {
+    /// Accept with rustls.
+    #[cfg(feature = "tls-rustls")]
+    Rustls(TlsAcceptor),
+    /// Accept with native-tls.
+    #[cfg(feature = "tls-native")]
+    NativeTls(NativeTlsAcceptor),
+}
+
+impl Acceptor {
+    /// Run the server side of the TLS handshake over `stream`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::Tls` if the handshake fails.
+    pub async fn accept<S>(&self, stream: S) -> Result<MaybeTlsStream<S>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        match self {
+            #[cfg(feature = "tls-rustls")]
+            Acceptor::Rustls(acceptor) => {
+                Ok(MaybeTlsStream::Rustls(acceptor.accept(stream).await?))
+            }
+            #[cfg(feature = "tls-native")]
+            Acceptor::NativeTls(acceptor) => {
+                Ok(MaybeTlsStream::NativeTls(acceptor.accept(stream).await?))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tls-rustls")]
+impl From<TlsAcceptor> for Acceptor {
+    fn from(acceptor: TlsAcceptor) -> Self {
+        Acceptor::Rustls(acceptor)
+    }
+}
+
+#[cfg(feature = "tls-native")]
+impl From<NativeTlsAcceptor> for Acceptor {
+    fn from(acceptor: NativeTlsAcceptor) -> Self {
+        Acceptor::NativeTls(acceptor)
+    }
+}
+
+#[cfg(feature = "tls-rustls")]
+impl From<TlsError> for Error {
+    fn from(err: TlsError) -> Self {
+        Error::Tls(err.to_string())
+    }
+}
+
+#[cfg(feature = "tls-native")]
+impl From<NativeTlsError> for Error {
+    fn from(err: NativeTlsError) -> Self {
+        Error::Tls(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_plain_connector_passes_stream_through() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let mut stream = Connector::Plain
+            .connect("example.com", client)
+            .await
+            .unwrap();
+        assert!(!stream.is_tls());
+
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        stream.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[test]
+    fn test_default_connector() {
+        let connector = Connector::new().unwrap();
+        #[cfg(feature = "tls-rustls")]
+        assert!(matches!(connector, Connector::Rustls(_)));
+        #[cfg(not(feature = "tls-rustls"))]
+        assert!(matches!(connector, Connector::NativeTls(_)));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_failure_is_tls_error() {
+        // The peer hangs up before answering the ClientHello.
+        let (client, server) = tokio::io::duplex(1024);
+        drop(server);
+        let result = Connector::new()
+            .unwrap()
+            .connect("example.com", client)
+            .await;
+        assert!(matches!(result, Err(Error::Tls(_))));
+    }
+}
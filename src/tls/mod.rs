@@ -5,19 +5,46 @@
 //!
 //! - **rustls** (feature `tls-rustls`): Pure Rust TLS implementation
 //! - **native-tls** (feature `tls-native`): Platform-native TLS (OpenSSL/Schannel/Security.framework)
+//!
+//! [`Connector`], [`Acceptor`] and [`MaybeTlsStream`] work the same with
+//! either backend (rustls is preferred when both are enabled), so client and
+//! server code does not have to change when the feature does.
+//!
+//! With rustls, `TlsServerConfigBuilder` covers client certificate
+//! authentication (mTLS), certificates selected by SNI and certificates
+//! reloaded from disk by a `CertReloader`. `TlsStream::peer_certificates`
+//! and `TlsStream::sni_hostname` expose the verified identity afterwards.
+//! `TlsClientConfigBuilder` is the client-side counterpart: system or
+//! custom roots, certificate pinning, a client identity and ALPN.
+
+mod maybe_tls;
 
 #[cfg(feature = "tls-rustls")]
 mod rustls_impl;
 
+#[cfg(feature = "tls-rustls")]
+mod rustls_client;
+
+#[cfg(feature = "tls-rustls")]
+mod rustls_server;
+
 #[cfg(feature = "tls-native")]
 mod native;
 
+pub use maybe_tls::{Acceptor, Connector, MaybeTlsStream};
+
 #[cfg(feature = "tls-rustls")]
 pub use rustls_impl::{
     TlsAcceptor, TlsConnector, TlsError, TlsStream, load_certs_from_file,
     load_private_key_from_file,
 };
 
+#[cfg(feature = "tls-rustls")]
+pub use rustls_client::TlsClientConfigBuilder;
+
+#[cfg(feature = "tls-rustls")]
+pub use rustls_server::{CertReloader, TlsServerConfigBuilder};
+
 #[cfg(feature = "tls-native")]
 pub use native::{
     NativeTlsAcceptor, NativeTlsConnector, NativeTlsError, NativeTlsStream,
@@ -30,6 +57,9 @@ use std::sync::Arc;
 #[cfg(feature = "tls-rustls")]
 use tokio_rustls::rustls::{ClientConfig, ServerConfig};
 
+/// Client configuration trusting the Mozilla roots bundled with
+/// `webpki-roots`. Despite the name, the platform's store is not consulted;
+/// use [`TlsClientConfigBuilder::with_system_roots`] for that.
 #[cfg(feature = "tls-rustls")]
 pub fn client_config_with_native_roots() -> Result<Arc<ClientConfig>, TlsError> {
     let root_store =
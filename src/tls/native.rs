@@ -52,6 +52,17 @@ pub enum NativeTlsStream<S> {
     Server(tokio_native_tls::TlsStream<S>),
 }
 
+impl<S> NativeTlsStream<S> {
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        match self {
+            NativeTlsStream::Client(s) | NativeTlsStream::Server(s) => {
+                s.get_ref().get_ref().get_ref()
+            }
+        }
+    }
+}
+
 impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for NativeTlsStream<S> {
     fn poll_read(
         self: std::pin::Pin<&mut Self>,
@@ -98,6 +109,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for NativeTlsStream<S> {
     }
 }
 
+#[derive(Clone)]
 pub struct NativeTlsConnector {
     inner: tokio_native_tls::TlsConnector,
 }
@@ -127,6 +139,7 @@ impl NativeTlsConnector {
     }
 }
 
+#[derive(Clone)]
 pub struct NativeTlsAcceptor {
     inner: tokio_native_tls::TlsAcceptor,
 }
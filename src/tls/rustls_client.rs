@@ -0,0 +1,510 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use base64::Engine;
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::hash::{Hash, HashAlgorithm};
+use rustls::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::ParsedCertificate;
+use rustls::{
+    CertificateError, DigitallySignedStruct, OtherError, RootCertStore, SignatureScheme,
+    SupportedCipherSuite,
+};
+use tokio_rustls::rustls::ClientConfig;
+
+use super::rustls_impl::{TlsError, load_certs_from_file, load_private_key_from_file};
+
+/// Where [`TlsClientConfigBuilder::with_system_roots`] looks for the
+/// platform CA bundle when `SSL_CERT_FILE` is not set.
+const SYSTEM_CA_BUNDLES: &[&str] = &[
+    "/etc/ssl/certs/ca-certificates.crt", // Debian, Ubuntu, Arch, Alpine
+    "/etc/pki/tls/certs/ca-bundle.crt",   // Fedora, RHEL
+    "/etc/ssl/ca-bundle.pem",             // openSUSE
+    "/etc/pki/tls/cacert.pem",            // OpenELEC
+    "/etc/ssl/cert.pem",                  // macOS, OpenBSD
+    "/usr/local/share/certs/ca-root-nss.crt", // FreeBSD
+];
+
+/// Builder for a rustls [`ClientConfig`] with custom trust roots,
+/// certificate pinning, a client identity and ALPN.
+///
+/// At least one source of trust is required: system roots, the bundled
+/// Mozilla roots, extra PEM roots, or [`Self::danger_accept_invalid_certs`].
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// use rsws::tls::{TlsClientConfigBuilder, TlsConnector};
+///
+/// let config = TlsClientConfigBuilder::new()
+///     .with_system_roots()?
+///     .with_root_pem_file("certs/internal-ca.pem")?
+///     .with_pin("sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=")?
+///     .with_client_cert_files("certs/client.pem", "certs/client.key")?
+///     .with_alpn_protocols(["http/1.1"])
+///     .build()?;
+/// let connector = TlsConnector::new(config);
+/// ```
+pub struct TlsClientConfigBuilder {
+    provider: Arc<CryptoProvider>,
+    roots: RootCertStore,
+    pins: Vec<Pin>,
+    client_identity: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+    alpn_protocols: Vec<Vec<u8>>,
+    accept_invalid_certs: bool,
+}
+
+impl fmt::Debug for TlsClientConfigBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsClientConfigBuilder")
+            .field("roots", &self.roots.len())
+            .field("pins", &self.pins.len())
+            .field("client_identity", &self.client_identity.is_some())
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for TlsClientConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TlsClientConfigBuilder {
+    /// Start a builder with no trusted roots, using the process-wide default
+    /// crypto provider.
+    pub fn new() -> Self {
+        Self::with_provider(Arc::clone(ClientConfig::builder().crypto_provider()))
+    }
+
+    /// Start a builder with no trusted roots, using `provider` for the
+    /// handshake, for loading the client key and for hashing pins.
+    pub fn with_provider(provider: Arc<CryptoProvider>) -> Self {
+        Self {
+            provider,
+            roots: RootCertStore::empty(),
+            pins: Vec::new(),
+            client_identity: None,
+            alpn_protocols: Vec::new(),
+            accept_invalid_certs: false,
+        }
+    }
+
+    /// Trust the platform's CA bundle.
+    ///
+    /// Reads the PEM file named by `SSL_CERT_FILE`, or else the first
+    /// bundle found in the usual locations for Linux distributions, macOS
+    /// and the BSDs. Certificates in the bundle that rustls cannot parse
+    /// are skipped.
+    ///
+    /// # Errors
+    ///
+    /// - `TlsError::SystemRootsNotFound` if no bundle exists
+    /// - `TlsError::NoCertificatesFound` if the bundle holds no usable
+    ///   certificate
+    /// - `TlsError::Io` if the bundle cannot be read
+    pub fn with_system_roots(mut self) -> Result<Self, TlsError> {
+        let path = system_ca_bundle().ok_or(TlsError::SystemRootsNotFound)?;
+        let certs = load_certs_from_file(&path)?;
+        let (added, _ignored) = self.roots.add_parsable_certificates(certs);
+        if added == 0 {
+            return Err(TlsError::NoCertificatesFound);
+        }
+        Ok(self)
+    }
+
+    /// Trust the Mozilla root program bundled with the crate
+    /// (`webpki-roots`), independent of the platform.
+    #[must_use]
+    pub fn with_webpki_roots(mut self) -> Self {
+        self.roots
+            .extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        self
+    }
+
+    /// Trust every certificate in a PEM file, e.g. an internal CA.
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`load_certs_from_file`], or
+    /// `TlsError::InvalidRootCertificate` if a certificate is not a valid
+    /// trust anchor.
+    pub fn with_root_pem_file(self, path: impl AsRef<Path>) -> Result<Self, TlsError> {
+        let certs = load_certs_from_file(path.as_ref())?;
+        self.with_root_certificates(certs)
+    }
+
+    /// Trust every certificate in PEM-encoded `pem`, e.g. a bundle embedded
+    /// with `include_bytes!`.
+    ///
+    /// # Errors
+    ///
+    /// - `TlsError::NoCertificatesFound` if `pem` holds no certificate
+    /// - `TlsError::InvalidRootCertificate` if a certificate is not a valid
+    ///   trust anchor
+    pub fn with_root_pem(self, pem: &[u8]) -> Result<Self, TlsError> {
+        let certs = rustls_pemfile::certs(&mut &pem[..]).collect::<Result<Vec<_>, _>>()?;
+        if certs.is_empty() {
+            return Err(TlsError::NoCertificatesFound);
+        }
+        self.with_root_certificates(certs)
+    }
+
+    /// Trust DER-encoded root certificates.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TlsError::InvalidRootCertificate` if a certificate is not a
+    /// valid trust anchor.
+    pub fn with_root_certificates<I>(mut self, certs: I) -> Result<Self, TlsError>
+    where
+        I: IntoIterator<Item = CertificateDer<'static>>,
+    {
+        for cert in certs {
+            self.roots
+                .add(cert)
+                .map_err(|e| TlsError::InvalidRootCertificate(e.to_string()))?;
+        }
+        Ok(self)
+    }
+
+    /// Only accept servers whose own certificate has this SHA-256 hash of
+    /// its DER-encoded SubjectPublicKeyInfo.
+    ///
+    /// Pins are checked after the usual chain and hostname verification;
+    /// with several pins any one of them has to match. Only the end-entity
+    /// certificate is compared: the intermediates are whatever the server
+    /// chose to send, so a pin on them would prove nothing. Pinning the
+    /// public key rather than the certificate survives renewals that keep
+    /// the key.
+    #[must_use]
+    pub fn with_pinned_spki_sha256(mut self, hash: [u8; 32]) -> Self {
+        self.pins.push(Pin::Spki(hash));
+        self
+    }
+
+    /// Only accept servers whose own certificate has this SHA-256 hash of
+    /// its DER encoding.
+    ///
+    /// See [`Self::with_pinned_spki_sha256`].
+    #[must_use]
+    pub fn with_pinned_cert_sha256(mut self, hash: [u8; 32]) -> Self {
+        self.pins.push(Pin::Cert(hash));
+        self
+    }
+
+    /// Pin a public key given as `sha256/<base64>`, the format used by
+    /// HPKP and curl's `--pinnedpubkey`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TlsError::InvalidPin` if `pin` is not in that format.
+    pub fn with_pin(self, pin: &str) -> Result<Self, TlsError> {
+        let hash = pin
+            .strip_prefix("sha256/")
+            .and_then(|encoded| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(encoded)
+                    .ok()
+            })
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+            .ok_or_else(|| TlsError::InvalidPin(pin.to_string()))?;
+        Ok(self.with_pinned_spki_sha256(hash))
+    }
+
+    /// Present `cert_chain` to servers that ask for a client certificate.
+    ///
+    /// The key is checked in [`Self::build`].
+    #[must_use]
+    pub fn with_client_cert(
+        mut self,
+        cert_chain: Vec<CertificateDer<'static>>,
+        private_key: PrivateKeyDer<'static>,
+    ) -> Self {
+        self.client_identity = Some((cert_chain, private_key));
+        self
+    }
+
+    /// Like [`Self::with_client_cert`], reading PEM files.
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`load_certs_from_file`] or
+    /// [`load_private_key_from_file`].
+    pub fn with_client_cert_files(
+        self,
+        cert_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> Result<Self, TlsError> {
+        let certs = load_certs_from_file(cert_path.as_ref())?;
+        let key = load_private_key_from_file(key_path.as_ref())?;
+        Ok(self.with_client_cert(certs, key))
+    }
+
+    /// Offer these ALPN protocols, in order of preference (e.g. `http/1.1`).
+    #[must_use]
+    pub fn with_alpn_protocols<I, P>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<Vec<u8>>,
+    {
+        self.alpn_protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Accept any server certificate: expired, self-signed, or issued for
+    /// another name.
+    ///
+    /// **This disables server authentication.** It is meant for local test
+    /// rigs only; anyone on the network path can impersonate the server.
+    /// Pins still apply to the server's own certificate, whose key the
+    /// handshake proves the server holds, so pinning the test server's key
+    /// keeps it authenticated.
+    #[must_use]
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// Build the client configuration.
+    ///
+    /// # Errors
+    ///
+    /// - `TlsError::NoRootCertificates` if no root was added and invalid
+    ///   certificates are not accepted
+    /// - `TlsError::InvalidClientIdentity` if the client key cannot be
+    ///   loaded or does not match the certificate
+    /// - `TlsError::Configuration` if the crypto provider supports no safe
+    ///   protocol version, or has no SHA-256 for pinning
+    pub fn build(self) -> Result<Arc<ClientConfig>, TlsError> {
+        if self.roots.is_empty() && !self.accept_invalid_certs {
+            return Err(TlsError::NoRootCertificates);
+        }
+
+        let builder = ClientConfig::builder_with_provider(Arc::clone(&self.provider))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| TlsError::Configuration(e.to_string()))?;
+
+        let builder = if self.pins.is_empty() && !self.accept_invalid_certs {
+            builder.with_root_certificates(self.roots)
+        } else {
+            let webpki = if self.accept_invalid_certs {
+                None
+            } else {
+                let verifier = WebPkiServerVerifier::builder_with_provider(
+                    Arc::new(self.roots),
+                    Arc::clone(&self.provider),
+                )
+                .build()
+                .map_err(|e| TlsError::Configuration(e.to_string()))?;
+                Some(verifier)
+            };
+            let sha256 = if self.pins.is_empty() {
+                None
+            } else {
+                Some(sha256(&self.provider).ok_or_else(|| {
+                    TlsError::Configuration("crypto provider has no SHA-256".to_string())
+                })?)
+            };
+            builder
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(PinningVerifier {
+                    webpki,
+                    pins: self.pins,
+                    sha256,
+                    supported: self.provider.signature_verification_algorithms,
+                }))
+        };
+
+        let mut config = match self.client_identity {
+            Some((chain, key)) => builder
+                .with_client_auth_cert(chain, key)
+                .map_err(|e| TlsError::InvalidClientIdentity(e.to_string()))?,
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = self.alpn_protocols;
+        Ok(Arc::new(config))
+    }
+}
+
+fn system_ca_bundle() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("SSL_CERT_FILE") {
+        let path = PathBuf::from(path);
+        if path.is_file() {
+            return Some(path);
+        }
+    }
+    SYSTEM_CA_BUNDLES
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.is_file())
+}
+
+fn sha256(provider: &CryptoProvider) -> Option<&'static dyn Hash> {
+    provider
+        .cipher_suites
+        .iter()
+        .map(|suite| match suite {
+            SupportedCipherSuite::Tls12(suite) => suite.common.hash_provider,
+            SupportedCipherSuite::Tls13(suite) => suite.common.hash_provider,
+        })
+        .find(|hash| hash.algorithm() == HashAlgorithm::SHA256)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pin {
+    Spki([u8; 32]),
+    Cert([u8; 32]),
+}
+
+/// Marks a handshake that failed because no pin matched, so
+/// [`TlsConnector::connect`](super::TlsConnector::connect) can report
+/// `TlsError::PinMismatch`.
+#[derive(Debug)]
+pub(crate) struct PinMismatch;
+
+impl fmt::Display for PinMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("server certificate matches no pin")
+    }
+}
+
+impl std::error::Error for PinMismatch {}
+
+/// Verifies the chain with webpki (unless invalid certificates are
+/// accepted), then checks the pins against the end-entity certificate.
+struct PinningVerifier {
+    webpki: Option<Arc<WebPkiServerVerifier>>,
+    pins: Vec<Pin>,
+    sha256: Option<&'static dyn Hash>,
+    supported: WebPkiSupportedAlgorithms,
+}
+
+impl fmt::Debug for PinningVerifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PinningVerifier")
+            .field("webpki", &self.webpki)
+            .field("pins", &self.pins)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PinningVerifier {
+    fn matches_pin(&self, cert: &CertificateDer<'_>) -> bool {
+        let Some(sha256) = self.sha256 else {
+            return false;
+        };
+        let cert_hash = sha256.hash(cert.as_ref());
+        let spki_hash = ParsedCertificate::try_from(cert)
+            .ok()
+            .map(|parsed| sha256.hash(parsed.subject_public_key_info().as_ref()));
+        self.pins.iter().any(|pin| match pin {
+            Pin::Cert(hash) => cert_hash.as_ref() == hash,
+            Pin::Spki(hash) => spki_hash.as_ref().is_some_and(|h| h.as_ref() == hash),
+        })
+    }
+}
+
+impl ServerCertVerifier for PinningVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(webpki) = &self.webpki {
+            webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            )?;
+        }
+        if !self.pins.is_empty() && !self.matches_pin(end_entity) {
+            return Err(rustls::Error::InvalidCertificate(CertificateError::Other(
+                OtherError(Arc::new(PinMismatch)),
+            )));
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.supported)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.supported)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.supported.supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_requires_roots() {
+        let result = TlsClientConfigBuilder::new().build();
+        assert!(matches!(result, Err(TlsError::NoRootCertificates)));
+
+        let result = TlsClientConfigBuilder::new()
+            .danger_accept_invalid_certs(true)
+            .build();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_with_pin_parses_hpkp_format() {
+        let builder = TlsClientConfigBuilder::new()
+            .with_pin("sha256/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=")
+            .unwrap();
+        assert_eq!(builder.pins.len(), 1);
+
+        for bad in [
+            "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=",
+            "sha1/47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=",
+            "sha256/not base64",
+            "sha256/AAAA",
+        ] {
+            let result = TlsClientConfigBuilder::new().with_pin(bad);
+            assert!(matches!(result, Err(TlsError::InvalidPin(ref p)) if p == bad));
+        }
+    }
+
+    #[test]
+    fn test_with_root_pem_rejects_empty_input() {
+        let result = TlsClientConfigBuilder::new().with_root_pem(b"no certificates here");
+        assert!(matches!(result, Err(TlsError::NoCertificatesFound)));
+    }
+
+    #[test]
+    fn test_provider_has_sha256() {
+        let provider = ClientConfig::builder().crypto_provider().clone();
+        let hash = sha256(&provider).unwrap();
+        assert_eq!(hash.algorithm(), HashAlgorithm::SHA256);
+        assert_eq!(
+            base64::engine::general_purpose::STANDARD.encode(hash.hash(b"")),
+            "47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
+        );
+    }
+}
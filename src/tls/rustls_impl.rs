@@ -7,6 +7,8 @@ use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_rustls::rustls::{ClientConfig, ServerConfig};
 
+use super::rustls_client::PinMismatch;
+
 #[derive(Debug)]
 pub enum TlsError {
     Io(std::io::Error),
@@ -15,6 +17,20 @@ pub enum TlsError {
     NoPrivateKeyFound,
     InvalidPrivateKey,
     InvalidDnsName(String),
+    /// The client configuration trusts no root certificate.
+    NoRootCertificates,
+    /// No platform CA bundle was found.
+    SystemRootsNotFound,
+    /// A certificate could not be used as a trust anchor.
+    InvalidRootCertificate(String),
+    /// A pin is not in `sha256/<base64>` form.
+    InvalidPin(String),
+    /// The client certificate or key was rejected.
+    InvalidClientIdentity(String),
+    /// The server's certificate chain matched none of the pins.
+    PinMismatch,
+    /// The peer's certificate failed verification during the handshake.
+    CertificateRejected(String),
 }
 
 impl std::fmt::Display for TlsError {
@@ -26,6 +42,21 @@ impl std::fmt::Display for TlsError {
             TlsError::NoPrivateKeyFound => write!(f, "no private key found in file"),
             TlsError::InvalidPrivateKey => write!(f, "invalid private key format"),
             TlsError::InvalidDnsName(name) => write!(f, "invalid DNS name: {}", name),
+            TlsError::NoRootCertificates => write!(f, "no trusted root certificates configured"),
+            TlsError::SystemRootsNotFound => {
+                write!(f, "no system CA bundle found (set SSL_CERT_FILE)")
+            }
+            TlsError::InvalidRootCertificate(msg) => {
+                write!(f, "invalid root certificate: {}", msg)
+            }
+            TlsError::InvalidPin(pin) => write!(f, "invalid certificate pin: {}", pin),
+            TlsError::InvalidClientIdentity(msg) => {
+                write!(f, "invalid client certificate or key: {}", msg)
+            }
+            TlsError::PinMismatch => write!(f, "server certificate matches no pin"),
+            TlsError::CertificateRejected(msg) => {
+                write!(f, "certificate verification failed: {}", msg)
+            }
         }
     }
 }
@@ -50,6 +81,60 @@ pub enum TlsStream<S> {
     Server(tokio_rustls::server::TlsStream<S>),
 }
 
+impl<S> TlsStream<S> {
+    /// The certificate chain the peer presented, end-entity certificate
+    /// first. The handshake has verified it against the configured roots
+    /// (for a server: the client CA).
+    ///
+    /// Returns `None` if the peer sent no certificate.
+    pub fn peer_certificates(&self) -> Option<&[CertificateDer<'static>]> {
+        match self {
+            TlsStream::Client(s) => s.get_ref().1.peer_certificates(),
+            TlsStream::Server(s) => s.get_ref().1.peer_certificates(),
+        }
+    }
+
+    /// The server name the client asked for via SNI (server side only).
+    pub fn sni_hostname(&self) -> Option<&str> {
+        match self {
+            TlsStream::Client(_) => None,
+            TlsStream::Server(s) => s.get_ref().1.server_name(),
+        }
+    }
+
+    /// The protocol agreed on via ALPN, if any.
+    pub fn alpn_protocol(&self) -> Option<&[u8]> {
+        match self {
+            TlsStream::Client(s) => s.get_ref().1.alpn_protocol(),
+            TlsStream::Server(s) => s.get_ref().1.alpn_protocol(),
+        }
+    }
+
+    /// The negotiated TLS version, e.g. for logging.
+    pub fn protocol_version(&self) -> Option<rustls::ProtocolVersion> {
+        match self {
+            TlsStream::Client(s) => s.get_ref().1.protocol_version(),
+            TlsStream::Server(s) => s.get_ref().1.protocol_version(),
+        }
+    }
+
+    /// The negotiated cipher suite, e.g. for logging.
+    pub fn cipher_suite(&self) -> Option<rustls::SupportedCipherSuite> {
+        match self {
+            TlsStream::Client(s) => s.get_ref().1.negotiated_cipher_suite(),
+            TlsStream::Server(s) => s.get_ref().1.negotiated_cipher_suite(),
+        }
+    }
+
+    /// Get a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        match self {
+            TlsStream::Client(s) => s.get_ref().0,
+            TlsStream::Server(s) => s.get_ref().0,
+        }
+    }
+}
+
 impl<S: AsyncRead + AsyncWrite + Unpin> AsyncRead for TlsStream<S> {
     fn poll_read(
         self: std::pin::Pin<&mut Self>,
@@ -96,6 +181,7 @@ impl<S: AsyncRead + AsyncWrite + Unpin> AsyncWrite for TlsStream<S> {
     }
 }
 
+#[derive(Clone)]
 pub struct TlsConnector {
     inner: tokio_rustls::TlsConnector,
 }
@@ -118,12 +204,30 @@ impl TlsConnector {
             .inner
             .connect(server_name, stream)
             .await
-            .map_err(TlsError::Io)?;
+            .map_err(handshake_error)?;
 
         Ok(TlsStream::Client(tls_stream))
     }
 }
 
+/// Surface certificate failures from the client handshake as their own
+/// variants instead of an opaque I/O error.
+fn handshake_error(err: std::io::Error) -> TlsError {
+    let Some(rustls::Error::InvalidCertificate(cert_err)) = err
+        .get_ref()
+        .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+    else {
+        return TlsError::Io(err);
+    };
+    match cert_err {
+        rustls::CertificateError::Other(other) if other.0.is::<PinMismatch>() => {
+            TlsError::PinMismatch
+        }
+        _ => TlsError::CertificateRejected(cert_err.to_string()),
+    }
+}
+
+#[derive(Clone)]
 pub struct TlsAcceptor {
     inner: tokio_rustls::TlsAcceptor,
 }
@@ -205,6 +309,10 @@ mod tests {
         let invalid_dns = TlsError::InvalidDnsName("bad.name".to_string());
         assert!(invalid_dns.to_string().contains("invalid DNS name"));
         assert!(invalid_dns.to_string().contains("bad.name"));
+
+        assert!(TlsError::PinMismatch.to_string().contains("no pin"));
+        let invalid_pin = TlsError::InvalidPin("md5/abc".to_string());
+        assert!(invalid_pin.to_string().contains("md5/abc"));
     }
 
     #[test]
@@ -0,0 +1,434 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert, WebPkiClientVerifier};
+use rustls::sign::CertifiedKey;
+use rustls::{InconsistentKeys, RootCertStore};
+use tokio_rustls::rustls::ServerConfig;
+
+use super::rustls_impl::{TlsError, load_certs_from_file, load_private_key_from_file};
+
+/// Builder for a rustls [`ServerConfig`] with client authentication,
+/// SNI-selected certificates and reloadable certificates.
+///
+/// ## Example
+///
+/// ```rust,ignore
+/// use rsws::tls::{CertReloader, TlsAcceptor, TlsServerConfigBuilder, load_certs_from_file};
+///
+/// let reloader = CertReloader::from_files("certs/api.pem", "certs/api.key")?;
+/// reloader.spawn_watcher(Duration::from_secs(30), |e| eprintln!("certificate reload failed: {e}"));
+///
+/// let config = TlsServerConfigBuilder::new()
+///     .with_cert_files("certs/default.pem", "certs/default.key")?
+///     .with_sni_reloader("api.example.com", reloader)
+///     .with_client_ca(load_certs_from_file(Path::new("certs/clients-ca.pem"))?)
+///     .build()?;
+/// let acceptor = TlsAcceptor::new(config);
+/// ```
+pub struct TlsServerConfigBuilder {
+    provider: Arc<CryptoProvider>,
+    default_cert: Option<CertSource>,
+    sni_certs: HashMap<String, CertSource>,
+    client_ca: Vec<CertificateDer<'static>>,
+    client_auth_optional: bool,
+    alpn_protocols: Vec<Vec<u8>>,
+}
+
+impl fmt::Debug for TlsServerConfigBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsServerConfigBuilder")
+            .field("default_cert", &self.default_cert)
+            .field("sni_certs", &self.sni_certs)
+            .field("client_ca", &self.client_ca.len())
+            .field("client_auth_optional", &self.client_auth_optional)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for TlsServerConfigBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TlsServerConfigBuilder {
+    /// Start a builder using the process-wide default crypto provider.
+    pub fn new() -> Self {
+        Self::with_provider(Arc::clone(ServerConfig::builder().crypto_provider()))
+    }
+
+    /// Start a builder using `provider` for the handshake and for loading
+    /// private keys.
+    pub fn with_provider(provider: Arc<CryptoProvider>) -> Self {
+        Self {
+            provider,
+            default_cert: None,
+            sni_certs: HashMap::new(),
+            client_ca: Vec::new(),
+            client_auth_optional: false,
+            alpn_protocols: Vec::new(),
+        }
+    }
+
+    /// Serve `cert_chain` to clients whose SNI name has no certificate of
+    /// its own, or that send no SNI at all.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TlsError::InvalidPrivateKey` if the key cannot be loaded or
+    /// does not match the certificate.
+    pub fn with_cert(
+        mut self,
+        cert_chain: Vec<CertificateDer<'static>>,
+        private_key: PrivateKeyDer<'static>,
+    ) -> Result<Self, TlsError> {
+        let key = certified_key(&self.provider, cert_chain, private_key)?;
+        self.default_cert = Some(CertSource::Static(key));
+        Ok(self)
+    }
+
+    /// Like [`Self::with_cert`], reading PEM files.
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`load_certs_from_file`], [`load_private_key_from_file`]
+    /// or [`Self::with_cert`].
+    pub fn with_cert_files(
+        self,
+        cert_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> Result<Self, TlsError> {
+        let certs = load_certs_from_file(cert_path.as_ref())?;
+        let key = load_private_key_from_file(key_path.as_ref())?;
+        self.with_cert(certs, key)
+    }
+
+    /// Serve `cert_chain` to clients that ask for `server_name` via SNI.
+    ///
+    /// Names are matched case-insensitively.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TlsError::InvalidPrivateKey` if the key cannot be loaded or
+    /// does not match the certificate.
+    pub fn with_sni_cert(
+        mut self,
+        server_name: &str,
+        cert_chain: Vec<CertificateDer<'static>>,
+        private_key: PrivateKeyDer<'static>,
+    ) -> Result<Self, TlsError> {
+        let key = certified_key(&self.provider, cert_chain, private_key)?;
+        self.sni_certs
+            .insert(server_name.to_ascii_lowercase(), CertSource::Static(key));
+        Ok(self)
+    }
+
+    /// Serve the reloader's current certificate as the default certificate.
+    #[must_use]
+    pub fn with_reloader(mut self, reloader: Arc<CertReloader>) -> Self {
+        self.default_cert = Some(CertSource::Reloading(reloader));
+        self
+    }
+
+    /// Serve the reloader's current certificate to clients that ask for
+    /// `server_name` via SNI.
+    #[must_use]
+    pub fn with_sni_reloader(mut self, server_name: &str, reloader: Arc<CertReloader>) -> Self {
+        self.sni_certs.insert(
+            server_name.to_ascii_lowercase(),
+            CertSource::Reloading(reloader),
+        );
+        self
+    }
+
+    /// Require client certificates issued by one of `ca_certs` (mTLS).
+    ///
+    /// Use [`load_certs_from_file`] to read a CA bundle.
+    #[must_use]
+    pub fn with_client_ca(mut self, ca_certs: Vec<CertificateDer<'static>>) -> Self {
+        self.client_ca = ca_certs;
+        self
+    }
+
+    /// Also accept clients that send no certificate. Clients that do send
+    /// one must still present a certificate issued by the client CA.
+    #[must_use]
+    pub fn with_optional_client_auth(mut self, optional: bool) -> Self {
+        self.client_auth_optional = optional;
+        self
+    }
+
+    /// Offer these ALPN protocols, in order of preference (e.g. `http/1.1`).
+    #[must_use]
+    pub fn with_alpn_protocols<I, P>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<Vec<u8>>,
+    {
+        self.alpn_protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Build the server configuration.
+    ///
+    /// # Errors
+    ///
+    /// - `TlsError::NoCertificatesFound` if no certificate was added
+    /// - `TlsError::Configuration` if a client CA certificate is invalid or
+    ///   the crypto provider supports no safe protocol version
+    pub fn build(self) -> Result<Arc<ServerConfig>, TlsError> {
+        if self.default_cert.is_none() && self.sni_certs.is_empty() {
+            return Err(TlsError::NoCertificatesFound);
+        }
+
+        let builder = ServerConfig::builder_with_provider(Arc::clone(&self.provider))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| TlsError::Configuration(e.to_string()))?;
+
+        let builder = if self.client_ca.is_empty() {
+            builder.with_no_client_auth()
+        } else {
+            let mut roots = RootCertStore::empty();
+            for cert in self.client_ca {
+                roots
+                    .add(cert)
+                    .map_err(|e| TlsError::Configuration(format!("client CA: {}", e)))?;
+            }
+            let mut verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(roots), self.provider);
+            if self.client_auth_optional {
+                verifier = verifier.allow_unauthenticated();
+            }
+            let verifier = verifier
+                .build()
+                .map_err(|e| TlsError::Configuration(format!("client CA: {}", e)))?;
+            builder.with_client_cert_verifier(verifier)
+        };
+
+        let mut config = builder.with_cert_resolver(Arc::new(SniResolver {
+            default_cert: self.default_cert,
+            sni_certs: self.sni_certs,
+        }));
+        config.alpn_protocols = self.alpn_protocols;
+        Ok(Arc::new(config))
+    }
+}
+
+#[derive(Debug)]
+enum CertSource {
+    Static(Arc<CertifiedKey>),
+    Reloading(Arc<CertReloader>),
+}
+
+impl CertSource {
+    fn current(&self) -> Arc<CertifiedKey> {
+        match self {
+            CertSource::Static(key) => Arc::clone(key),
+            CertSource::Reloading(reloader) => reloader.current(),
+        }
+    }
+}
+
+/// Picks the certificate for the client's SNI name, falling back to the
+/// default certificate.
+#[derive(Debug)]
+struct SniResolver {
+    default_cert: Option<CertSource>,
+    sni_certs: HashMap<String, CertSource>,
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        client_hello
+            .server_name()
+            .and_then(|name| self.sni_certs.get(&name.to_ascii_lowercase()))
+            .or(self.default_cert.as_ref())
+            .map(CertSource::current)
+    }
+}
+
+/// A certificate and key read from PEM files that can be reloaded while the
+/// server keeps running.
+///
+/// New handshakes use the certificate loaded last; established connections
+/// are not affected. A reload that fails (missing file, unparsable key, a
+/// key that does not match the certificate) keeps the previous certificate.
+pub struct CertReloader {
+    provider: Arc<CryptoProvider>,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Loaded>,
+}
+
+struct Loaded {
+    key: Arc<CertifiedKey>,
+    modified: Option<(SystemTime, SystemTime)>,
+}
+
+impl fmt::Debug for CertReloader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CertReloader")
+            .field("cert_path", &self.cert_path)
+            .field("key_path", &self.key_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl CertReloader {
+    /// Load the certificate chain and private key from PEM files.
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`load_certs_from_file`] or
+    /// [`load_private_key_from_file`], or `TlsError::InvalidPrivateKey`.
+    pub fn from_files(
+        cert_path: impl Into<PathBuf>,
+        key_path: impl Into<PathBuf>,
+    ) -> Result<Arc<Self>, TlsError> {
+        Self::with_provider(
+            Arc::clone(ServerConfig::builder().crypto_provider()),
+            cert_path,
+            key_path,
+        )
+    }
+
+    /// Like [`Self::from_files`], loading keys with `provider`.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::from_files`].
+    pub fn with_provider(
+        provider: Arc<CryptoProvider>,
+        cert_path: impl Into<PathBuf>,
+        key_path: impl Into<PathBuf>,
+    ) -> Result<Arc<Self>, TlsError> {
+        let cert_path = cert_path.into();
+        let key_path = key_path.into();
+        let current = load(&provider, &cert_path, &key_path)?;
+        Ok(Arc::new(Self {
+            provider,
+            cert_path,
+            key_path,
+            current: RwLock::new(current),
+        }))
+    }
+
+    /// Read the files again and use the new certificate for new handshakes.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::from_files`]; the previous certificate stays in use.
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let loaded = load(&self.provider, &self.cert_path, &self.key_path)?;
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = loaded;
+        Ok(())
+    }
+
+    /// Reload when either file's modification time has changed since the
+    /// last load. Returns `Ok(true)` if a new certificate was loaded.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`Self::reload`].
+    pub fn reload_if_changed(&self) -> Result<bool, TlsError> {
+        let modified = modified_times(&self.cert_path, &self.key_path);
+        let last = self
+            .current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .modified;
+        if modified.is_some() && modified == last {
+            return Ok(false);
+        }
+        self.reload()?;
+        Ok(true)
+    }
+
+    /// Check the files every `interval` and reload them when they change.
+    ///
+    /// The task ends once every other handle to the reloader is dropped.
+    /// A failed reload is passed to `on_error` and the previous certificate
+    /// stays in use until the files are valid again. Each change to the
+    /// files is reported at most once, not on every check.
+    pub fn spawn_watcher<F>(
+        self: &Arc<Self>,
+        interval: Duration,
+        on_error: F,
+    ) -> tokio::task::JoinHandle<()>
+    where
+        F: Fn(TlsError) + Send + 'static,
+    {
+        let reloader: Weak<Self> = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut reported = None;
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(reloader) = reloader.upgrade() else {
+                    return;
+                };
+                let modified = modified_times(&reloader.cert_path, &reloader.key_path);
+                if reported == Some(modified) {
+                    continue;
+                }
+                if let Err(e) = reloader.reload_if_changed() {
+                    reported = Some(modified);
+                    on_error(e);
+                }
+            }
+        })
+    }
+
+    /// The certificate used for new handshakes.
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        Arc::clone(&self.current.read().unwrap_or_else(|e| e.into_inner()).key)
+    }
+}
+
+impl ResolvesServerCert for CertReloader {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+fn load(provider: &CryptoProvider, cert_path: &Path, key_path: &Path) -> Result<Loaded, TlsError> {
+    // Taken before reading, so a write that lands mid-load is seen next time.
+    let modified = modified_times(cert_path, key_path);
+    let certs = load_certs_from_file(cert_path)?;
+    let key = load_private_key_from_file(key_path)?;
+    Ok(Loaded {
+        key: certified_key(provider, certs, key)?,
+        modified,
+    })
+}
+
+fn modified_times(cert_path: &Path, key_path: &Path) -> Option<(SystemTime, SystemTime)> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    Some((modified(cert_path)?, modified(key_path)?))
+}
+
+fn certified_key(
+    provider: &CryptoProvider,
+    cert_chain: Vec<CertificateDer<'static>>,
+    private_key: PrivateKeyDer<'static>,
+) -> Result<Arc<CertifiedKey>, TlsError> {
+    if cert_chain.is_empty() {
+        return Err(TlsError::NoCertificatesFound);
+    }
+    let signing_key = provider
+        .key_provider
+        .load_private_key(private_key)
+        .map_err(|_| TlsError::InvalidPrivateKey)?;
+    let key = CertifiedKey::new(cert_chain, signing_key);
+    // A rotation can be caught between writing the certificate and the key.
+    // Keys whose public half the provider cannot tell are let through.
+    if let Err(rustls::Error::InconsistentKeys(InconsistentKeys::KeyMismatch)) = key.keys_match() {
+        return Err(TlsError::InvalidPrivateKey);
+    }
+    Ok(Arc::new(key))
+}
@@ -2,10 +2,10 @@
 //!
 //! Provides a TestClient implementation for connecting and handshaking.
 
-use rsws::{CloseCode, Config, Connection, HandshakeResponse, Message, Role, compute_accept_key};
+use rsws::client::ClientBuilder;
+use rsws::{CloseCode, Config, Connection, Message};
 use std::error::Error;
 use std::net::SocketAddr;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::TcpStream;
 
 /// Test client wrapping a WebSocket connection.
@@ -35,40 +35,10 @@ impl TestClient {
         addr: SocketAddr,
         id: Option<usize>,
     ) -> Result<TestClient, Box<dyn Error + Send + Sync>> {
-        let mut stream = TcpStream::connect(addr).await?;
-        let key = base64_encode_random_key();
-
-        let request = format!(
-            "GET / HTTP/1.1\r\n\
-             Host: {}\r\n\
-             Upgrade: websocket\r\n\
-             Connection: Upgrade\r\n\
-             Sec-WebSocket-Key: {}\r\n\
-             Sec-WebSocket-Version: 13\r\n\
-             \r\n",
-            addr, key
-        );
-        stream.write_all(request.as_bytes()).await?;
-
-        let mut reader = BufReader::new(&mut stream);
-        let mut response_bytes = Vec::new();
-        loop {
-            let mut line = String::new();
-            reader.read_line(&mut line).await?;
-            response_bytes.extend_from_slice(line.as_bytes());
-            if line == "\r\n" {
-                break;
-            }
-        }
-
-        let response = HandshakeResponse::parse(&response_bytes)?;
-        let expected_accept = compute_accept_key(&key);
-        if response.accept != expected_accept {
-            return Err("Invalid Sec-WebSocket-Accept".into());
-        }
-
-        let config = Config::client();
-        let conn = Connection::new(stream, Role::Client, config);
+        let (conn, _response) = ClientBuilder::new(&format!("ws://{}/", addr))?
+            .with_config(Config::client())
+            .connect()
+            .await?;
 
         Ok(TestClient { conn, id })
     }
@@ -98,19 +68,3 @@ impl TestClient {
         Ok(())
     }
 }
-
-fn base64_encode_random_key() -> String {
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    let seed = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_nanos();
-
-    let mut bytes = [0u8; 16];
-    for (i, b) in bytes.iter_mut().enumerate() {
-        *b = ((seed >> (i * 4)) & 0xFF) as u8;
-    }
-
-    base64::Engine::encode(&base64::engine::general_purpose::STANDARD, bytes)
-}
@@ -2,9 +2,8 @@
 //!
 //! Provides a TestServer implementation that can spawn echo servers on random ports.
 
-use rsws::{Config, Connection, HandshakeRequest, HandshakeResponse, Message, Role};
+use rsws::{Config, Message};
 use std::net::SocketAddr;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::oneshot;
 
@@ -55,13 +54,10 @@ impl TestServer {
                 }
 
                 result = listener.accept() => {
-                    match result {
-                        Ok((stream, _addr)) => {
-                            tokio::spawn(async move {
-                                let _ = Self::handle_connection(stream).await;
-                            });
-                        }
-                        Err(_) => {}
+                    if let Ok((stream, _addr)) = result {
+                        tokio::spawn(async move {
+                            let _ = Self::handle_connection(stream).await;
+                        });
                     }
                 }
             }
@@ -69,36 +65,12 @@ impl TestServer {
     }
 
     async fn handle_connection(
-        mut stream: TcpStream,
+        stream: TcpStream,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Step 1: Read HTTP upgrade request
-        let mut reader = BufReader::new(&mut stream);
-        let mut request_bytes = Vec::new();
+        // Read the upgrade request, validate it and send the 101 response
+        let (mut conn, _request) = rsws::server::accept(stream, Config::server()).await?;
 
-        loop {
-            let mut line = String::new();
-            reader.read_line(&mut line).await?;
-            request_bytes.extend_from_slice(line.as_bytes());
-            if line == "\r\n" {
-                break;
-            }
-        }
-
-        // Step 2: Parse and validate handshake request
-        let request = HandshakeRequest::parse(&request_bytes)?;
-        let config = Config::server();
-        request.validate_with_config(&config)?;
-
-        // Step 3: Generate and send handshake response
-        let response = HandshakeResponse::from_request(&request);
-        let mut response_bytes = Vec::new();
-        let _ = response.write(&mut response_bytes);
-        stream.write_all(&response_bytes).await?;
-
-        // Step 4: Create WebSocket connection
-        let mut conn = Connection::new(stream, Role::Server, config);
-
-        // Step 5: Echo loop - handle messages
+        // Echo loop - handle messages
         while conn.is_open() {
             match conn.recv().await? {
                 Some(Message::Text(text)) => {
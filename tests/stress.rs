@@ -188,12 +188,10 @@ async fn test_stress_latency() {
                     let msg = format!("latency:{}:{}", client_id, seq);
 
                     let start = Instant::now();
-                    if client.send_text(&msg).await.is_ok() {
-                        if client.recv_text().await.is_ok() {
-                            let latency = start.elapsed();
-                            latencies.record(latency);
-                            success.fetch_add(1, Ordering::Relaxed);
-                        }
+                    if client.send_text(&msg).await.is_ok() && client.recv_text().await.is_ok() {
+                        let latency = start.elapsed();
+                        latencies.record(latency);
+                        success.fetch_add(1, Ordering::Relaxed);
                     }
                 }
 
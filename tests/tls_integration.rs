@@ -1,9 +1,14 @@
 #![cfg(feature = "tls-rustls")]
 
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 
+use rcgen::{BasicConstraints, CertificateParams, IsCa, KeyPair};
 use rcgen::{CertifiedKey, generate_simple_self_signed};
-use rsws::tls::{TlsAcceptor, TlsConnector, TlsError};
+use rsws::tls::{
+    CertReloader, TlsAcceptor, TlsClientConfigBuilder, TlsConnector, TlsError,
+    TlsServerConfigBuilder,
+};
 use rustls::pki_types::{CertificateDer, PrivateKeyDer};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
@@ -94,7 +99,7 @@ async fn test_invalid_dns_name() {
 
 #[test]
 fn test_tls_error_display() {
-    let io_err = TlsError::Io(std::io::Error::new(std::io::ErrorKind::Other, "test"));
+    let io_err = TlsError::Io(std::io::Error::other("test"));
     assert!(io_err.to_string().contains("TLS I/O error"));
 
     let config_err = TlsError::Configuration("bad config".to_string());
@@ -112,3 +117,927 @@ fn test_tls_error_display() {
     let invalid_dns = TlsError::InvalidDnsName("bad.name".to_string());
     assert!(invalid_dns.to_string().contains("invalid DNS name"));
 }
+
+/// A CA that signs leaf certificates for the mTLS tests.
+struct TestCa {
+    cert: rcgen::Certificate,
+    key: KeyPair,
+}
+
+impl TestCa {
+    fn new() -> Self {
+        let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let key = KeyPair::generate().unwrap();
+        let cert = params.self_signed(&key).unwrap();
+        Self { cert, key }
+    }
+
+    fn der(&self) -> CertificateDer<'static> {
+        CertificateDer::from(self.cert.der().to_vec())
+    }
+
+    fn issue(&self, name: &str) -> (Vec<CertificateDer<'static>>, PrivateKeyDer<'static>) {
+        let params = CertificateParams::new(vec![name.to_string()]).unwrap();
+        let key = KeyPair::generate().unwrap();
+        let cert = params.signed_by(&key, &self.cert, &self.key).unwrap();
+        (
+            vec![CertificateDer::from(cert.der().to_vec())],
+            PrivateKeyDer::Pkcs8(key.serialize_der().into()),
+        )
+    }
+}
+
+fn roots(certs: &[CertificateDer<'static>]) -> RootCertStore {
+    let mut store = RootCertStore::empty();
+    for cert in certs {
+        store.add(cert.clone()).unwrap();
+    }
+    store
+}
+
+/// Accept one TLS connection and report what the server saw.
+async fn accept_one(
+    config: Arc<ServerConfig>,
+) -> (
+    std::net::SocketAddr,
+    tokio::task::JoinHandle<
+        Result<(Option<Vec<CertificateDer<'static>>>, Option<String>), TlsError>,
+    >,
+) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut tls = TlsAcceptor::new(config).accept(stream).await?;
+        // TLS 1.3 clients finish before the server checks their certificate;
+        // a round trip makes sure both sides agree.
+        let mut buf = [0u8; 4];
+        tls.read_exact(&mut buf).await?;
+        tls.write_all(&buf).await?;
+        Ok((
+            tls.peer_certificates().map(<[_]>::to_vec),
+            tls.sni_hostname().map(str::to_string),
+        ))
+    });
+    (addr, handle)
+}
+
+async fn connect_and_ping(
+    addr: std::net::SocketAddr,
+    config: impl Into<Arc<ClientConfig>>,
+    name: &str,
+) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let stream = TcpStream::connect(addr).await?;
+    let mut tls = TlsConnector::new(config.into())
+        .connect(name, stream)
+        .await?;
+    tls.write_all(b"ping").await?;
+    let mut buf = [0u8; 4];
+    tls.read_exact(&mut buf).await?;
+    Ok(tls.peer_certificates().unwrap_or_default().to_vec())
+}
+
+#[tokio::test]
+async fn test_mtls_requires_client_certificate() {
+    let ca = TestCa::new();
+    let (server_chain, server_key) = ca.issue("localhost");
+    let (client_chain, client_key) = ca.issue("client.example");
+    let config = TlsServerConfigBuilder::new()
+        .with_cert(server_chain, server_key)
+        .unwrap()
+        .with_client_ca(vec![ca.der()])
+        .build()
+        .unwrap();
+
+    // Without a client certificate the handshake fails on the server.
+    let (addr, server) = accept_one(Arc::clone(&config)).await;
+    let anonymous = ClientConfig::builder()
+        .with_root_certificates(roots(&[ca.der()]))
+        .with_no_client_auth();
+    assert!(
+        connect_and_ping(addr, anonymous, "localhost")
+            .await
+            .is_err()
+    );
+    assert!(server.await.unwrap().is_err());
+
+    // With one, the server sees the verified chain.
+    let (addr, server) = accept_one(config).await;
+    let client = ClientConfig::builder()
+        .with_root_certificates(roots(&[ca.der()]))
+        .with_client_auth_cert(client_chain.clone(), client_key)
+        .unwrap();
+    connect_and_ping(addr, client, "localhost").await.unwrap();
+    let (peer_certs, sni) = server.await.unwrap().unwrap();
+    assert_eq!(peer_certs, Some(client_chain));
+    assert_eq!(sni.as_deref(), Some("localhost"));
+}
+
+#[tokio::test]
+async fn test_mtls_rejects_certificate_from_other_ca() {
+    let ca = TestCa::new();
+    let other = TestCa::new();
+    let (server_chain, server_key) = ca.issue("localhost");
+    let (client_chain, client_key) = other.issue("client.example");
+    let config = TlsServerConfigBuilder::new()
+        .with_cert(server_chain, server_key)
+        .unwrap()
+        .with_client_ca(vec![ca.der()])
+        .with_optional_client_auth(true)
+        .build()
+        .unwrap();
+
+    let (addr, server) = accept_one(config).await;
+    let client = ClientConfig::builder()
+        .with_root_certificates(roots(&[ca.der()]))
+        .with_client_auth_cert(client_chain, client_key)
+        .unwrap();
+    assert!(connect_and_ping(addr, client, "localhost").await.is_err());
+    assert!(server.await.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn test_optional_client_auth_accepts_anonymous_clients() {
+    let ca = TestCa::new();
+    let (server_chain, server_key) = ca.issue("localhost");
+    let config = TlsServerConfigBuilder::new()
+        .with_cert(server_chain, server_key)
+        .unwrap()
+        .with_client_ca(vec![ca.der()])
+        .with_optional_client_auth(true)
+        .build()
+        .unwrap();
+
+    let (addr, server) = accept_one(config).await;
+    let anonymous = ClientConfig::builder()
+        .with_root_certificates(roots(&[ca.der()]))
+        .with_no_client_auth();
+    connect_and_ping(addr, anonymous, "localhost")
+        .await
+        .unwrap();
+    let (peer_certs, _) = server.await.unwrap().unwrap();
+    assert_eq!(peer_certs, None);
+}
+
+#[tokio::test]
+async fn test_sni_selects_certificate() {
+    let ca = TestCa::new();
+    let (default_chain, default_key) = ca.issue("localhost");
+    let (api_chain, api_key) = ca.issue("api.example.com");
+    let (www_chain, www_key) = ca.issue("www.example.com");
+    let config = TlsServerConfigBuilder::new()
+        .with_cert(default_chain.clone(), default_key)
+        .unwrap()
+        .with_sni_cert("api.example.com", api_chain.clone(), api_key)
+        .unwrap()
+        .with_sni_cert("WWW.example.com", www_chain.clone(), www_key)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    for (name, expected) in [
+        ("api.example.com", &api_chain),
+        ("www.example.com", &www_chain),
+        ("localhost", &default_chain),
+    ] {
+        let (addr, server) = accept_one(Arc::clone(&config)).await;
+        let client = ClientConfig::builder()
+            .with_root_certificates(roots(&[ca.der()]))
+            .with_no_client_auth();
+        let served = connect_and_ping(addr, client, name).await.unwrap();
+        assert_eq!(&served, expected);
+        let (_, sni) = server.await.unwrap().unwrap();
+        assert_eq!(sni.as_deref(), Some(name));
+    }
+}
+
+#[test]
+fn test_server_builder_needs_a_certificate() {
+    let result = TlsServerConfigBuilder::new().build();
+    assert!(matches!(result, Err(TlsError::NoCertificatesFound)));
+}
+
+fn write_pem(dir: &std::path::Path, chain: &[CertificateDer<'static>], key: &PrivateKeyDer<'_>) {
+    let cert_pem = pem_block("CERTIFICATE", chain[0].as_ref());
+    let key_pem = pem_block("PRIVATE KEY", key.secret_der());
+    std::fs::write(dir.join("cert.pem"), cert_pem).unwrap();
+    std::fs::write(dir.join("key.pem"), key_pem).unwrap();
+}
+
+fn pem_block(label: &str, der: &[u8]) -> String {
+    use base64::Engine;
+    let encoded = base64::engine::general_purpose::STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {}-----\n", label);
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", label));
+    pem
+}
+
+#[tokio::test]
+async fn test_cert_reloader_swaps_certificate() {
+    let ca = TestCa::new();
+    let dir = tempfile::tempdir().unwrap();
+    let (first_chain, first_key) = ca.issue("localhost");
+    write_pem(dir.path(), &first_chain, &first_key);
+
+    let reloader =
+        CertReloader::from_files(dir.path().join("cert.pem"), dir.path().join("key.pem")).unwrap();
+    assert!(!reloader.reload_if_changed().unwrap());
+    let config = TlsServerConfigBuilder::new()
+        .with_reloader(Arc::clone(&reloader))
+        .build()
+        .unwrap();
+
+    let client = || {
+        ClientConfig::builder()
+            .with_root_certificates(roots(&[ca.der()]))
+            .with_no_client_auth()
+    };
+    let (addr, server) = accept_one(Arc::clone(&config)).await;
+    assert_eq!(
+        connect_and_ping(addr, client(), "localhost").await.unwrap(),
+        first_chain
+    );
+    server.await.unwrap().unwrap();
+
+    // A broken file keeps the current certificate.
+    std::fs::write(dir.path().join("key.pem"), "garbage").unwrap();
+    assert!(reloader.reload().is_err());
+    assert_eq!(reloader.current().cert, first_chain);
+
+    let (second_chain, second_key) = ca.issue("localhost");
+    write_pem(dir.path(), &second_chain, &second_key);
+    reloader.reload().unwrap();
+
+    let (addr, server) = accept_one(config).await;
+    assert_eq!(
+        connect_and_ping(addr, client(), "localhost").await.unwrap(),
+        second_chain
+    );
+    server.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_cert_reloader_watcher_reports_errors() {
+    let ca = TestCa::new();
+    let dir = tempfile::tempdir().unwrap();
+    let (first_chain, first_key) = ca.issue("localhost");
+    write_pem(dir.path(), &first_chain, &first_key);
+    let reloader =
+        CertReloader::from_files(dir.path().join("cert.pem"), dir.path().join("key.pem")).unwrap();
+
+    let key_path = dir.path().join("key.pem");
+    std::fs::write(&key_path, "garbage").unwrap();
+    let later = SystemTime::now() + Duration::from_secs(10);
+    std::fs::File::options()
+        .write(true)
+        .open(&key_path)
+        .unwrap()
+        .set_modified(later)
+        .unwrap();
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let watcher = reloader.spawn_watcher(Duration::from_millis(10), move |e| {
+        let _ = tx.send(e);
+    });
+    tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("the failed reload must be reported")
+        .unwrap();
+    assert_eq!(reloader.current().cert, first_chain);
+
+    // The same broken files are not reported again on every check.
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(rx.try_recv().is_err());
+    watcher.abort();
+}
+
+#[test]
+fn test_cert_reloader_rejects_mismatched_key() {
+    let ca = TestCa::new();
+    let dir = tempfile::tempdir().unwrap();
+    let (first_chain, first_key) = ca.issue("localhost");
+    write_pem(dir.path(), &first_chain, &first_key);
+    let reloader =
+        CertReloader::from_files(dir.path().join("cert.pem"), dir.path().join("key.pem")).unwrap();
+
+    // The new certificate has landed but the key is still the old one.
+    let (second_chain, second_key) = ca.issue("localhost");
+    write_pem(dir.path(), &second_chain, &first_key);
+    assert!(matches!(
+        reloader.reload(),
+        Err(TlsError::InvalidPrivateKey)
+    ));
+    assert_eq!(reloader.current().cert, first_chain);
+
+    write_pem(dir.path(), &second_chain, &second_key);
+    reloader.reload().unwrap();
+    assert_eq!(reloader.current().cert, second_chain);
+
+    let result = TlsServerConfigBuilder::new().with_cert(second_chain, first_key);
+    assert!(matches!(result, Err(TlsError::InvalidPrivateKey)));
+}
+
+#[tokio::test]
+async fn test_server_handler_sees_client_identity() {
+    use rsws::server::ServerStream;
+    use rsws::{Config, Connection, Message, Server};
+
+    let ca = TestCa::new();
+    let (server_chain, server_key) = ca.issue("localhost");
+    let (client_chain, client_key) = ca.issue("client.example");
+    let config = TlsServerConfigBuilder::new()
+        .with_cert(server_chain, server_key)
+        .unwrap()
+        .with_client_ca(vec![ca.der()])
+        .build()
+        .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Server::new(Config::server())
+        .route(
+            "/whoami",
+            |mut conn: Connection<ServerStream>, _| async move {
+                let certs = conn.get_ref().peer_certificates().map_or(0, <[_]>::len);
+                let sni = conn
+                    .get_ref()
+                    .sni_hostname()
+                    .unwrap_or_default()
+                    .to_string();
+                let _ = conn.send(Message::text(format!("{} {}", certs, sni))).await;
+            },
+        )
+        .with_tls(TlsAcceptor::new(config));
+    tokio::spawn(server.serve(listener));
+
+    let client_config = ClientConfig::builder()
+        .with_root_certificates(roots(&[ca.der()]))
+        .with_client_auth_cert(client_chain, client_key)
+        .unwrap();
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let tls = TlsConnector::new(Arc::new(client_config))
+        .connect("localhost", stream)
+        .await
+        .unwrap();
+    let (mut conn, _) = rsws::client::ClientBuilder::new("ws://localhost/whoami")
+        .unwrap()
+        .handshake(tls)
+        .await
+        .unwrap();
+    assert_eq!(
+        conn.recv().await.unwrap(),
+        Some(Message::text("1 localhost"))
+    );
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    use rustls::crypto::hash::HashAlgorithm;
+    let provider = ClientConfig::builder().crypto_provider().clone();
+    let hash = provider
+        .cipher_suites
+        .iter()
+        .filter_map(|suite| suite.tls13())
+        .map(|suite| suite.common.hash_provider)
+        .find(|hash| hash.algorithm() == HashAlgorithm::SHA256)
+        .unwrap();
+    hash.hash(data).as_ref().try_into().unwrap()
+}
+
+/// A server whose certificate is issued by `ca` for `localhost`, plus the
+/// SHA-256 of its SPKI.
+fn pinned_server(ca: &TestCa) -> (Arc<ServerConfig>, Vec<CertificateDer<'static>>, [u8; 32]) {
+    let params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+    let key = KeyPair::generate().unwrap();
+    let cert = params.signed_by(&key, &ca.cert, &ca.key).unwrap();
+    let chain = vec![CertificateDer::from(cert.der().to_vec())];
+    let spki = sha256(&key.public_key_der());
+    let config = TlsServerConfigBuilder::new()
+        .with_cert(
+            chain.clone(),
+            PrivateKeyDer::Pkcs8(key.serialize_der().into()),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+    (config, chain, spki)
+}
+
+#[tokio::test]
+async fn test_client_builder_trusts_pem_roots() {
+    let ca = TestCa::new();
+    let (server, chain, _) = pinned_server(&ca);
+
+    let pem = pem_block("CERTIFICATE", ca.der().as_ref());
+    let client = TlsClientConfigBuilder::new()
+        .with_root_pem(pem.as_bytes())
+        .unwrap()
+        .build()
+        .unwrap();
+    let (addr, handle) = accept_one(Arc::clone(&server)).await;
+    assert_eq!(
+        connect_and_ping(addr, client, "localhost").await.unwrap(),
+        chain
+    );
+    handle.await.unwrap().unwrap();
+
+    // The bundled Mozilla roots do not know the test CA.
+    let client = TlsClientConfigBuilder::new()
+        .with_webpki_roots()
+        .build()
+        .unwrap();
+    let (addr, handle) = accept_one(server).await;
+    let result = connect_and_ping(addr, client, "localhost").await;
+    assert!(matches!(result, Err(TlsError::CertificateRejected(_))));
+    let _ = handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_client_builder_reads_root_file() {
+    let ca = TestCa::new();
+    let (server, _, _) = pinned_server(&ca);
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ca.pem");
+    std::fs::write(&path, pem_block("CERTIFICATE", ca.der().as_ref())).unwrap();
+
+    let client = TlsClientConfigBuilder::new()
+        .with_root_pem_file(&path)
+        .unwrap()
+        .build()
+        .unwrap();
+    let (addr, handle) = accept_one(server).await;
+    connect_and_ping(addr, client, "localhost").await.unwrap();
+    handle.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_client_builder_pins() {
+    use base64::Engine;
+
+    let ca = TestCa::new();
+    let (server, chain, spki) = pinned_server(&ca);
+    let builder = || {
+        TlsClientConfigBuilder::new()
+            .with_root_certificates([ca.der()])
+            .unwrap()
+    };
+    let pin = format!(
+        "sha256/{}",
+        base64::engine::general_purpose::STANDARD.encode(spki)
+    );
+
+    let matching = [
+        builder().with_pin(&pin).unwrap().build().unwrap(),
+        builder()
+            .with_pinned_cert_sha256(sha256(chain[0].as_ref()))
+            .build()
+            .unwrap(),
+        // Any one pin is enough.
+        builder()
+            .with_pinned_spki_sha256([0; 32])
+            .with_pinned_spki_sha256(spki)
+            .build()
+            .unwrap(),
+    ];
+    for client in matching {
+        let (addr, handle) = accept_one(Arc::clone(&server)).await;
+        connect_and_ping(addr, client, "localhost").await.unwrap();
+        handle.await.unwrap().unwrap();
+    }
+
+    let client = builder().with_pinned_spki_sha256([0; 32]).build().unwrap();
+    let (addr, handle) = accept_one(server).await;
+    let result = connect_and_ping(addr, client, "localhost").await;
+    assert!(matches!(result, Err(TlsError::PinMismatch)));
+    let _ = handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_client_builder_pins_ignore_intermediates() {
+    // A valid certificate for the name from the same CA, with the pinned
+    // server certificate tacked on as an unused "intermediate".
+    let ca = TestCa::new();
+    let (_, pinned_chain, spki) = pinned_server(&ca);
+    let (mut chain, key) = ca.issue("localhost");
+    chain.extend(pinned_chain.iter().cloned());
+    let server = TlsServerConfigBuilder::new()
+        .with_cert(chain, key)
+        .unwrap()
+        .build()
+        .unwrap();
+
+    let builder = || {
+        TlsClientConfigBuilder::new()
+            .with_root_certificates([ca.der()])
+            .unwrap()
+    };
+    for client in [
+        builder().with_pinned_spki_sha256(spki).build().unwrap(),
+        builder()
+            .with_pinned_cert_sha256(sha256(pinned_chain[0].as_ref()))
+            .build()
+            .unwrap(),
+    ] {
+        let (addr, handle) = accept_one(Arc::clone(&server)).await;
+        let result = connect_and_ping(addr, client, "localhost").await;
+        assert!(matches!(result, Err(TlsError::PinMismatch)));
+        let _ = handle.await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_client_builder_pins_checked_after_chain() {
+    // A matching pin does not make an untrusted chain acceptable.
+    let ca = TestCa::new();
+    let other = TestCa::new();
+    let (server, _, spki) = pinned_server(&ca);
+    let client = TlsClientConfigBuilder::new()
+        .with_root_certificates([other.der()])
+        .unwrap()
+        .with_pinned_spki_sha256(spki)
+        .build()
+        .unwrap();
+    let (addr, handle) = accept_one(server).await;
+    let result = connect_and_ping(addr, client, "localhost").await;
+    assert!(matches!(result, Err(TlsError::CertificateRejected(_))));
+    let _ = handle.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_client_builder_insecure_mode() {
+    let (server, _, spki) = pinned_server(&TestCa::new());
+
+    let client = TlsClientConfigBuilder::new()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let (addr, handle) = accept_one(Arc::clone(&server)).await;
+    // Even the name does not have to match.
+    connect_and_ping(addr, client, "example.com").await.unwrap();
+    handle.await.unwrap().unwrap();
+
+    // Pins still apply.
+    let client = TlsClientConfigBuilder::new()
+        .danger_accept_invalid_certs(true)
+        .with_pinned_spki_sha256([0; 32])
+        .build()
+        .unwrap();
+    let (addr, handle) = accept_one(Arc::clone(&server)).await;
+    let result = connect_and_ping(addr, client, "localhost").await;
+    assert!(matches!(result, Err(TlsError::PinMismatch)));
+    let _ = handle.await.unwrap();
+
+    let client = TlsClientConfigBuilder::new()
+        .danger_accept_invalid_certs(true)
+        .with_pinned_spki_sha256(spki)
+        .build()
+        .unwrap();
+    let (addr, handle) = accept_one(server).await;
+    connect_and_ping(addr, client, "localhost").await.unwrap();
+    handle.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn test_client_builder_identity_and_alpn() {
+    let ca = TestCa::new();
+    let (server_chain, server_key) = ca.issue("localhost");
+    let (client_chain, client_key) = ca.issue("client.example");
+    let server = TlsServerConfigBuilder::new()
+        .with_cert(server_chain, server_key)
+        .unwrap()
+        .with_client_ca(vec![ca.der()])
+        .with_alpn_protocols(["http/1.1"])
+        .build()
+        .unwrap();
+
+    let client = TlsClientConfigBuilder::new()
+        .with_root_certificates([ca.der()])
+        .unwrap()
+        .with_client_cert(client_chain.clone(), client_key)
+        .with_alpn_protocols(["h2", "http/1.1"])
+        .build()
+        .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let tls = TlsAcceptor::new(server).accept(stream).await.unwrap();
+        (
+            tls.peer_certificates().map(<[_]>::to_vec),
+            tls.alpn_protocol().map(<[u8]>::to_vec),
+        )
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let tls = TlsConnector::new(client)
+        .connect("localhost", stream)
+        .await
+        .unwrap();
+    assert_eq!(tls.alpn_protocol(), Some(&b"http/1.1"[..]));
+
+    let (peer_certs, alpn) = handle.await.unwrap();
+    assert_eq!(peer_certs, Some(client_chain));
+    assert_eq!(alpn.as_deref(), Some(&b"http/1.1"[..]));
+}
+
+#[test]
+fn test_client_builder_errors() {
+    let result = TlsClientConfigBuilder::new().build();
+    assert!(matches!(result, Err(TlsError::NoRootCertificates)));
+
+    let result =
+        TlsClientConfigBuilder::new().with_root_certificates([CertificateDer::from(vec![1, 2, 3])]);
+    assert!(matches!(result, Err(TlsError::InvalidRootCertificate(_))));
+
+    let ca = TestCa::new();
+    let (chain, _) = ca.issue("client.example");
+    let result = TlsClientConfigBuilder::new()
+        .with_root_certificates([ca.der()])
+        .unwrap()
+        .with_client_cert(chain, PrivateKeyDer::Pkcs8(vec![0; 8].into()))
+        .build();
+    assert!(matches!(result, Err(TlsError::InvalidClientIdentity(_))));
+}
+
+#[tokio::test]
+async fn test_unified_connector_and_acceptor_with_server() {
+    use rsws::server::ServerStream;
+    use rsws::tls::{Acceptor, Connector, MaybeTlsStream};
+    use rsws::{Config, Connection, Message, Server};
+
+    let ca = TestCa::new();
+    let (server_chain, server_key) = ca.issue("localhost");
+    let acceptor = Acceptor::from(TlsAcceptor::new(
+        TlsServerConfigBuilder::new()
+            .with_cert(server_chain, server_key)
+            .unwrap()
+            .build()
+            .unwrap(),
+    ));
+    let connector = Connector::from(TlsConnector::new(
+        TlsClientConfigBuilder::new()
+            .with_root_certificates([ca.der()])
+            .unwrap()
+            .build()
+            .unwrap(),
+    ));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Server::new(Config::server())
+        .route(
+            "/echo",
+            |mut conn: Connection<ServerStream>, _| async move {
+                assert!(matches!(
+                    conn.get_ref().as_maybe_tls(),
+                    MaybeTlsStream::Rustls(_)
+                ));
+                while let Ok(Some(msg)) = conn.recv().await {
+                    if conn.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+            },
+        )
+        .with_tls(acceptor);
+    tokio::spawn(server.serve(listener));
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let tls = connector.connect("localhost", stream).await.unwrap();
+    assert!(matches!(tls, MaybeTlsStream::Rustls(_)));
+    let (mut conn, _) = rsws::client::ClientBuilder::new("wss://localhost/echo")
+        .unwrap()
+        .handshake(tls)
+        .await
+        .unwrap();
+    conn.send(Message::text("hello")).await.unwrap();
+    assert_eq!(conn.recv().await.unwrap(), Some(Message::text("hello")));
+
+    // A failed handshake surfaces as `Error::Tls`.
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let result = connector.connect("example.com", stream).await;
+    assert!(matches!(result, Err(rsws::Error::Tls(_))));
+}
+
+/// An echo server on 127.0.0.1 whose certificate, issued by `ca`, covers
+/// `sans`.
+async fn wss_echo_server(ca: &TestCa, sans: &[&str]) -> std::net::SocketAddr {
+    use rsws::server::ServerStream;
+    use rsws::{Config, Connection, Server};
+
+    let params =
+        CertificateParams::new(sans.iter().map(|s| s.to_string()).collect::<Vec<_>>()).unwrap();
+    let key = KeyPair::generate().unwrap();
+    let cert = params.signed_by(&key, &ca.cert, &ca.key).unwrap();
+    let config = TlsServerConfigBuilder::new()
+        .with_cert(
+            vec![CertificateDer::from(cert.der().to_vec())],
+            PrivateKeyDer::Pkcs8(key.serialize_der().into()),
+        )
+        .unwrap()
+        .with_alpn_protocols(["http/1.1"])
+        .build()
+        .unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Server::new(Config::server())
+        .route(
+            "/echo",
+            |mut conn: Connection<ServerStream>, _| async move {
+                while let Ok(Some(msg)) = conn.recv().await {
+                    if conn.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+            },
+        )
+        .with_tls(TlsAcceptor::new(config));
+    tokio::spawn(server.serve(listener));
+    addr
+}
+
+fn trusting(ca: &TestCa) -> Arc<ClientConfig> {
+    TlsClientConfigBuilder::new()
+        .with_root_certificates([ca.der()])
+        .unwrap()
+        .with_alpn_protocols(["http/1.1"])
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_connect_tls_by_hostname() {
+    use rsws::Message;
+    use rsws::client::ClientBuilder;
+
+    let ca = TestCa::new();
+    let addr = wss_echo_server(&ca, &["localhost"]).await;
+
+    let url = format!("wss://localhost:{}/echo", addr.port());
+    let (mut conn, _) = ClientBuilder::new(&url)
+        .unwrap()
+        .with_tls_config(trusting(&ca))
+        .connect_tls()
+        .await
+        .unwrap();
+
+    let tls = conn.get_ref();
+    assert_eq!(
+        tls.protocol_version(),
+        Some(rustls::ProtocolVersion::TLSv1_3)
+    );
+    assert!(tls.cipher_suite().is_some());
+    assert_eq!(tls.alpn_protocol(), Some(&b"http/1.1"[..]));
+
+    conn.send(Message::text("hello")).await.unwrap();
+    assert_eq!(conn.recv().await.unwrap(), Some(Message::text("hello")));
+}
+
+#[tokio::test]
+async fn test_connect_tls_by_ip_address() {
+    use rsws::Message;
+    use rsws::client::ClientBuilder;
+
+    let ca = TestCa::new();
+    let addr = wss_echo_server(&ca, &["localhost", "127.0.0.1"]).await;
+    let url = format!("wss://127.0.0.1:{}/echo", addr.port());
+    let (mut conn, _) = ClientBuilder::new(&url)
+        .unwrap()
+        .with_tls_config(trusting(&ca))
+        .connect_tls()
+        .await
+        .unwrap();
+    conn.send(Message::text("hello")).await.unwrap();
+    assert_eq!(conn.recv().await.unwrap(), Some(Message::text("hello")));
+
+    // Without an IP address SAN the certificate is not valid for the host.
+    let addr = wss_echo_server(&ca, &["localhost"]).await;
+    let url = format!("wss://127.0.0.1:{}/echo", addr.port());
+    let result = ClientBuilder::new(&url)
+        .unwrap()
+        .with_tls_config(trusting(&ca))
+        .connect_tls()
+        .await;
+    assert!(matches!(result, Err(rsws::Error::Tls(_))));
+}
+
+#[tokio::test]
+async fn test_connect_tls_default_roots_reject_private_ca() {
+    let ca = TestCa::new();
+    let addr = wss_echo_server(&ca, &["localhost"]).await;
+    let url = format!("wss://localhost:{}/echo", addr.port());
+    let result = rsws::client::connect_tls(&url).await;
+    assert!(matches!(result, Err(rsws::Error::Tls(_))));
+}
+
+#[tokio::test]
+async fn test_connect_tls_handshake_timeout() {
+    use rsws::client::ClientBuilder;
+    use rsws::config::Timeouts;
+    use std::time::Duration;
+
+    // Accepts TCP connections but never answers the ClientHello.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (_stream, _) = listener.accept().await.unwrap();
+        std::future::pending::<()>().await;
+    });
+
+    let config = rsws::Config::client().with_timeouts(Timeouts {
+        handshake: Duration::from_millis(50),
+        ..Timeouts::default()
+    });
+    let url = format!("wss://localhost:{}/", addr.port());
+    let result = ClientBuilder::new(&url)
+        .unwrap()
+        .with_config(config)
+        .with_tls_config(trusting(&TestCa::new()))
+        .connect_tls()
+        .await;
+    assert!(matches!(result, Err(rsws::Error::HandshakeTimeout)));
+}
+
+/// An HTTP CONNECT proxy stand-in that requires `user:pass` and resolves
+/// every target host to 127.0.0.1, so only proxy-side DNS can succeed.
+async fn connect_proxy() -> (std::net::SocketAddr, tokio::task::JoinHandle<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handle = tokio::spawn(async move {
+        let (mut client, _) = listener.accept().await.unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(client.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap();
+        if !head.contains("Proxy-Authorization: Basic dXNlcjpwYXNz\r\n") {
+            client
+                .write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n")
+                .await
+                .unwrap();
+            return head;
+        }
+        let target = head.split(' ').nth(1).unwrap();
+        let port = target.rsplit_once(':').unwrap().1;
+        let mut upstream = TcpStream::connect(format!("127.0.0.1:{}", port))
+            .await
+            .unwrap();
+        client
+            .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+            .await
+            .unwrap();
+        tokio::spawn(async move {
+            let _ = tokio::io::copy_bidirectional(&mut client, &mut upstream).await;
+        });
+        head
+    });
+    (addr, handle)
+}
+
+#[tokio::test]
+async fn test_connect_tls_through_http_proxy() {
+    use rsws::Message;
+    use rsws::client::{ClientBuilder, Proxy};
+
+    let ca = TestCa::new();
+    let addr = wss_echo_server(&ca, &["ws.invalid"]).await;
+    let (proxy_addr, proxy) = connect_proxy().await;
+
+    let url = format!("wss://ws.invalid:{}/echo", addr.port());
+    let (mut conn, _) = ClientBuilder::new(&url)
+        .unwrap()
+        .with_tls_config(trusting(&ca))
+        .with_proxy(Proxy::http("127.0.0.1", proxy_addr.port()).with_credentials("user", "pass"))
+        .connect_tls()
+        .await
+        .unwrap();
+    conn.send(Message::text("hello")).await.unwrap();
+    assert_eq!(conn.recv().await.unwrap(), Some(Message::text("hello")));
+
+    let head = proxy.await.unwrap();
+    let expected = format!("CONNECT ws.invalid:{} HTTP/1.1\r\n", addr.port());
+    assert!(head.starts_with(&expected), "{}", head);
+}
+
+#[tokio::test]
+async fn test_connect_tls_proxy_authentication_required() {
+    use rsws::client::{ClientBuilder, Proxy};
+
+    let (proxy_addr, _proxy) = connect_proxy().await;
+    let result = ClientBuilder::new("wss://ws.invalid/echo")
+        .unwrap()
+        .with_tls_config(trusting(&TestCa::new()))
+        .with_proxy(Proxy::http("127.0.0.1", proxy_addr.port()))
+        .connect_tls()
+        .await;
+    match result {
+        Err(rsws::Error::Proxy(msg)) => assert!(msg.contains("407"), "{}", msg),
+        other => panic!("expected proxy error, got {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,154 @@
+#![cfg(feature = "tls-native")]
+
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+use rsws::client::ClientBuilder;
+use rsws::server::ServerStream;
+use rsws::tls::{Acceptor, Connector, MaybeTlsStream, NativeTlsAcceptor, NativeTlsConnector};
+use rsws::{Config, Connection, Error, Message, Server};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A CA plus a `localhost` certificate it issued, as native-tls objects.
+fn native_pair() -> (NativeTlsAcceptor, NativeTlsConnector) {
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    // OpenSSL treats a leaf whose issuer equals its subject as self-signed.
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "rsws test CA");
+    let ca_key = KeyPair::generate().unwrap();
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+
+    let params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+    let key = KeyPair::generate().unwrap();
+    let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+
+    let identity =
+        native_tls::Identity::from_pkcs8(cert.pem().as_bytes(), key.serialize_pem().as_bytes())
+            .unwrap();
+    let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+    let connector = native_tls::TlsConnector::builder()
+        .add_root_certificate(native_tls::Certificate::from_pem(ca.pem().as_bytes()).unwrap())
+        .build()
+        .unwrap();
+    (
+        NativeTlsAcceptor::new(acceptor),
+        NativeTlsConnector::new(connector),
+    )
+}
+
+#[tokio::test]
+async fn test_native_connector_and_acceptor() {
+    let (acceptor, connector) = native_pair();
+    let acceptor = Acceptor::from(acceptor);
+    let connector = Connector::from(connector);
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut tls = acceptor.accept(stream).await.unwrap();
+        assert!(matches!(tls, MaybeTlsStream::NativeTls(_)));
+        let mut buf = [0u8; 4];
+        tls.read_exact(&mut buf).await.unwrap();
+        tls.write_all(&buf).await.unwrap();
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut tls = connector.connect("localhost", stream).await.unwrap();
+    assert!(tls.is_tls());
+    assert_eq!(tls.get_ref().peer_addr().unwrap(), addr);
+    tls.write_all(b"ping").await.unwrap();
+    let mut buf = [0u8; 4];
+    tls.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"ping");
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_native_connector_rejects_wrong_name() {
+    let (acceptor, connector) = native_pair();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let _ = acceptor.accept(stream).await;
+    });
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let result = Connector::from(connector)
+        .connect("example.com", stream)
+        .await;
+    assert!(matches!(result, Err(Error::Tls(_))));
+}
+
+/// An echo server on 127.0.0.1 accepting with native-tls.
+async fn native_echo_server(acceptor: NativeTlsAcceptor) -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = Server::new(Config::server())
+        .route(
+            "/echo",
+            |mut conn: Connection<ServerStream>, _| async move {
+                assert!(matches!(
+                    conn.get_ref().as_maybe_tls(),
+                    MaybeTlsStream::NativeTls(_)
+                ));
+                while let Ok(Some(msg)) = conn.recv().await {
+                    if conn.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+            },
+        )
+        .with_tls(acceptor);
+    tokio::spawn(server.serve(listener));
+    addr
+}
+
+#[tokio::test]
+async fn test_server_with_native_tls() {
+    let (acceptor, connector) = native_pair();
+    let addr = native_echo_server(acceptor).await;
+
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let tls = Connector::from(connector)
+        .connect("localhost", stream)
+        .await
+        .unwrap();
+    let (mut conn, _) = ClientBuilder::new("wss://localhost/echo")
+        .unwrap()
+        .handshake(tls)
+        .await
+        .unwrap();
+    conn.send(Message::text("hello")).await.unwrap();
+    assert_eq!(conn.recv().await.unwrap(), Some(Message::text("hello")));
+}
+
+#[tokio::test]
+async fn test_connect_tls_with_native_connector() {
+    let (acceptor, connector) = native_pair();
+    let addr = native_echo_server(acceptor).await;
+
+    let url = format!("wss://localhost:{}/echo", addr.port());
+    let (mut conn, _) = ClientBuilder::new(&url)
+        .unwrap()
+        .with_tls_connector(connector)
+        .connect_tls()
+        .await
+        .unwrap();
+    assert!(matches!(conn.get_ref(), MaybeTlsStream::NativeTls(_)));
+    conn.send(Message::text("hello")).await.unwrap();
+    assert_eq!(conn.recv().await.unwrap(), Some(Message::text("hello")));
+}
+
+#[tokio::test]
+async fn test_connect_tls_default_connector_rejects_private_ca() {
+    let (acceptor, _) = native_pair();
+    let addr = native_echo_server(acceptor).await;
+
+    let url = format!("wss://localhost:{}/echo", addr.port());
+    let result = rsws::client::connect_tls(&url).await;
+    assert!(matches!(result, Err(Error::Tls(_))));
+}